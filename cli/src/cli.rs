@@ -0,0 +1,529 @@
+use std::path::PathBuf;
+
+use uuid::Uuid;
+
+pub const USAGE: &str =
+    "Usage: gitmirrors [--url <url>] [--token <token>] [--output table|json] <command>
+
+Options:
+  --url <url>            Server URL, defaults to $GITMIRRORS_URL or http://localhost:4000
+  --token <token>        API token, defaults to $GITMIRRORS_TOKEN
+  -o, --output <format>  table (default) or json, which prints the response data unchanged
+  --help                 Print this help and exit
+
+Commands:
+  repo list [--search <text>] [--tag <tag>] [--host <host>] [--failing] [--overdue]
+  repo show <id>
+  repo add --name <name> --source <git url> --target <git url> --target-key-file <file>
+           --period <seconds> [--source-key-file <file>] [--web-url <url>]
+           [--tag <tag>]... [--team <id>]
+  repo update <id> [--name <name>] [--source <git url>] [--target <git url>]
+           [--source-key-file <file>] [--target-key-file <file>] [--period <seconds>]
+           [--web-url <url>] [--tag <tag>]... [--enable | --disable]
+  repo sync <id>
+  repo delete <id> --yes
+  apply <file.toml|file.yaml> [--dry-run] [--prune]
+  export [--all] [--passphrase-file <file>] [--file <path>]
+  import <file> [--passphrase-file <file>] [--map-user <old>=<new>]...
+           [--map-team <old>=<new>]... [--dry-run]
+  import-org <github|gitlab|gitea> <owner> --target-template <template>
+           --target-key-file <file> --period <seconds> [--api-url <url>]
+           [--token-file <file>] [--match <pattern>] [--include-archived]
+           [--visibility public|private|internal] [--ssh] [--source-key-file <file>]
+           [--tag <tag>]... [--team <id>] [--dry-run]
+  logs [<repository id>] [--type <type>] [--limit <count>] [--follow]
+  stats
+
+--tag can be repeated, on update the given tags replace the current ones.
+apply reconciles the repositories with the file, --prune deletes those not listed.
+export includes keys only with a passphrase, which encrypts them.
+import-org mirrors every matching repository of an organization, group or user, with
+{owner} and {name} replaced in the target template, e.g. git@backup:{owner}/{name}.git.";
+
+pub const DEFAULT_URL: &str = "http://localhost:4000";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+}
+
+impl OutputFormat {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            other => Err(format!(
+                "unknown output format {}, expected table or json",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Args {
+    pub url: Option<String>,
+    pub token: Option<String>,
+    pub output: OutputFormat,
+    pub help: bool,
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Default)]
+pub struct RepositoryFilter {
+    pub search: Option<String>,
+    pub tag: Option<String>,
+    pub host: Option<String>,
+    pub failing: bool,
+    pub overdue: bool,
+}
+
+/// Fields of `repo add` and `repo update`, `None` leaves a field out of the request.
+#[derive(Debug, Default)]
+pub struct RepositoryFields {
+    pub name: Option<String>,
+    pub web_url: Option<String>,
+    pub source: Option<String>,
+    pub source_key_file: Option<PathBuf>,
+    pub target: Option<String>,
+    pub target_key_file: Option<PathBuf>,
+    pub period_seconds: Option<u32>,
+    pub tags: Option<Vec<String>>,
+    pub team_id: Option<Uuid>,
+    pub enabled: Option<bool>,
+}
+
+/// Options of `import-org`.
+#[derive(Debug)]
+pub struct ProviderImport {
+    pub provider: String,
+    pub owner: String,
+    pub api_url: Option<String>,
+    pub token_file: Option<PathBuf>,
+    pub name_pattern: Option<String>,
+    pub include_archived: bool,
+    pub visibility: Option<String>,
+    pub use_ssh: bool,
+    pub target_template: String,
+    pub source_key_file: Option<PathBuf>,
+    pub target_key_file: PathBuf,
+    pub period_seconds: u32,
+    pub tags: Vec<String>,
+    pub team_id: Option<Uuid>,
+    pub dry_run: bool,
+}
+
+#[derive(Debug)]
+pub enum Command {
+    RepoList(RepositoryFilter),
+    RepoShow {
+        id: Uuid,
+    },
+    RepoAdd(RepositoryFields),
+    RepoUpdate {
+        id: Uuid,
+        fields: RepositoryFields,
+    },
+    RepoSync {
+        id: Uuid,
+    },
+    RepoDelete {
+        id: Uuid,
+    },
+    Apply {
+        file: PathBuf,
+        prune: bool,
+        dry_run: bool,
+    },
+    Export {
+        all: bool,
+        passphrase_file: Option<PathBuf>,
+        file: Option<PathBuf>,
+    },
+    Import {
+        file: PathBuf,
+        passphrase_file: Option<PathBuf>,
+        user_map: Vec<(String, String)>,
+        team_map: Vec<(String, String)>,
+        dry_run: bool,
+    },
+    ImportOrg(ProviderImport),
+    Logs {
+        repository_id: Option<Uuid>,
+        types: Option<String>,
+        limit: Option<u32>,
+        follow: bool,
+    },
+    Stats,
+}
+
+impl Args {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+        let mut parsed = Args::default();
+        let mut rest = Vec::new();
+        let mut args = args.into_iter();
+
+        // global options may appear anywhere, everything else belongs to the command
+        while let Some(arg) = args.next() {
+            let (name, inline) = match arg.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            match name {
+                "--url" | "--token" | "--output" | "-o" => {
+                    let value = match inline.or_else(|| args.next()) {
+                        Some(value) => value,
+                        None => return Err(format!("{} requires a value", name)),
+                    };
+                    match name {
+                        "--url" => parsed.url = Some(value),
+                        "--token" => parsed.token = Some(value),
+                        _ => parsed.output = OutputFormat::parse(&value)?,
+                    }
+                }
+                "--help" | "-h" => parsed.help = true,
+                _ => rest.push(arg),
+            }
+        }
+
+        if !rest.is_empty() {
+            parsed.command = Some(Command::parse(&rest)?);
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// Command arguments split into positionals and `--flag [value]` options.
+struct Options {
+    positional: Vec<String>,
+    values: Vec<(String, String)>,
+    switches: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String], with_value: &[&str], switches: &[&str]) -> Result<Options, String> {
+        let mut options = Options {
+            positional: Vec::new(),
+            values: Vec::new(),
+            switches: Vec::new(),
+        };
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                options.positional.push(arg.clone());
+                continue;
+            }
+
+            let (name, inline) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (arg.as_str(), None),
+            };
+            if with_value.contains(&name) {
+                match inline.or_else(|| args.next().cloned()) {
+                    Some(value) => options.values.push((name.to_string(), value)),
+                    None => return Err(format!("{} requires a value", name)),
+                }
+            } else if switches.contains(&name) && inline.is_none() {
+                options.switches.push(name.to_string());
+            } else {
+                return Err(format!("unknown argument {}", arg));
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// The last value given for `name`.
+    fn value(&self, name: &str) -> Option<String> {
+        self.values
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.clone())
+    }
+
+    fn all_values(&self, name: &str) -> Vec<String> {
+        self.values
+            .iter()
+            .filter(|(n, _)| n == name)
+            .map(|(_, v)| v.clone())
+            .collect()
+    }
+
+    fn switch(&self, name: &str) -> bool {
+        self.switches.iter().any(|s| s == name)
+    }
+
+    fn parsed<T>(&self, name: &str) -> Result<Option<T>, String>
+    where
+        T: std::str::FromStr,
+    {
+        self.value(name)
+            .map(|v| {
+                v.parse()
+                    .map_err(|_| format!("invalid value {} for {}", v, name))
+            })
+            .transpose()
+    }
+
+    /// Fails unless exactly `count` positionals were given.
+    fn expect_positional(&self, count: usize, usage: &str) -> Result<(), String> {
+        if self.positional.len() == count {
+            Ok(())
+        } else {
+            Err(format!("usage: gitmirrors {}", usage))
+        }
+    }
+}
+
+fn parse_id(value: &str) -> Result<Uuid, String> {
+    Uuid::parse_str(value).map_err(|_| format!("invalid id {}", value))
+}
+
+/// `old=new` pairs of `--map-user` and `--map-team`.
+fn parse_mappings(options: &Options, name: &str) -> Result<Vec<(String, String)>, String> {
+    options
+        .all_values(name)
+        .into_iter()
+        .map(|mapping| match mapping.split_once('=') {
+            Some((old, new)) if !old.is_empty() && !new.is_empty() => {
+                Ok((old.to_string(), new.to_string()))
+            }
+            _ => Err(format!(
+                "invalid value {} for {}, expected old=new",
+                mapping, name
+            )),
+        })
+        .collect()
+}
+
+const FIELD_OPTIONS: [&str; 9] = [
+    "--name",
+    "--web-url",
+    "--source",
+    "--source-key-file",
+    "--target",
+    "--target-key-file",
+    "--period",
+    "--tag",
+    "--team",
+];
+
+impl RepositoryFields {
+    fn from_options(options: &Options) -> Result<Self, String> {
+        let tags = options.all_values("--tag");
+        let enabled = match (options.switch("--enable"), options.switch("--disable")) {
+            (true, true) => return Err("--enable and --disable conflict".to_string()),
+            (true, false) => Some(true),
+            (false, true) => Some(false),
+            (false, false) => None,
+        };
+
+        Ok(RepositoryFields {
+            name: options.value("--name"),
+            web_url: options.value("--web-url"),
+            source: options.value("--source"),
+            source_key_file: options.value("--source-key-file").map(PathBuf::from),
+            target: options.value("--target"),
+            target_key_file: options.value("--target-key-file").map(PathBuf::from),
+            period_seconds: options.parsed("--period")?,
+            tags: Some(tags).filter(|t| !t.is_empty()),
+            team_id: options
+                .value("--team")
+                .as_deref()
+                .map(parse_id)
+                .transpose()?,
+            enabled,
+        })
+    }
+}
+
+impl Command {
+    fn parse(args: &[String]) -> Result<Command, String> {
+        let (group, action) = (
+            args[0].as_str(),
+            args.get(1).map(String::as_str).unwrap_or_default(),
+        );
+
+        let command = match (group, action) {
+            ("repo", "list") => {
+                let options = Options::parse(
+                    &args[2..],
+                    &["--search", "--tag", "--host"],
+                    &["--failing", "--overdue"],
+                )?;
+                options.expect_positional(0, "repo list")?;
+                Command::RepoList(RepositoryFilter {
+                    search: options.value("--search"),
+                    tag: options.value("--tag"),
+                    host: options.value("--host"),
+                    failing: options.switch("--failing"),
+                    overdue: options.switch("--overdue"),
+                })
+            }
+            ("repo", "show" | "sync") => {
+                let options = Options::parse(&args[2..], &[], &[])?;
+                options.expect_positional(1, &format!("repo {} <id>", action))?;
+                let id = parse_id(&options.positional[0])?;
+                match action {
+                    "show" => Command::RepoShow { id },
+                    _ => Command::RepoSync { id },
+                }
+            }
+            ("repo", "add") => {
+                let options = Options::parse(&args[2..], &FIELD_OPTIONS, &[])?;
+                options.expect_positional(0, "repo add --name <name> ...")?;
+                let fields = RepositoryFields::from_options(&options)?;
+                let missing: Vec<&str> = [
+                    ("--name", fields.name.is_none()),
+                    ("--source", fields.source.is_none()),
+                    ("--target", fields.target.is_none()),
+                    ("--target-key-file", fields.target_key_file.is_none()),
+                    ("--period", fields.period_seconds.is_none()),
+                ]
+                .into_iter()
+                .filter_map(|(name, missing)| missing.then_some(name))
+                .collect();
+                if !missing.is_empty() {
+                    return Err(format!("repo add requires {}", missing.join(", ")));
+                }
+                Command::RepoAdd(fields)
+            }
+            ("repo", "update") => {
+                let options =
+                    Options::parse(&args[2..], &FIELD_OPTIONS, &["--enable", "--disable"])?;
+                options.expect_positional(1, "repo update <id> [options]")?;
+                if options.value("--team").is_some() {
+                    return Err("the team of a repository changes through a transfer".to_string());
+                }
+                Command::RepoUpdate {
+                    id: parse_id(&options.positional[0])?,
+                    fields: RepositoryFields::from_options(&options)?,
+                }
+            }
+            ("repo", "delete") => {
+                let options = Options::parse(&args[2..], &[], &["--yes"])?;
+                options.expect_positional(1, "repo delete <id> --yes")?;
+                let id = parse_id(&options.positional[0])?;
+                if !options.switch("--yes") {
+                    return Err(format!(
+                        "deleting repository {} removes its mirror and logs, pass --yes to confirm",
+                        id
+                    ));
+                }
+                Command::RepoDelete { id }
+            }
+            ("apply", _) => {
+                let options = Options::parse(&args[1..], &[], &["--dry-run", "--prune"])?;
+                options.expect_positional(1, "apply <file> [--dry-run] [--prune]")?;
+                Command::Apply {
+                    file: PathBuf::from(&options.positional[0]),
+                    prune: options.switch("--prune"),
+                    dry_run: options.switch("--dry-run"),
+                }
+            }
+            ("export", _) => {
+                let options =
+                    Options::parse(&args[1..], &["--passphrase-file", "--file"], &["--all"])?;
+                options.expect_positional(0, "export [options]")?;
+                Command::Export {
+                    all: options.switch("--all"),
+                    passphrase_file: options.value("--passphrase-file").map(PathBuf::from),
+                    file: options.value("--file").map(PathBuf::from),
+                }
+            }
+            ("import", _) => {
+                let options = Options::parse(
+                    &args[1..],
+                    &["--passphrase-file", "--map-user", "--map-team"],
+                    &["--dry-run"],
+                )?;
+                options.expect_positional(1, "import <file> [options]")?;
+                Command::Import {
+                    file: PathBuf::from(&options.positional[0]),
+                    passphrase_file: options.value("--passphrase-file").map(PathBuf::from),
+                    user_map: parse_mappings(&options, "--map-user")?,
+                    team_map: parse_mappings(&options, "--map-team")?,
+                    dry_run: options.switch("--dry-run"),
+                }
+            }
+            ("import-org", _) => {
+                let options = Options::parse(
+                    &args[1..],
+                    &[
+                        "--target-template",
+                        "--target-key-file",
+                        "--period",
+                        "--api-url",
+                        "--token-file",
+                        "--match",
+                        "--visibility",
+                        "--source-key-file",
+                        "--tag",
+                        "--team",
+                    ],
+                    &["--include-archived", "--ssh", "--dry-run"],
+                )?;
+                options.expect_positional(2, "import-org <provider> <owner> [options]")?;
+                let (Some(target_template), Some(target_key_file), Some(period_seconds)) = (
+                    options.value("--target-template"),
+                    options.value("--target-key-file"),
+                    options.parsed("--period")?,
+                ) else {
+                    return Err(
+                        "import-org requires --target-template, --target-key-file and --period"
+                            .to_string(),
+                    );
+                };
+                Command::ImportOrg(ProviderImport {
+                    provider: options.positional[0].clone(),
+                    owner: options.positional[1].clone(),
+                    api_url: options.value("--api-url"),
+                    token_file: options.value("--token-file").map(PathBuf::from),
+                    name_pattern: options.value("--match"),
+                    include_archived: options.switch("--include-archived"),
+                    visibility: options.value("--visibility"),
+                    use_ssh: options.switch("--ssh"),
+                    target_template,
+                    source_key_file: options.value("--source-key-file").map(PathBuf::from),
+                    target_key_file: PathBuf::from(target_key_file),
+                    period_seconds,
+                    tags: options.all_values("--tag"),
+                    team_id: options
+                        .value("--team")
+                        .as_deref()
+                        .map(parse_id)
+                        .transpose()?,
+                    dry_run: options.switch("--dry-run"),
+                })
+            }
+            ("logs", _) => {
+                let options = Options::parse(&args[1..], &["--type", "--limit"], &["--follow"])?;
+                if options.positional.len() > 1 {
+                    return Err("usage: gitmirrors logs [<repository id>] [options]".to_string());
+                }
+                Command::Logs {
+                    repository_id: options
+                        .positional
+                        .first()
+                        .map(|id| parse_id(id))
+                        .transpose()?,
+                    types: options.value("--type"),
+                    limit: options.parsed("--limit")?,
+                    follow: options.switch("--follow"),
+                }
+            }
+            ("stats", _) => {
+                Options::parse(&args[1..], &[], &[])?.expect_positional(0, "stats")?;
+                Command::Stats
+            }
+            _ => return Err(format!("unknown command {}", args.join(" "))),
+        };
+
+        Ok(command)
+    }
+}
@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use reqwest::Method;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+const USER_AGENT: &str = concat!("gitmirrors-cli/", env!("CARGO_PKG_VERSION"));
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The `{ success, message, data }` envelope every API route responds with.
+#[derive(Deserialize)]
+struct ApiResponse {
+    success: bool,
+    message: String,
+    data: Option<Value>,
+}
+
+/// Client for the `/api` routes, authenticated with an API token.
+pub struct ApiClient {
+    base_url: String,
+    token: String,
+    http: Client,
+}
+
+impl ApiClient {
+    pub fn new(base_url: &str, token: String) -> Result<Self, String> {
+        let http = Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+
+        Ok(ApiClient {
+            base_url: format!("{}/api", base_url.trim_end_matches('/')),
+            token,
+            http,
+        })
+    }
+
+    /// Sends the request and unwraps the envelope, returning its `data` or its `message`
+    /// as the error.
+    pub fn request(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        body: Option<&Value>,
+    ) -> Result<Value, String> {
+        self.request_with_headers(method, path, query, &[], body)
+    }
+
+    /// `request` with extra headers, for values that should stay out of the URL.
+    pub fn request_with_headers(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        headers: &[(&str, &str)],
+        body: Option<&Value>,
+    ) -> Result<Value, String> {
+        let url = format!("{}{}", self.base_url, path);
+        let mut request = self
+            .http
+            .request(method, &url)
+            .bearer_auth(&self.token)
+            .query(query);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+
+        let response = request
+            .send()
+            .map_err(|e| format!("Request to {} failed: {}", url, e))?;
+        let status = response.status();
+
+        match response.json::<ApiResponse>() {
+            Ok(envelope) if envelope.success => Ok(envelope.data.unwrap_or(Value::Null)),
+            Ok(envelope) => Err(format!("{} (HTTP {})", envelope.message, status.as_u16())),
+            // Rocket's own error pages, e.g. for unknown routes, are not enveloped
+            Err(_) => Err(format!("Unexpected response from server (HTTP {})", status)),
+        }
+    }
+}
+
+/// Reads response data into the shape a table is printed from.
+pub fn decode<T: DeserializeOwned>(data: Value) -> Result<T, String> {
+    serde_json::from_value(data).map_err(|e| format!("Unexpected response data: {}", e))
+}
@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use reqwest::Method;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::cli::OutputFormat;
+use crate::client::{ApiClient, decode};
+use crate::commands::CommandResult;
+use crate::manifest::Manifest;
+use crate::output::print_json;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApplyResponse {
+    dry_run: bool,
+    plan: Vec<PlannedChange>,
+    created: usize,
+    updated: usize,
+    deleted: usize,
+    unchanged: usize,
+}
+
+#[derive(Deserialize)]
+struct PlannedChange {
+    action: String,
+    name: String,
+    changes: BTreeMap<String, FieldChange>,
+}
+
+#[derive(Deserialize)]
+struct FieldChange {
+    before: Value,
+    after: Value,
+}
+
+fn print_plan(response: &ApplyResponse) {
+    for change in response.plan.iter().filter(|c| c.action != "unchanged") {
+        let symbol = match change.action.as_str() {
+            "create" => "+",
+            "delete" => "-",
+            _ => "~",
+        };
+        println!("{} {} ({})", symbol, change.name, change.action);
+
+        for (field, value) in &change.changes {
+            if change.action == "create" {
+                println!("    {}: {}", field, value.after);
+            } else {
+                println!("    {}: {} -> {}", field, value.before, value.after);
+            }
+        }
+    }
+
+    println!(
+        "{}: {} to create, {} to update, {} to delete, {} unchanged.",
+        if response.dry_run { "Plan" } else { "Applied" },
+        response.created,
+        response.updated,
+        response.deleted,
+        response.unchanged
+    );
+    if response.dry_run {
+        println!("Dry run, nothing was changed.");
+    }
+}
+
+/// Reconciles the repositories on the server with a configuration file.
+pub fn apply(
+    client: &ApiClient,
+    file: &Path,
+    prune: bool,
+    dry_run: bool,
+    output: OutputFormat,
+) -> CommandResult {
+    let manifest = Manifest::load(file)?;
+    let base_dir = file.parent().unwrap_or(Path::new("."));
+    let body = manifest.to_request(base_dir)?;
+
+    let data = client.request(
+        Method::POST,
+        "/repository/apply",
+        &[
+            ("dry_run", dry_run.to_string()),
+            ("prune", prune.to_string()),
+        ],
+        Some(&body),
+    )?;
+
+    if output == OutputFormat::Json {
+        print_json(&data);
+    } else {
+        print_plan(&decode(data)?);
+    }
+    Ok(())
+}
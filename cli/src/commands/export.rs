@@ -0,0 +1,186 @@
+use std::path::Path;
+
+use reqwest::Method;
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+
+use crate::cli::OutputFormat;
+use crate::client::{ApiClient, decode};
+use crate::commands::CommandResult;
+use crate::output::print_json;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportResponse {
+    dry_run: bool,
+    #[serde(default)]
+    settings_imported: bool,
+    imported: Vec<ImportedRepository>,
+    conflicts: Vec<ImportConflict>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportedRepository {
+    name: String,
+    owner: Option<String>,
+    team: Option<String>,
+    needs_credentials: bool,
+}
+
+#[derive(Deserialize)]
+struct ImportConflict {
+    name: String,
+    owner: Option<String>,
+    team: Option<String>,
+    reason: String,
+}
+
+fn describe_owner(owner: &Option<String>, team: &Option<String>) -> String {
+    match (owner, team) {
+        (_, Some(team)) => format!("team {}", team),
+        (Some(owner), None) => format!("user {}", owner),
+        (None, None) => "unknown owner".to_string(),
+    }
+}
+
+/// Passphrases are read from files like keys, without the trailing newline.
+fn read_passphrase(path: &Path) -> Result<String, String> {
+    let passphrase = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(passphrase.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn mappings(pairs: &[(String, String)]) -> Value {
+    Value::Object(
+        pairs
+            .iter()
+            .map(|(old, new)| (old.clone(), json!(new)))
+            .collect::<Map<String, Value>>(),
+    )
+}
+
+/// Writes the export document to `file`, or to stdout.
+pub fn export(
+    client: &ApiClient,
+    all: bool,
+    passphrase_file: Option<&Path>,
+    file: Option<&Path>,
+    output: OutputFormat,
+) -> CommandResult {
+    let passphrase = passphrase_file.map(read_passphrase).transpose()?;
+    let query: Vec<(&str, String)> = if all {
+        vec![("all", "true".to_string())]
+    } else {
+        Vec::new()
+    };
+    let headers: Vec<(&str, &str)> = passphrase
+        .as_deref()
+        .map(|p| ("X-Export-Passphrase", p))
+        .into_iter()
+        .collect();
+
+    let document = client.request_with_headers(Method::GET, "/export", &query, &headers, None)?;
+    let Some(file) = file else {
+        print_json(&document);
+        return Ok(());
+    };
+
+    let content = serde_json::to_string_pretty(&document).expect("Failed to serialize export");
+    std::fs::write(file, content + "\n")
+        .map_err(|e| format!("Failed to write {}: {}", file.display(), e))?;
+
+    let count = document["repositories"].as_array().map_or(0, Vec::len);
+    let encrypted = !document["encryption"].is_null();
+    if output == OutputFormat::Json {
+        print_json(&json!({
+            "file": file,
+            "repositories": count,
+            "encrypted": encrypted,
+        }));
+    } else {
+        println!(
+            "Exported {} repositories to {}, {}",
+            count,
+            file.display(),
+            if encrypted {
+                "keys encrypted"
+            } else {
+                "without keys"
+            }
+        );
+    }
+    Ok(())
+}
+
+fn print_import(response: &ImportResponse) {
+    for repo in &response.imported {
+        println!(
+            "+ {} ({})",
+            repo.name,
+            describe_owner(&repo.owner, &repo.team)
+        );
+        if repo.needs_credentials {
+            println!("    no keys in the export, imported disabled");
+        }
+    }
+    for conflict in &response.conflicts {
+        println!(
+            "! {} ({}): {}",
+            conflict.name,
+            describe_owner(&conflict.owner, &conflict.team),
+            conflict.reason
+        );
+    }
+
+    if response.settings_imported {
+        println!("~ log retention and stale threshold settings");
+    }
+
+    println!(
+        "{}: {} to import, {} skipped.",
+        if response.dry_run { "Plan" } else { "Imported" },
+        response.imported.len(),
+        response.conflicts.len()
+    );
+    if response.dry_run {
+        println!("Dry run, nothing was changed.");
+    }
+}
+
+/// Imports an export document, mapping its users and teams to those of this server.
+pub fn import(
+    client: &ApiClient,
+    file: &Path,
+    passphrase_file: Option<&Path>,
+    user_map: &[(String, String)],
+    team_map: &[(String, String)],
+    dry_run: bool,
+    output: OutputFormat,
+) -> CommandResult {
+    let content = std::fs::read_to_string(file)
+        .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+    let document: Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Invalid export {}: {}", file.display(), e))?;
+    let passphrase = passphrase_file.map(read_passphrase).transpose()?;
+
+    let body = json!({
+        "document": document,
+        "passphrase": passphrase,
+        "userMap": mappings(user_map),
+        "teamMap": mappings(team_map),
+    });
+    let data = client.request(
+        Method::POST,
+        "/import",
+        &[("dry_run", dry_run.to_string())],
+        Some(&body),
+    )?;
+
+    if output == OutputFormat::Json {
+        print_json(&data);
+    } else {
+        print_import(&decode(data)?);
+    }
+    Ok(())
+}
@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+use std::thread::sleep;
+use std::time::Duration;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::Method;
+use serde::Deserialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::cli::OutputFormat;
+use crate::client::{ApiClient, decode};
+use crate::commands::CommandResult;
+use crate::output::format_time;
+
+const DEFAULT_LIMIT: u32 = 20;
+const FOLLOW_INTERVAL: Duration = Duration::from_secs(2);
+/// Page size while following, new logs are fetched until the previous newest one.
+const FOLLOW_PAGE_LIMIT: u32 = 500;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LogPage {
+    repository_logs: Vec<Value>,
+    next_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LogEntry {
+    #[serde(rename = "type")]
+    log_type: String,
+    message: String,
+    created_at: DateTime<Utc>,
+    /// Only set by the log feed across repositories.
+    repository_name: Option<String>,
+}
+
+struct LogQuery<'a> {
+    path: String,
+    types: Option<&'a str>,
+}
+
+impl LogQuery<'_> {
+    fn fetch(
+        &self,
+        client: &ApiClient,
+        limit: u32,
+        since: Option<DateTime<Utc>>,
+        cursor: Option<String>,
+    ) -> Result<LogPage, String> {
+        let mut query = vec![("limit", limit.to_string())];
+        if let Some(types) = self.types {
+            query.push(("type", types.to_string()));
+        }
+        if let Some(since) = since {
+            query.push(("since", since.to_rfc3339_opts(SecondsFormat::Micros, true)));
+        }
+        if let Some(cursor) = cursor {
+            query.push(("cursor", cursor));
+        }
+
+        decode(client.request(Method::GET, &self.path, &query, None)?)
+    }
+}
+
+fn print_entry(entry: Value, output: OutputFormat) -> Result<(), String> {
+    if output == OutputFormat::Json {
+        println!("{}", entry);
+        return Ok(());
+    }
+
+    let log: LogEntry = decode(entry)?;
+    let time = format_time(Some(log.created_at));
+    match &log.repository_name {
+        Some(name) => println!(
+            "{}  {}  {}  {}",
+            time,
+            name,
+            log.log_type,
+            log.message.trim_end()
+        ),
+        None => println!("{}  {}  {}", time, log.log_type, log.message.trim_end()),
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LogKey {
+    id: Uuid,
+    created_at: DateTime<Utc>,
+}
+
+/// Position of the newest printed log. `since` also returns logs sharing its timestamp,
+/// those are skipped by id.
+#[derive(Default)]
+struct Position {
+    newest: Option<DateTime<Utc>>,
+    seen_at_newest: HashSet<Uuid>,
+}
+
+impl Position {
+    /// Records the log and returns whether it was not printed before.
+    fn advance(&mut self, entry: &Value) -> Result<bool, String> {
+        let key: LogKey = decode(entry.clone())?;
+        if self.newest == Some(key.created_at) {
+            return Ok(self.seen_at_newest.insert(key.id));
+        }
+        if self.newest.is_some_and(|newest| key.created_at < newest) {
+            return Ok(false);
+        }
+
+        self.newest = Some(key.created_at);
+        self.seen_at_newest.clear();
+        self.seen_at_newest.insert(key.id);
+        Ok(true)
+    }
+}
+
+/// Prints the newest logs oldest first, then keeps polling for new ones with `follow`.
+/// JSON output is one log object per line so it can be streamed.
+pub fn logs(
+    client: &ApiClient,
+    repository_id: Option<Uuid>,
+    types: Option<String>,
+    limit: Option<u32>,
+    follow: bool,
+    output: OutputFormat,
+) -> CommandResult {
+    let query = LogQuery {
+        path: match repository_id {
+            Some(id) => format!("/repository/{}/logs", id),
+            None => "/repository/logs".to_string(),
+        },
+        types: types.as_deref(),
+    };
+
+    let page = query.fetch(client, limit.unwrap_or(DEFAULT_LIMIT), None, None)?;
+    let mut position = Position::default();
+    for entry in page.repository_logs.into_iter().rev() {
+        position.advance(&entry)?;
+        print_entry(entry, output)?;
+    }
+
+    if !follow {
+        return Ok(());
+    }
+
+    loop {
+        sleep(FOLLOW_INTERVAL);
+
+        let mut entries = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = query.fetch(client, FOLLOW_PAGE_LIMIT, position.newest, cursor)?;
+            entries.extend(page.repository_logs);
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        for entry in entries.into_iter().rev() {
+            if position.advance(&entry)? {
+                print_entry(entry, output)?;
+            }
+        }
+    }
+}
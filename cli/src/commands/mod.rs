@@ -0,0 +1,70 @@
+pub mod apply;
+pub mod export;
+pub mod logs;
+pub mod provider;
+pub mod repo;
+pub mod stats;
+
+use crate::cli::{Command, OutputFormat};
+use crate::client::ApiClient;
+
+pub type CommandResult = Result<(), String>;
+
+/// Runs the command and prints errors to stderr. Returns the exit code.
+pub fn run(client: &ApiClient, command: Command, output: OutputFormat) -> i32 {
+    let result = match command {
+        Command::RepoList(filter) => repo::list(client, &filter, output),
+        Command::RepoShow { id } => repo::show(client, id, output),
+        Command::RepoAdd(fields) => repo::add(client, &fields, output),
+        Command::RepoUpdate { id, fields } => repo::update(client, id, &fields, output),
+        Command::RepoSync { id } => repo::sync(client, id, output),
+        Command::RepoDelete { id } => repo::delete(client, id, output),
+        Command::Apply {
+            file,
+            prune,
+            dry_run,
+        } => apply::apply(client, &file, prune, dry_run, output),
+        Command::Export {
+            all,
+            passphrase_file,
+            file,
+        } => export::export(
+            client,
+            all,
+            passphrase_file.as_deref(),
+            file.as_deref(),
+            output,
+        ),
+        Command::Import {
+            file,
+            passphrase_file,
+            user_map,
+            team_map,
+            dry_run,
+        } => export::import(
+            client,
+            &file,
+            passphrase_file.as_deref(),
+            &user_map,
+            &team_map,
+            dry_run,
+            output,
+        ),
+        Command::ImportOrg(import) => provider::import_org(client, &import, output),
+        Command::Logs {
+            repository_id,
+            types,
+            limit,
+            follow,
+        } => logs::logs(client, repository_id, types, limit, follow, output),
+        Command::Stats => stats::stats(client, output),
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            1
+        }
+    }
+}
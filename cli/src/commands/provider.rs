@@ -0,0 +1,111 @@
+use reqwest::Method;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::cli::{OutputFormat, ProviderImport};
+use crate::client::{ApiClient, decode};
+use crate::commands::CommandResult;
+use crate::commands::repo::read_key;
+use crate::output::{Table, print_json};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProviderImportResponse {
+    dry_run: bool,
+    listed: usize,
+    filtered: usize,
+    created: Vec<ImportedRepository>,
+    skipped: Vec<SkippedRepository>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportedRepository {
+    name: String,
+    git_source: String,
+    git_target: String,
+}
+
+#[derive(Deserialize)]
+struct SkippedRepository {
+    name: String,
+    reason: String,
+}
+
+fn print_import(response: &ProviderImportResponse) {
+    if !response.created.is_empty() {
+        let mut table = Table::new(&["NAME", "SOURCE", "TARGET"]);
+        for repo in &response.created {
+            table.row(vec![
+                repo.name.clone(),
+                repo.git_source.clone(),
+                repo.git_target.clone(),
+            ]);
+        }
+        table.print();
+    }
+    for skipped in &response.skipped {
+        println!("! {}: {}", skipped.name, skipped.reason);
+    }
+
+    println!(
+        "{}: {} listed, {} filtered out, {} to import, {} skipped.",
+        if response.dry_run { "Plan" } else { "Imported" },
+        response.listed,
+        response.filtered,
+        response.created.len(),
+        response.skipped.len()
+    );
+    if response.dry_run {
+        println!("Dry run, nothing was changed.");
+    }
+}
+
+/// Mirrors the repositories of a GitHub, GitLab or Gitea organization.
+pub fn import_org(
+    client: &ApiClient,
+    import: &ProviderImport,
+    output: OutputFormat,
+) -> CommandResult {
+    let token = import
+        .token_file
+        .as_deref()
+        .map(read_key)
+        .transpose()?
+        .map(|token| token.trim().to_string());
+    let source_key = import
+        .source_key_file
+        .as_deref()
+        .map(read_key)
+        .transpose()?;
+
+    let body = json!({
+        "provider": import.provider,
+        "apiUrl": import.api_url,
+        "owner": import.owner,
+        "token": token,
+        "namePattern": import.name_pattern,
+        "includeArchived": import.include_archived,
+        "visibility": import.visibility,
+        "useSsh": import.use_ssh,
+        "targetTemplate": import.target_template,
+        "gitSourceSecretKey": source_key,
+        "gitTargetSecretKey": read_key(&import.target_key_file)?,
+        "gitClonePeriodSeconds": import.period_seconds,
+        "teamId": import.team_id,
+        "tags": import.tags,
+    });
+    let data = client.request(
+        Method::POST,
+        "/repository/provider-import",
+        &[("dry_run", import.dry_run.to_string())],
+        Some(&body),
+    )?;
+
+    if output == OutputFormat::Json {
+        print_json(&data);
+    } else {
+        print_import(&decode(data)?);
+    }
+    Ok(())
+}
@@ -0,0 +1,248 @@
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use reqwest::Method;
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use uuid::Uuid;
+
+use crate::cli::{OutputFormat, RepositoryFields, RepositoryFilter};
+use crate::client::{ApiClient, decode};
+use crate::commands::CommandResult;
+use crate::output::{Table, format_period, format_time, or_dash, print_fields, print_json};
+
+/// Largest page the server returns, `repo list` fetches every page.
+const PAGE_LIMIT: usize = 500;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Repository {
+    id: Uuid,
+    name: String,
+    url: Option<String>,
+    is_enabled: bool,
+    git_source: String,
+    git_target: String,
+    git_clone_period_seconds: i64,
+    last_clone_at: Option<DateTime<Utc>>,
+    last_sync_status: Option<String>,
+    health_state: String,
+    sync_requested_at: Option<DateTime<Utc>>,
+    team_id: Option<Uuid>,
+    tags: Vec<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl Repository {
+    fn status(&self) -> &str {
+        if !self.is_enabled {
+            "disabled"
+        } else {
+            self.last_sync_status.as_deref().unwrap_or("never synced")
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RepositoryPage {
+    repositories: Vec<Value>,
+    total: usize,
+}
+
+fn print_repository(data: Value, key: &str, output: OutputFormat) -> CommandResult {
+    if output == OutputFormat::Json {
+        print_json(&data);
+        return Ok(());
+    }
+
+    let repo: Repository = decode(data.get(key).cloned().unwrap_or_default())?;
+    print_fields(&[
+        ("ID", repo.id.to_string()),
+        ("Name", repo.name.clone()),
+        ("URL", or_dash(repo.url.as_deref())),
+        ("Source", repo.git_source.clone()),
+        ("Target", repo.git_target.clone()),
+        ("Period", format_period(repo.git_clone_period_seconds)),
+        ("Status", repo.status().to_string()),
+        ("Health", repo.health_state.clone()),
+        ("Last clone", format_time(repo.last_clone_at)),
+        ("Sync requested", format_time(repo.sync_requested_at)),
+        (
+            "Team",
+            or_dash(repo.team_id.map(|t| t.to_string()).as_deref()),
+        ),
+        ("Tags", or_dash(Some(repo.tags.join(", ").as_str()))),
+        ("Created", format_time(Some(repo.created_at))),
+        ("Updated", format_time(Some(repo.updated_at))),
+    ]);
+    Ok(())
+}
+
+pub fn list(client: &ApiClient, filter: &RepositoryFilter, output: OutputFormat) -> CommandResult {
+    let mut query: Vec<(&str, String)> = vec![("limit", PAGE_LIMIT.to_string())];
+    for (name, value) in [
+        ("search", &filter.search),
+        ("tag", &filter.tag),
+        ("host", &filter.host),
+    ] {
+        if let Some(value) = value {
+            query.push((name, value.clone()));
+        }
+    }
+    if filter.failing {
+        query.push(("failing", "true".to_string()));
+    }
+    if filter.overdue {
+        query.push(("overdue", "true".to_string()));
+    }
+
+    let mut repositories = Vec::new();
+    loop {
+        let mut page_query = query.clone();
+        page_query.push(("offset", repositories.len().to_string()));
+        let page: RepositoryPage =
+            decode(client.request(Method::GET, "/repository", &page_query, None)?)?;
+
+        let last_page = page.repositories.len() < PAGE_LIMIT;
+        repositories.extend(page.repositories);
+        if last_page || repositories.len() >= page.total {
+            break;
+        }
+    }
+
+    if output == OutputFormat::Json {
+        print_json(&json!({
+            "total": repositories.len(),
+            "repositories": repositories,
+        }));
+        return Ok(());
+    }
+
+    let mut table = Table::new(&[
+        "ID",
+        "NAME",
+        "STATUS",
+        "HEALTH",
+        "LAST CLONE",
+        "PERIOD",
+        "TAGS",
+    ]);
+    for repo in repositories {
+        let repo: Repository = decode(repo)?;
+        table.row(vec![
+            repo.id.to_string(),
+            repo.name.clone(),
+            repo.status().to_string(),
+            repo.health_state.clone(),
+            format_time(repo.last_clone_at),
+            format_period(repo.git_clone_period_seconds),
+            repo.tags.join(","),
+        ]);
+    }
+    table.print();
+    Ok(())
+}
+
+pub fn show(client: &ApiClient, id: Uuid, output: OutputFormat) -> CommandResult {
+    let data = client.request(Method::GET, &format!("/repository/{}", id), &[], None)?;
+    print_repository(data, "repository", output)
+}
+
+/// Keys are read from files so they stay out of the shell history and process list.
+pub fn read_key(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+/// Request body with the given fields under their API names.
+fn fields_body(fields: &RepositoryFields) -> Result<Value, String> {
+    let mut body = Map::new();
+    let mut set = |key: &str, value: Value| {
+        body.insert(key.to_string(), value);
+    };
+
+    if let Some(name) = &fields.name {
+        set("name", json!(name));
+    }
+    if let Some(url) = &fields.web_url {
+        set("url", json!(url));
+    }
+    if let Some(source) = &fields.source {
+        set("gitSource", json!(source));
+    }
+    if let Some(path) = &fields.source_key_file {
+        set("gitSourceSecretKey", json!(read_key(path)?));
+    }
+    if let Some(target) = &fields.target {
+        set("gitTarget", json!(target));
+    }
+    if let Some(path) = &fields.target_key_file {
+        set("gitTargetSecretKey", json!(read_key(path)?));
+    }
+    if let Some(period) = fields.period_seconds {
+        set("gitClonePeriodSeconds", json!(period));
+    }
+    if let Some(tags) = &fields.tags {
+        set("tags", json!(tags));
+    }
+    if let Some(team_id) = fields.team_id {
+        set("teamId", json!(team_id));
+    }
+    if let Some(enabled) = fields.enabled {
+        set("isEnabled", json!(enabled));
+    }
+
+    Ok(Value::Object(body))
+}
+
+pub fn add(client: &ApiClient, fields: &RepositoryFields, output: OutputFormat) -> CommandResult {
+    let mut body = fields_body(fields)?;
+    // the add form requires the web URL field, empty means none
+    if let Some(body) = body.as_object_mut() {
+        body.entry("url").or_insert_with(|| json!(""));
+    }
+
+    let data = client.request(Method::POST, "/repository", &[], Some(&body))?;
+    print_repository(data, "createdRepository", output)
+}
+
+pub fn update(
+    client: &ApiClient,
+    id: Uuid,
+    fields: &RepositoryFields,
+    output: OutputFormat,
+) -> CommandResult {
+    let body = fields_body(fields)?;
+    if body.as_object().is_some_and(Map::is_empty) {
+        return Err("nothing to update, pass at least one field".to_string());
+    }
+
+    let data = client.request(
+        Method::PATCH,
+        &format!("/repository/{}", id),
+        &[],
+        Some(&body),
+    )?;
+    print_repository(data, "repository", output)
+}
+
+pub fn sync(client: &ApiClient, id: Uuid, output: OutputFormat) -> CommandResult {
+    let data = client.request(Method::POST, &format!("/repository/{}/sync", id), &[], None)?;
+    if output == OutputFormat::Json {
+        print_json(&data);
+    } else {
+        println!("Sync of repository {} requested", id);
+    }
+    Ok(())
+}
+
+pub fn delete(client: &ApiClient, id: Uuid, output: OutputFormat) -> CommandResult {
+    let data = client.request(Method::DELETE, &format!("/repository/{}", id), &[], None)?;
+    if output == OutputFormat::Json {
+        print_json(&data);
+    } else {
+        println!("Repository {} deleted", id);
+    }
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use reqwest::Method;
+use serde::Deserialize;
+
+use crate::cli::OutputFormat;
+use crate::client::{ApiClient, decode};
+use crate::commands::CommandResult;
+use crate::output::{Table, format_time, print_fields, print_json};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DashboardResponse {
+    dashboard: Dashboard,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Dashboard {
+    total_repositories: i64,
+    enabled: i64,
+    disabled: i64,
+    last_cloned_repos: Vec<ClonedRepository>,
+    daily_logs: Vec<DailyCount>,
+    daily_error_logs: Vec<DailyCount>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ClonedRepository {
+    name: String,
+    last_clone_at: Option<DateTime<Utc>>,
+    last_sync_status: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DailyCount {
+    day: NaiveDate,
+    count: i64,
+}
+
+/// The numbers of the web dashboard: repository counts, logs of the last week and the
+/// most recently cloned repositories.
+pub fn stats(client: &ApiClient, output: OutputFormat) -> CommandResult {
+    let data = client.request(Method::GET, "/aggregate/dashboard", &[], None)?;
+    if output == OutputFormat::Json {
+        print_json(&data);
+        return Ok(());
+    }
+
+    let dashboard = decode::<DashboardResponse>(data)?.dashboard;
+
+    print_fields(&[
+        ("Repositories", dashboard.total_repositories.to_string()),
+        ("Enabled", dashboard.enabled.to_string()),
+        ("Disabled", dashboard.disabled.to_string()),
+    ]);
+
+    println!();
+    let mut days = Table::new(&["DAY", "LOGS", "ERRORS"]);
+    for day in &dashboard.daily_logs {
+        let errors = dashboard
+            .daily_error_logs
+            .iter()
+            .find(|e| e.day == day.day)
+            .map(|e| e.count)
+            .unwrap_or(0);
+        days.row(vec![
+            day.day.to_string(),
+            day.count.to_string(),
+            errors.to_string(),
+        ]);
+    }
+    days.print();
+
+    if !dashboard.last_cloned_repos.is_empty() {
+        println!();
+        let mut recent = Table::new(&["LAST CLONED", "AT", "STATUS"]);
+        for repo in dashboard.last_cloned_repos {
+            recent.row(vec![
+                repo.name,
+                format_time(repo.last_clone_at),
+                repo.last_sync_status.unwrap_or_else(|| "-".to_string()),
+            ]);
+        }
+        recent.print();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,55 @@
+mod cli;
+mod client;
+mod commands;
+mod manifest;
+mod output;
+
+use cli::{Args, DEFAULT_URL, USAGE};
+use client::ApiClient;
+
+fn main() {
+    // exit quietly when piped into `head` instead of panicking on the closed stdout
+    #[cfg(unix)]
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if args.help {
+        println!("{}", USAGE);
+        return;
+    }
+    let Some(command) = args.command else {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+
+    let url = args
+        .url
+        .or_else(|| std::env::var("GITMIRRORS_URL").ok())
+        .unwrap_or_else(|| DEFAULT_URL.to_string());
+    let Some(token) = args
+        .token
+        .or_else(|| std::env::var("GITMIRRORS_TOKEN").ok())
+        .filter(|t| !t.is_empty())
+    else {
+        eprintln!("error: no API token, set GITMIRRORS_TOKEN or pass --token");
+        std::process::exit(2);
+    };
+
+    let client = match ApiClient::new(&url, token) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    std::process::exit(commands::run(&client, command, args.output));
+}
@@ -0,0 +1,165 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+/// A declarative mirror configuration, in TOML or YAML:
+///
+/// ```toml
+/// team = "<team id>" # optional, personal repositories otherwise
+///
+/// [[repositories]]
+/// name = "app"
+/// source = "git@github.com:org/app.git"
+/// target = "git@backup.example.com:org/app.git"
+/// source_credential = { env = "GITHUB_DEPLOY_KEY" }
+/// target_credential = { file = "keys/backup" }
+/// schedule = "6h"
+/// refs = ["refs/heads/main", "refs/tags/*"]
+/// tags = ["prod"]
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub team: Option<Uuid>,
+    #[serde(default)]
+    pub repositories: Vec<RepositoryEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RepositoryEntry {
+    pub name: String,
+    pub url: Option<String>,
+    pub source: String,
+    pub target: String,
+    pub source_credential: Option<CredentialRef>,
+    pub target_credential: CredentialRef,
+    pub schedule: Schedule,
+    /// Refs pushed to the target, all of them when empty.
+    #[serde(default)]
+    pub refs: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+/// Where a key is read from, keeping secrets out of the reviewed file. Relative files are
+/// resolved against the directory of the configuration. Exactly one of `env` or `file` is set.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CredentialRef {
+    pub env: Option<String>,
+    pub file: Option<PathBuf>,
+}
+
+/// Seconds, or a duration such as `90s`, `30m`, `6h`, `1d` or `2w`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum Schedule {
+    Seconds(u32),
+    Duration(String),
+}
+
+impl Schedule {
+    fn seconds(&self) -> Result<u32, String> {
+        let value = match self {
+            Schedule::Seconds(seconds) => return Ok(*seconds),
+            Schedule::Duration(value) => value.trim(),
+        };
+
+        let split = value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len());
+        let (amount, unit) = value.split_at(split);
+        let multiplier = match unit {
+            "" | "s" => 1,
+            "m" => 60,
+            "h" => 3_600,
+            "d" => 86_400,
+            "w" => 604_800,
+            _ => return Err(format!("invalid schedule {}", value)),
+        };
+
+        amount
+            .parse::<u32>()
+            .ok()
+            .and_then(|amount| amount.checked_mul(multiplier))
+            .ok_or_else(|| format!("invalid schedule {}", value))
+    }
+}
+
+impl CredentialRef {
+    fn resolve(&self, base_dir: &Path) -> Result<String, String> {
+        match (&self.env, &self.file) {
+            (Some(name), None) => {
+                std::env::var(name).map_err(|_| format!("environment variable {} is not set", name))
+            }
+            (None, Some(path)) => {
+                let path = base_dir.join(path);
+                std::fs::read_to_string(&path)
+                    .map_err(|e| format!("failed to read {}: {}", path.display(), e))
+            }
+            _ => Err("a credential needs exactly one of env or file".to_string()),
+        }
+    }
+}
+
+impl Manifest {
+    /// Reads a `.toml`, `.yaml` or `.yml` file.
+    pub fn load(path: &Path) -> Result<Manifest, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::from_str(&content)
+                .map_err(|e| format!("Invalid configuration {}: {}", path.display(), e)),
+            Some("yaml" | "yml") => serde_yaml::from_str(&content)
+                .map_err(|e| format!("Invalid configuration {}: {}", path.display(), e)),
+            _ => Err(format!(
+                "Unknown configuration format {}, expected .toml, .yaml or .yml",
+                path.display()
+            )),
+        }
+    }
+
+    /// The body of `POST /api/repository/apply`, with credentials resolved.
+    pub fn to_request(&self, base_dir: &Path) -> Result<Value, String> {
+        let mut repositories = Vec::with_capacity(self.repositories.len());
+        for entry in &self.repositories {
+            let context = |e: String| format!("Repository {}: {}", entry.name, e);
+
+            let source_key = entry
+                .source_credential
+                .as_ref()
+                .map(|c| c.resolve(base_dir))
+                .transpose()
+                .map_err(context)?;
+            let target_key = entry.target_credential.resolve(base_dir).map_err(context)?;
+
+            repositories.push(json!({
+                "name": entry.name,
+                "url": entry.url,
+                "gitSource": entry.source,
+                "gitSourceSecretKey": source_key,
+                "gitTarget": entry.target,
+                "gitTargetSecretKey": target_key,
+                "gitClonePeriodSeconds": entry.schedule.seconds().map_err(context)?,
+                "tags": entry.tags,
+                "refFilters": entry.refs,
+                "isEnabled": entry.enabled,
+            }));
+        }
+
+        Ok(json!({
+            "teamId": self.team,
+            "repositories": repositories,
+        }))
+    }
+}
@@ -0,0 +1,4 @@
+fn main() {
+    // migrations are embedded into the binary, new ones have to trigger a rebuild
+    println!("cargo:rerun-if-changed=migrations");
+}
@@ -1,7 +1,7 @@
 #!/bin/bash
 set -euo pipefail
 
-echo "Running Diesel migrations. Working directory: {$PWD}"
+echo "Working directory: {$PWD}"
 
 echo "Waiting for PostgreSQL at $POSTGRES_HOST:$POSTGRES_PORT..."
 
@@ -19,17 +19,7 @@ if ! pg_isready -h "$POSTGRES_HOST" -p "$POSTGRES_PORT" >/dev/null 2>&1; then
 	exit 1
 fi
 
-echo "Diesel setup..."
-diesel setup
-
-ls -la
-ls -la ./migrations
-
-echo "Migrations list"
-diesel migration list
-
-echo "Running diesel migration..."
-diesel migration run
-
+# pending migrations are applied by the server itself on startup, diesel CLI is only
+# needed to write new ones
 echo "Starting app with cargo watch..."
 exec cargo watch -i clone_storage -x run
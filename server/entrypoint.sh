@@ -7,8 +7,6 @@ ls -la
 
 pwd
 
-echo "Running Diesel migrations..."
-
 echo "Waiting for PostgreSQL at $POSTGRES_HOST:$POSTGRES_PORT..."
 
 for i in {1..30}; do
@@ -25,14 +23,6 @@ if ! pg_isready -h "$POSTGRES_HOST" -p "$POSTGRES_PORT" >/dev/null 2>&1; then
 	exit 1
 fi
 
-echo "Diesel setup..."
-diesel setup
-
-echo "Migrations list"
-diesel migration list
-
-echo "Running diesel migration..."
-diesel migration run
-
+# pending migrations are applied by the server itself on startup
 echo "Starting app with ./server"
-./server
+exec ./server
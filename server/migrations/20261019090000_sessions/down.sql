@@ -0,0 +1,5 @@
+ALTER TABLE public.user ADD COLUMN session_token character varying(256);
+
+DROP INDEX IF EXISTS idx_session_expires_at;
+DROP INDEX IF EXISTS idx_session_user_id;
+DROP TABLE IF EXISTS session;
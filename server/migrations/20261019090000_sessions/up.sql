@@ -0,0 +1,21 @@
+CREATE TABLE public.session (
+    id uuid NOT NULL DEFAULT uuid_generate_v4(),
+    user_id uuid NOT NULL REFERENCES "user" (
+        id
+    ) ON DELETE CASCADE ON UPDATE CASCADE,
+    token_hash varchar(64) NOT NULL,
+    user_agent varchar(512),
+    ip_address varchar(64),
+    last_seen_at timestamptz NOT NULL DEFAULT now(),
+    expires_at timestamptz NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    updated_at timestamptz NOT NULL DEFAULT now(),
+
+    CONSTRAINT session_pkey PRIMARY KEY (id),
+    CONSTRAINT "UQ_session_token_hash" UNIQUE (token_hash)
+);
+
+CREATE INDEX idx_session_user_id ON session (user_id);
+CREATE INDEX idx_session_expires_at ON session (expires_at);
+
+ALTER TABLE public.user DROP COLUMN session_token;
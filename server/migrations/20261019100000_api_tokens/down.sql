@@ -0,0 +1,4 @@
+ALTER TABLE public.repository DROP COLUMN sync_requested_at;
+
+DROP INDEX IF EXISTS idx_api_token_user_id;
+DROP TABLE IF EXISTS api_token;
@@ -0,0 +1,21 @@
+CREATE TABLE public.api_token (
+    id uuid NOT NULL DEFAULT uuid_generate_v4(),
+    user_id uuid NOT NULL REFERENCES "user" (
+        id
+    ) ON DELETE CASCADE ON UPDATE CASCADE,
+    name varchar(100) NOT NULL,
+    token_prefix varchar(16) NOT NULL,
+    token_hash varchar(64) NOT NULL,
+    scopes varchar(256) NOT NULL,
+    expires_at timestamptz,
+    last_used_at timestamptz,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    updated_at timestamptz NOT NULL DEFAULT now(),
+
+    CONSTRAINT api_token_pkey PRIMARY KEY (id),
+    CONSTRAINT "UQ_api_token_token_hash" UNIQUE (token_hash)
+);
+
+CREATE INDEX idx_api_token_user_id ON api_token (user_id);
+
+ALTER TABLE public.repository ADD COLUMN sync_requested_at timestamptz;
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_login_attempt_ip_address_created_at;
+DROP INDEX IF EXISTS idx_login_attempt_username_created_at;
+DROP TABLE IF EXISTS login_attempt;
+
+ALTER TABLE public.user DROP COLUMN is_admin;
@@ -0,0 +1,21 @@
+ALTER TABLE public.user ADD COLUMN is_admin boolean NOT NULL DEFAULT FALSE;
+UPDATE public.user SET is_admin = TRUE WHERE username = 'admin';
+
+CREATE TABLE public.login_attempt (
+    id uuid NOT NULL DEFAULT uuid_generate_v4(),
+    username varchar(64) NOT NULL,
+    ip_address varchar(64),
+    user_agent varchar(512),
+    succeeded boolean NOT NULL,
+    failure_reason varchar(30),
+    created_at timestamptz NOT NULL DEFAULT now(),
+
+    CONSTRAINT login_attempt_pkey PRIMARY KEY (id)
+);
+
+CREATE INDEX idx_login_attempt_username_created_at ON login_attempt (
+    username, created_at
+);
+CREATE INDEX idx_login_attempt_ip_address_created_at ON login_attempt (
+    ip_address, created_at
+);
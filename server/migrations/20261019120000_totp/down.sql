@@ -0,0 +1,8 @@
+DROP TABLE IF EXISTS app_setting;
+DROP TABLE IF EXISTS login_challenge;
+DROP INDEX IF EXISTS idx_recovery_code_user_id;
+DROP TABLE IF EXISTS recovery_code;
+
+ALTER TABLE public.user DROP COLUMN totp_last_used_step;
+ALTER TABLE public.user DROP COLUMN totp_enabled;
+ALTER TABLE public.user DROP COLUMN totp_secret;
@@ -0,0 +1,39 @@
+ALTER TABLE public.user ADD COLUMN totp_secret varchar(64);
+ALTER TABLE public.user ADD COLUMN totp_enabled boolean NOT NULL DEFAULT FALSE;
+ALTER TABLE public.user ADD COLUMN totp_last_used_step bigint;
+
+CREATE TABLE public.recovery_code (
+    id uuid NOT NULL DEFAULT uuid_generate_v4(),
+    user_id uuid NOT NULL REFERENCES "user" (
+        id
+    ) ON DELETE CASCADE ON UPDATE CASCADE,
+    code_hash varchar(64) NOT NULL,
+    used_at timestamptz,
+    created_at timestamptz NOT NULL DEFAULT now(),
+
+    CONSTRAINT recovery_code_pkey PRIMARY KEY (id)
+);
+
+CREATE INDEX idx_recovery_code_user_id ON recovery_code (user_id);
+
+CREATE TABLE public.login_challenge (
+    id uuid NOT NULL DEFAULT uuid_generate_v4(),
+    user_id uuid NOT NULL REFERENCES "user" (
+        id
+    ) ON DELETE CASCADE ON UPDATE CASCADE,
+    token_hash varchar(64) NOT NULL,
+    attempts int NOT NULL DEFAULT 0,
+    expires_at timestamptz NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+
+    CONSTRAINT login_challenge_pkey PRIMARY KEY (id),
+    CONSTRAINT "UQ_login_challenge_token_hash" UNIQUE (token_hash)
+);
+
+CREATE TABLE public.app_setting (
+    key varchar(64) NOT NULL,
+    value text NOT NULL,
+    updated_at timestamptz NOT NULL DEFAULT now(),
+
+    CONSTRAINT app_setting_pkey PRIMARY KEY (key)
+);
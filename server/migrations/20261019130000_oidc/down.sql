@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS oidc_login_state;
+
+ALTER TABLE public.user DROP CONSTRAINT "UQ_user_oidc_subject";
+ALTER TABLE public.user DROP COLUMN oidc_subject;
@@ -0,0 +1,14 @@
+ALTER TABLE public.user ADD COLUMN oidc_subject varchar(255);
+ALTER TABLE public.user ADD CONSTRAINT "UQ_user_oidc_subject" UNIQUE (oidc_subject);
+
+CREATE TABLE public.oidc_login_state (
+    id uuid NOT NULL DEFAULT uuid_generate_v4(),
+    state varchar(64) NOT NULL,
+    nonce varchar(64) NOT NULL,
+    code_verifier varchar(128) NOT NULL,
+    expires_at timestamptz NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+
+    CONSTRAINT oidc_login_state_pkey PRIMARY KEY (id),
+    CONSTRAINT "UQ_oidc_login_state_state" UNIQUE (state)
+);
@@ -0,0 +1,2 @@
+ALTER TABLE public.user DROP CONSTRAINT "UQ_user_ldap_dn";
+ALTER TABLE public.user DROP COLUMN ldap_dn;
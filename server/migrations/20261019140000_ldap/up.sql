@@ -0,0 +1,2 @@
+ALTER TABLE public.user ADD COLUMN ldap_dn varchar(1024);
+ALTER TABLE public.user ADD CONSTRAINT "UQ_user_ldap_dn" UNIQUE (ldap_dn);
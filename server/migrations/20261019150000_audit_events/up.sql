@@ -0,0 +1,34 @@
+-- actor_user_id has no foreign key so events outlive deleted users
+CREATE TABLE public.audit_event (
+    id uuid NOT NULL DEFAULT uuid_generate_v4(),
+    actor_user_id uuid,
+    actor_username varchar(32),
+    action varchar(64) NOT NULL,
+    target_type varchar(32),
+    target_id uuid,
+    before jsonb,
+    after jsonb,
+    ip_address varchar(64),
+    user_agent varchar(512),
+    created_at timestamptz NOT NULL DEFAULT now(),
+
+    CONSTRAINT audit_event_pkey PRIMARY KEY (id)
+);
+
+CREATE INDEX idx_audit_event_created_at ON audit_event (created_at);
+CREATE INDEX idx_audit_event_actor_user_id_created_at ON audit_event (
+    actor_user_id, created_at
+);
+CREATE INDEX idx_audit_event_target_id_created_at ON audit_event (
+    target_id, created_at
+);
+
+CREATE FUNCTION audit_event_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_event is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_event_append_only
+    BEFORE UPDATE OR DELETE ON audit_event
+    FOR EACH ROW EXECUTE FUNCTION audit_event_append_only();
@@ -0,0 +1,8 @@
+DELETE FROM public.repository WHERE team_id IS NOT NULL;
+DROP INDEX IF EXISTS idx_repository_team_id;
+ALTER TABLE public.repository DROP CONSTRAINT repository_owner_check;
+ALTER TABLE public.repository DROP COLUMN team_id;
+ALTER TABLE public.repository ALTER COLUMN user_id SET NOT NULL;
+
+DROP TABLE IF EXISTS team_member;
+DROP TABLE IF EXISTS team;
@@ -0,0 +1,38 @@
+CREATE TABLE public.team (
+    id uuid NOT NULL DEFAULT uuid_generate_v4(),
+    name varchar(100) NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    updated_at timestamptz NOT NULL DEFAULT now(),
+
+    CONSTRAINT team_pkey PRIMARY KEY (id),
+    CONSTRAINT "UQ_team_name" UNIQUE (name)
+);
+
+CREATE TABLE public.team_member (
+    id uuid NOT NULL DEFAULT uuid_generate_v4(),
+    team_id uuid NOT NULL REFERENCES team (
+        id
+    ) ON DELETE CASCADE ON UPDATE CASCADE,
+    user_id uuid NOT NULL REFERENCES "user" (
+        id
+    ) ON DELETE CASCADE ON UPDATE CASCADE,
+    role varchar(16) NOT NULL CHECK (role IN ('viewer', 'operator', 'maintainer')),
+    created_at timestamptz NOT NULL DEFAULT now(),
+    updated_at timestamptz NOT NULL DEFAULT now(),
+
+    CONSTRAINT team_member_pkey PRIMARY KEY (id),
+    CONSTRAINT "UQ_team_member_team_id_user_id" UNIQUE (team_id, user_id)
+);
+
+CREATE INDEX idx_team_member_user_id ON team_member (user_id);
+
+-- a repository is owned by exactly one user or one team
+ALTER TABLE public.repository ALTER COLUMN user_id DROP NOT NULL;
+ALTER TABLE public.repository ADD COLUMN team_id uuid REFERENCES team (
+    id
+) ON DELETE RESTRICT ON UPDATE CASCADE;
+ALTER TABLE public.repository ADD CONSTRAINT repository_owner_check CHECK (
+    num_nonnulls(user_id, team_id) = 1
+);
+
+CREATE INDEX idx_repository_team_id ON repository (team_id);
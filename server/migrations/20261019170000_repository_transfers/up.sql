@@ -0,0 +1,37 @@
+CREATE TABLE public.repository_transfer (
+    id uuid NOT NULL DEFAULT uuid_generate_v4(),
+    repository_id uuid NOT NULL REFERENCES repository (
+        id
+    ) ON DELETE CASCADE ON UPDATE CASCADE,
+    requested_by uuid REFERENCES "user" (
+        id
+    ) ON DELETE SET NULL ON UPDATE CASCADE,
+    from_user_id uuid,
+    from_team_id uuid,
+    to_user_id uuid REFERENCES "user" (
+        id
+    ) ON DELETE CASCADE ON UPDATE CASCADE,
+    to_team_id uuid REFERENCES team (
+        id
+    ) ON DELETE CASCADE ON UPDATE CASCADE,
+    status varchar(16) NOT NULL DEFAULT 'pending' CHECK (
+        status IN ('pending', 'accepted', 'declined', 'cancelled')
+    ),
+    resolved_by uuid REFERENCES "user" (
+        id
+    ) ON DELETE SET NULL ON UPDATE CASCADE,
+    resolved_at timestamptz,
+    created_at timestamptz NOT NULL DEFAULT now(),
+
+    CONSTRAINT repository_transfer_pkey PRIMARY KEY (id),
+    CONSTRAINT repository_transfer_recipient_check CHECK (
+        num_nonnulls(to_user_id, to_team_id) = 1
+    )
+);
+
+-- at most one open transfer per repository
+CREATE UNIQUE INDEX "UQ_repository_transfer_pending" ON repository_transfer (
+    repository_id
+) WHERE status = 'pending';
+CREATE INDEX idx_repository_transfer_to_user_id ON repository_transfer (to_user_id);
+CREATE INDEX idx_repository_transfer_to_team_id ON repository_transfer (to_team_id);
@@ -0,0 +1,11 @@
+DROP INDEX IF EXISTS idx_repository_search;
+DROP INDEX IF EXISTS idx_repository_tags;
+DROP INDEX IF EXISTS idx_repository_source_host;
+DROP INDEX IF EXISTS idx_repository_last_sync_status;
+DROP INDEX IF EXISTS idx_repository_last_clone_at;
+DROP INDEX IF EXISTS idx_repository_created_at;
+DROP INDEX IF EXISTS idx_repository_user_id_name;
+
+ALTER TABLE public.repository DROP COLUMN source_host;
+ALTER TABLE public.repository DROP COLUMN last_sync_status;
+ALTER TABLE public.repository DROP COLUMN tags;
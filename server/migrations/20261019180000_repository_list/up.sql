@@ -0,0 +1,30 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+ALTER TABLE public.repository ADD COLUMN tags text[] NOT NULL DEFAULT '{}';
+-- outcome of the latest finished clone job, null until the first one finishes
+ALTER TABLE public.repository ADD COLUMN last_sync_status varchar(16) CHECK (
+    last_sync_status IN ('succeeded', 'failed')
+);
+-- host part of https://, ssh:// and scp-like git@host:path sources
+ALTER TABLE public.repository ADD COLUMN source_host varchar(255) GENERATED ALWAYS AS (
+    lower(substring(git_source FROM '^(?:[a-zA-Z][a-zA-Z0-9+.-]*://)?(?:[^@/]+@)?([^:/]+)'))
+) STORED;
+
+UPDATE repository r SET last_sync_status = CASE l.type WHEN 'finished_clone_job' THEN 'succeeded' ELSE 'failed' END
+FROM (
+    SELECT DISTINCT ON (repository_id) repository_id, type
+    FROM repository_logs
+    WHERE type IN ('finished_clone_job', 'error_clone_job', 'panic_clone_job')
+    ORDER BY repository_id, created_at DESC
+) l
+WHERE l.repository_id = r.id;
+
+CREATE INDEX idx_repository_user_id_name ON repository (user_id, name);
+CREATE INDEX idx_repository_created_at ON repository (created_at);
+CREATE INDEX idx_repository_last_clone_at ON repository (last_clone_at);
+CREATE INDEX idx_repository_last_sync_status ON repository (last_sync_status);
+CREATE INDEX idx_repository_source_host ON repository (source_host);
+CREATE INDEX idx_repository_tags ON repository USING gin (tags);
+CREATE INDEX idx_repository_search ON repository USING gin (
+    (name || ' ' || coalesce(url, '') || ' ' || git_source || ' ' || git_target) gin_trgm_ops
+);
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_repository_logs_created_at;
+DROP INDEX IF EXISTS idx_repository_logs_repository_id_created_at;
+CREATE INDEX idx_repository_logs_repository_id ON repository_logs (
+    repository_id
+);
@@ -0,0 +1,6 @@
+-- keyset pagination walks (created_at, id) newest first, per repository and across them
+DROP INDEX IF EXISTS idx_repository_logs_repository_id;
+CREATE INDEX idx_repository_logs_repository_id_created_at ON repository_logs (
+    repository_id, created_at DESC, id DESC
+);
+CREATE INDEX idx_repository_logs_created_at ON repository_logs (created_at DESC, id DESC);
@@ -0,0 +1,6 @@
+DROP TRIGGER IF EXISTS repository_log_daily_count_increment ON repository_logs;
+DROP FUNCTION IF EXISTS repository_log_daily_count_increment();
+DROP TABLE IF EXISTS public.repository_log_daily_count;
+
+ALTER TABLE public.repository DROP COLUMN log_max_rows;
+ALTER TABLE public.repository DROP COLUMN log_retention_days;
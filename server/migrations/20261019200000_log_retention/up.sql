@@ -0,0 +1,36 @@
+-- per-repository overrides of the global retention settings, 0 keeps logs forever
+ALTER TABLE public.repository ADD COLUMN log_retention_days int CHECK (log_retention_days >= 0);
+ALTER TABLE public.repository ADD COLUMN log_max_rows int CHECK (log_max_rows >= 0);
+
+-- daily log counts outlive pruned logs so the dashboard charts stay correct
+CREATE TABLE public.repository_log_daily_count (
+    repository_id uuid NOT NULL REFERENCES repository (
+        id
+    ) ON DELETE CASCADE ON UPDATE CASCADE,
+    day date NOT NULL,
+    type varchar(30) NOT NULL,
+    count bigint NOT NULL DEFAULT 0,
+
+    CONSTRAINT repository_log_daily_count_pkey PRIMARY KEY (repository_id, day, type)
+);
+
+CREATE INDEX idx_repository_log_daily_count_day ON repository_log_daily_count (day);
+
+INSERT INTO repository_log_daily_count (repository_id, day, type, count)
+SELECT repository_id, (created_at AT TIME ZONE 'UTC')::date, type, count(*)
+FROM repository_logs
+GROUP BY 1, 2, 3;
+
+CREATE FUNCTION repository_log_daily_count_increment() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO repository_log_daily_count (repository_id, day, type, count)
+    VALUES (NEW.repository_id, (NEW.created_at AT TIME ZONE 'UTC')::date, NEW.type, 1)
+    ON CONFLICT (repository_id, day, type)
+    DO UPDATE SET count = repository_log_daily_count.count + 1;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER repository_log_daily_count_increment
+    AFTER INSERT ON repository_logs
+    FOR EACH ROW EXECUTE FUNCTION repository_log_daily_count_increment();
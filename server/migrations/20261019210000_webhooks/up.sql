@@ -0,0 +1,32 @@
+-- the secret is kept in plain text because HMAC signatures are verified with it
+CREATE TABLE public.repository_webhook (
+    repository_id uuid NOT NULL REFERENCES repository (
+        id
+    ) ON DELETE CASCADE ON UPDATE CASCADE,
+    secret varchar(64) NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    updated_at timestamptz NOT NULL DEFAULT now(),
+
+    CONSTRAINT repository_webhook_pkey PRIMARY KEY (repository_id)
+);
+
+CREATE TABLE public.webhook_delivery (
+    id uuid NOT NULL DEFAULT uuid_generate_v4(),
+    repository_id uuid NOT NULL REFERENCES repository (
+        id
+    ) ON DELETE CASCADE ON UPDATE CASCADE,
+    provider varchar(16),
+    event varchar(64),
+    delivery_id varchar(128),
+    outcome varchar(16) NOT NULL CHECK (outcome IN ('accepted', 'ignored', 'rejected')),
+    message text NOT NULL,
+    ip_address varchar(64),
+    created_at timestamptz NOT NULL DEFAULT now(),
+
+    CONSTRAINT webhook_delivery_pkey PRIMARY KEY (id)
+);
+
+CREATE INDEX idx_webhook_delivery_repository_id_created_at ON webhook_delivery (
+    repository_id, created_at DESC
+);
+CREATE INDEX idx_webhook_delivery_created_at ON webhook_delivery (created_at);
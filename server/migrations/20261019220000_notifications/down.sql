@@ -0,0 +1,4 @@
+ALTER TABLE public.repository DROP COLUMN health_state;
+
+DROP TABLE IF EXISTS public.notification_delivery;
+DROP TABLE IF EXISTS public.notification_channel;
@@ -0,0 +1,51 @@
+CREATE TABLE public.notification_channel (
+    id uuid NOT NULL DEFAULT uuid_generate_v4(),
+    user_id uuid REFERENCES "user" (
+        id
+    ) ON DELETE CASCADE ON UPDATE CASCADE,
+    team_id uuid REFERENCES team (
+        id
+    ) ON DELETE CASCADE ON UPDATE CASCADE,
+    name varchar(100) NOT NULL,
+    kind varchar(16) NOT NULL CHECK (kind IN ('webhook', 'slack', 'email')),
+    -- URL for webhook and slack channels, address for email channels
+    target varchar(1024) NOT NULL,
+    -- signs generic webhook payloads when set
+    secret varchar(128),
+    is_enabled boolean NOT NULL DEFAULT TRUE,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    updated_at timestamptz NOT NULL DEFAULT now(),
+
+    CONSTRAINT notification_channel_pkey PRIMARY KEY (id),
+    CONSTRAINT notification_channel_owner_check CHECK (num_nonnulls(user_id, team_id) = 1)
+);
+
+CREATE INDEX idx_notification_channel_user_id ON notification_channel (user_id);
+CREATE INDEX idx_notification_channel_team_id ON notification_channel (team_id);
+
+CREATE TABLE public.notification_delivery (
+    id uuid NOT NULL DEFAULT uuid_generate_v4(),
+    channel_id uuid NOT NULL REFERENCES notification_channel (
+        id
+    ) ON DELETE CASCADE ON UPDATE CASCADE,
+    repository_id uuid REFERENCES repository (
+        id
+    ) ON DELETE CASCADE ON UPDATE CASCADE,
+    event varchar(32) NOT NULL,
+    succeeded boolean NOT NULL,
+    error text,
+    created_at timestamptz NOT NULL DEFAULT now(),
+
+    CONSTRAINT notification_delivery_pkey PRIMARY KEY (id)
+);
+
+CREATE INDEX idx_notification_delivery_channel_id_created_at ON notification_delivery (
+    channel_id, created_at DESC
+);
+CREATE INDEX idx_notification_delivery_created_at ON notification_delivery (created_at);
+
+-- last state notifications were sent for, transitions away from it notify once
+ALTER TABLE public.repository ADD COLUMN health_state varchar(16) NOT NULL DEFAULT 'healthy' CHECK (
+    health_state IN ('healthy', 'failing', 'stale')
+);
+UPDATE repository SET health_state = 'failing' WHERE last_sync_status = 'failed';
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_repository_logs_job_id;
+
+ALTER TABLE public.repository_logs DROP COLUMN IF EXISTS job_id;
@@ -0,0 +1,4 @@
+-- groups the logs written by one run of the clone worker for a repository
+ALTER TABLE public.repository_logs ADD COLUMN job_id uuid;
+
+CREATE INDEX idx_repository_logs_job_id ON repository_logs (job_id) WHERE job_id IS NOT NULL;
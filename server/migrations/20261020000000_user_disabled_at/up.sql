@@ -0,0 +1,2 @@
+-- disabled users can no longer sign in, their sessions and API tokens stop working
+ALTER TABLE public."user" ADD COLUMN disabled_at timestamptz;
@@ -0,0 +1 @@
+ALTER TABLE public.repository DROP COLUMN IF EXISTS ref_filters;
@@ -0,0 +1,2 @@
+-- ref patterns such as refs/heads/main or refs/tags/* pushed to the target, all refs when empty
+ALTER TABLE public.repository ADD COLUMN ref_filters text[] NOT NULL DEFAULT '{}';
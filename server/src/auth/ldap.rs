@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use ldap3::{LdapConnAsync, LdapConnSettings, Scope, SearchEntry, ldap_escape};
+
+use crate::config::LdapConfig;
+
+type LdapResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// LDAP result code for a bind with a wrong password, RFC 4511 appendix A.
+const LDAP_INVALID_CREDENTIALS: u32 = 49;
+const LDAP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Directory entry that passed the bind with the user's password.
+pub struct LdapIdentity {
+    pub dn: String,
+    pub is_admin: Option<bool>,
+}
+
+pub struct LdapAuthenticator {
+    pub config: LdapConfig,
+}
+
+impl LdapAuthenticator {
+    pub fn new(config: LdapConfig) -> Self {
+        LdapAuthenticator { config }
+    }
+
+    /// Searches for the user entry with the service account, then binds as that entry.
+    /// Returns `Ok(None)` for unknown users, users outside the allowed filter and wrong passwords.
+    pub async fn authenticate(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> LdapResult<Option<LdapIdentity>> {
+        // an empty password would be an unauthenticated bind, which most servers accept
+        if password.is_empty() {
+            return Ok(None);
+        }
+
+        let settings = LdapConnSettings::new()
+            .set_conn_timeout(LDAP_TIMEOUT)
+            .set_starttls(self.config.starttls)
+            .set_no_tls_verify(self.config.tls_no_verify);
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &self.config.url).await?;
+        ldap3::drive!(conn);
+
+        if let (Some(bind_dn), Some(bind_password)) = (
+            self.config.bind_dn.as_deref(),
+            self.config.bind_password.as_deref(),
+        ) {
+            ldap.with_timeout(LDAP_TIMEOUT)
+                .simple_bind(bind_dn, bind_password)
+                .await?
+                .success()?;
+        }
+
+        let user_filter = self
+            .config
+            .user_filter
+            .replace("{username}", &ldap_escape(username));
+        let filter = match self.config.allowed_filter.as_deref() {
+            Some(allowed) => format!("(&{}{})", user_filter, allowed),
+            None => user_filter,
+        };
+
+        let (entries, _) = ldap
+            .with_timeout(LDAP_TIMEOUT)
+            .search(
+                &self.config.user_base_dn,
+                Scope::Subtree,
+                &filter,
+                vec!["1.1"],
+            )
+            .await?
+            .success()?;
+
+        // ambiguous filters are treated like unknown users
+        let dn = match entries.as_slice() {
+            [entry] => SearchEntry::construct(entry.clone()).dn,
+            _ => {
+                let _ = ldap.unbind().await;
+                return Ok(None);
+            }
+        };
+
+        // checked with the service account, users often cannot read their own group membership
+        let is_admin = match self.config.admin_filter.as_deref() {
+            Some(admin_filter) => {
+                let (entries, _) = ldap
+                    .with_timeout(LDAP_TIMEOUT)
+                    .search(&dn, Scope::Base, admin_filter, vec!["1.1"])
+                    .await?
+                    .success()?;
+                Some(!entries.is_empty())
+            }
+            None => None,
+        };
+
+        let bind_result = ldap
+            .with_timeout(LDAP_TIMEOUT)
+            .simple_bind(&dn, password)
+            .await?;
+        let _ = ldap.unbind().await;
+
+        if bind_result.rc == LDAP_INVALID_CREDENTIALS {
+            return Ok(None);
+        }
+        bind_result.success()?;
+
+        Ok(Some(LdapIdentity { dn, is_admin }))
+    }
+}
@@ -0,0 +1,2 @@
+pub mod ldap;
+pub mod oidc;
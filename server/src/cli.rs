@@ -0,0 +1,181 @@
+use std::path::PathBuf;
+
+use uuid::Uuid;
+
+pub const USAGE: &str = "Usage: server [--config <file>] [--print-config | --migrate-only]
+       server [--config <file>] <command>
+
+Options:
+  --config <file>   Read the configuration from <file> instead of gitmirrors.toml
+  --print-config    Print the effective configuration with secrets masked and exit
+  --migrate-only    Apply pending database migrations and exit
+  --help            Print this help and exit
+
+Commands, which print their result as JSON:
+  user create <username> [--admin] [--password-stdin]
+  user reset-password <username> [--password-stdin]
+  user disable <username>
+  user enable <username>
+  repo list
+  repo sync <id>
+  repo enable <id>
+  repo disable <id>
+  keys rotate-master
+  storage gc [--dry-run]
+  db migrate
+
+Without --password-stdin a random password is generated and printed.";
+
+#[derive(Debug, Default)]
+pub struct Args {
+    pub config_path: Option<PathBuf>,
+    pub print_config: bool,
+    pub migrate_only: bool,
+    pub help: bool,
+    pub command: Option<Command>,
+}
+
+/// Administrative commands run instead of the HTTP server.
+#[derive(Debug)]
+pub enum Command {
+    UserCreate {
+        username: String,
+        admin: bool,
+        password_stdin: bool,
+    },
+    UserResetPassword {
+        username: String,
+        password_stdin: bool,
+    },
+    UserDisable {
+        username: String,
+    },
+    UserEnable {
+        username: String,
+    },
+    RepoList,
+    RepoSync {
+        id: Uuid,
+    },
+    RepoEnable {
+        id: Uuid,
+    },
+    RepoDisable {
+        id: Uuid,
+    },
+    KeysRotateMaster,
+    StorageGc {
+        dry_run: bool,
+    },
+    DbMigrate,
+}
+
+impl Args {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => match args.next() {
+                    Some(path) => parsed.config_path = Some(PathBuf::from(path)),
+                    None => return Err("--config requires a file".to_string()),
+                },
+                "--print-config" => parsed.print_config = true,
+                "--migrate-only" => parsed.migrate_only = true,
+                "--help" | "-h" => parsed.help = true,
+                other if other.starts_with("--config=") => {
+                    parsed.config_path = Some(PathBuf::from(&other["--config=".len()..]));
+                }
+                other if other.starts_with('-') => {
+                    return Err(format!("unknown argument {}", other));
+                }
+                // everything from the first positional argument on belongs to the command
+                _ => {
+                    let rest: Vec<String> = std::iter::once(arg).chain(args).collect();
+                    parsed.command = Some(Command::parse(&rest)?);
+                    break;
+                }
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+impl Command {
+    fn parse(args: &[String]) -> Result<Command, String> {
+        let words: Vec<&str> = args.iter().map(String::as_str).collect();
+        let (positional, flags): (Vec<&str>, Vec<&str>) =
+            words.iter().partition(|w| !w.starts_with("--"));
+
+        let allow_flags = |allowed: &[&str]| -> Result<(), String> {
+            match flags.iter().find(|f| !allowed.contains(f)) {
+                Some(flag) => Err(format!("unknown argument {}", flag)),
+                None => Ok(()),
+            }
+        };
+        let has_flag = |flag: &str| flags.contains(&flag);
+
+        let command = match positional.as_slice() {
+            ["user", "create", username] => {
+                allow_flags(&["--admin", "--password-stdin"])?;
+                Command::UserCreate {
+                    username: username.to_string(),
+                    admin: has_flag("--admin"),
+                    password_stdin: has_flag("--password-stdin"),
+                }
+            }
+            ["user", "reset-password", username] => {
+                allow_flags(&["--password-stdin"])?;
+                Command::UserResetPassword {
+                    username: username.to_string(),
+                    password_stdin: has_flag("--password-stdin"),
+                }
+            }
+            ["user", "disable", username] => {
+                allow_flags(&[])?;
+                Command::UserDisable {
+                    username: username.to_string(),
+                }
+            }
+            ["user", "enable", username] => {
+                allow_flags(&[])?;
+                Command::UserEnable {
+                    username: username.to_string(),
+                }
+            }
+            ["repo", "list"] => {
+                allow_flags(&[])?;
+                Command::RepoList
+            }
+            ["repo", action @ ("sync" | "enable" | "disable"), id] => {
+                allow_flags(&[])?;
+                let id =
+                    Uuid::parse_str(id).map_err(|_| format!("invalid repository id {}", id))?;
+                match *action {
+                    "sync" => Command::RepoSync { id },
+                    "enable" => Command::RepoEnable { id },
+                    _ => Command::RepoDisable { id },
+                }
+            }
+            ["keys", "rotate-master"] => {
+                allow_flags(&[])?;
+                Command::KeysRotateMaster
+            }
+            ["storage", "gc"] => {
+                allow_flags(&["--dry-run"])?;
+                Command::StorageGc {
+                    dry_run: has_flag("--dry-run"),
+                }
+            }
+            ["db", "migrate"] => {
+                allow_flags(&[])?;
+                Command::DbMigrate
+            }
+            _ => return Err(format!("unknown command {}", words.join(" "))),
+        };
+
+        Ok(command)
+    }
+}
@@ -0,0 +1,248 @@
+use chrono::{DateTime, Utc};
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sql_types::{BigInt, Integer, Timestamptz, Uuid as SqlUuid};
+use diesel::{PgConnection, QueryableByName, RunQueryDsl, sql_query};
+use rocket::tokio;
+use uuid::Uuid;
+
+use crate::utils::settings::{
+    DEFAULT_LOG_MAX_ROWS, DEFAULT_LOG_RETENTION_DAYS, SETTING_LOG_MAX_ROWS,
+    SETTING_LOG_RETENTION_DAYS, get_int_setting,
+};
+
+/// Rows deleted per statement, so pruning a large backlog never holds long locks.
+const PRUNE_BATCH_SIZE: i64 = 5_000;
+
+/// Webhook and notification deliveries are only kept for debugging recent setups.
+const DELIVERY_RETENTION_DAYS: i32 = 30;
+
+const DELETE_EXPIRED_WEBHOOK_DELIVERIES: &str = "\
+    DELETE FROM webhook_delivery WHERE id IN ( \
+        SELECT id FROM webhook_delivery \
+        WHERE created_at < now() - $1 * interval '1 day' \
+        LIMIT $2)";
+
+const DELETE_EXPIRED_NOTIFICATION_DELIVERIES: &str = "\
+    DELETE FROM notification_delivery WHERE id IN ( \
+        SELECT id FROM notification_delivery \
+        WHERE created_at < now() - $1 * interval '1 day' \
+        LIMIT $2)";
+
+/// Logs older than the repository's retention days, or its global default.
+const DELETE_EXPIRED_LOGS: &str = "\
+    DELETE FROM repository_logs WHERE id IN ( \
+        SELECT l.id FROM repository_logs l \
+        JOIN repository r ON r.id = l.repository_id \
+        WHERE coalesce(r.log_retention_days, $1) > 0 \
+        AND l.created_at < now() - coalesce(r.log_retention_days, $1) * interval '1 day' \
+        LIMIT $2)";
+
+/// Newest log each repository keeps under its max rows, or the global default, for
+/// repositories with more logs than that. The lateral offset walks the
+/// `(repository_id, created_at, id)` index instead of ranking the whole table.
+const SELECT_LOG_CUTOFFS: &str = "\
+    SELECT r.id AS repository_id, cutoff.created_at, cutoff.id \
+    FROM repository r \
+    CROSS JOIN LATERAL ( \
+        SELECT l.created_at, l.id FROM repository_logs l \
+        WHERE l.repository_id = r.id \
+        ORDER BY l.created_at DESC, l.id DESC \
+        OFFSET coalesce(r.log_max_rows, $1) - 1 \
+        LIMIT 1 \
+    ) cutoff \
+    WHERE coalesce(r.log_max_rows, $1) > 0 \
+    AND EXISTS ( \
+        SELECT 1 FROM repository_logs older \
+        WHERE older.repository_id = r.id \
+        AND (older.created_at, older.id) < (cutoff.created_at, cutoff.id))";
+
+/// Logs of a repository older than its cutoff.
+const DELETE_LOGS_BEFORE_CUTOFF: &str = "\
+    DELETE FROM repository_logs WHERE id IN ( \
+        SELECT id FROM repository_logs \
+        WHERE repository_id = $1 AND (created_at, id) < ($2, $3) \
+        LIMIT $4)";
+
+#[derive(QueryableByName)]
+struct LogCutoff {
+    #[diesel(sql_type = SqlUuid)]
+    repository_id: Uuid,
+    #[diesel(sql_type = Timestamptz)]
+    created_at: DateTime<Utc>,
+    #[diesel(sql_type = SqlUuid)]
+    id: Uuid,
+}
+
+#[derive(Debug)]
+pub struct PruneSummary {
+    pub expired: usize,
+    pub excess: usize,
+    pub webhook_deliveries: usize,
+    pub notification_deliveries: usize,
+}
+
+/// Deletes repository logs outside their retention and old webhook and
+/// notification deliveries. Daily counts
+/// in `repository_log_daily_count` are kept, so dashboard charts are unaffected.
+pub async fn prune_repository_logs(
+    pool: &Pool<ConnectionManager<PgConnection>>,
+) -> Result<PruneSummary, Box<dyn std::error::Error + Send + Sync>> {
+    let pool = pool.clone();
+
+    tokio::task::spawn_blocking(
+        move || -> Result<PruneSummary, Box<dyn std::error::Error + Send + Sync>> {
+            let mut conn = pool.get()?;
+
+            let retention_days = get_int_setting(
+                &mut conn,
+                SETTING_LOG_RETENTION_DAYS,
+                DEFAULT_LOG_RETENTION_DAYS,
+            )?;
+            let max_rows = get_int_setting(&mut conn, SETTING_LOG_MAX_ROWS, DEFAULT_LOG_MAX_ROWS)?;
+
+            Ok(PruneSummary {
+                expired: delete_in_batches(&mut conn, DELETE_EXPIRED_LOGS, retention_days)?,
+                excess: delete_excess_logs(&mut conn, max_rows)?,
+                webhook_deliveries: delete_in_batches(
+                    &mut conn,
+                    DELETE_EXPIRED_WEBHOOK_DELIVERIES,
+                    DELIVERY_RETENTION_DAYS,
+                )?,
+                notification_deliveries: delete_in_batches(
+                    &mut conn,
+                    DELETE_EXPIRED_NOTIFICATION_DELIVERIES,
+                    DELIVERY_RETENTION_DAYS,
+                )?,
+            })
+        },
+    )
+    .await?
+}
+
+fn delete_in_batches(
+    conn: &mut PgConnection,
+    statement: &str,
+    limit: i32,
+) -> diesel::QueryResult<usize> {
+    let mut total = 0;
+    loop {
+        let deleted = sql_query(statement)
+            .bind::<Integer, _>(limit)
+            .bind::<BigInt, _>(PRUNE_BATCH_SIZE)
+            .execute(conn)?;
+        total += deleted;
+
+        if (deleted as i64) < PRUNE_BATCH_SIZE {
+            return Ok(total);
+        }
+    }
+}
+
+/// Deletes the logs beyond each repository's max rows. The cutoffs are looked up once, so
+/// the batches only walk the logs of the repositories that have too many.
+fn delete_excess_logs(conn: &mut PgConnection, max_rows: i32) -> diesel::QueryResult<usize> {
+    let cutoffs = sql_query(SELECT_LOG_CUTOFFS)
+        .bind::<Integer, _>(max_rows)
+        .load::<LogCutoff>(conn)?;
+
+    let mut total = 0;
+    for cutoff in cutoffs {
+        loop {
+            let deleted = sql_query(DELETE_LOGS_BEFORE_CUTOFF)
+                .bind::<SqlUuid, _>(cutoff.repository_id)
+                .bind::<Timestamptz, _>(cutoff.created_at)
+                .bind::<SqlUuid, _>(cutoff.id)
+                .bind::<BigInt, _>(PRUNE_BATCH_SIZE)
+                .execute(conn)?;
+            total += deleted;
+
+            if (deleted as i64) < PRUNE_BATCH_SIZE {
+                break;
+            }
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::prelude::*;
+
+    use super::*;
+    use crate::db::init_pool;
+    use crate::db::migrations::run_pending_migrations;
+    use crate::schema::{repository, repository_logs, user};
+
+    const LOGS_PER_REPOSITORY: i32 = 9;
+
+    /// Newest first, in the order the retention keeps them.
+    fn log_ids(conn: &mut PgConnection, repository_id: Uuid) -> Vec<Uuid> {
+        repository_logs::table
+            .filter(repository_logs::repository_id.eq(repository_id))
+            .order((
+                repository_logs::created_at.desc(),
+                repository_logs::id.desc(),
+            ))
+            .select(repository_logs::id)
+            .load(conn)
+            .unwrap()
+    }
+
+    #[test]
+    #[ignore = "needs a Postgres database in DATABASE_URL"]
+    fn keeps_the_newest_logs_of_each_repository() {
+        let url = std::env::var("DATABASE_URL").expect("DATABASE_URL is not set");
+        let conn = &mut init_pool(&url).get().unwrap();
+        run_pending_migrations(conn).unwrap();
+
+        conn.test_transaction::<_, diesel::result::Error, _>(|conn| {
+            let owner: Uuid = diesel::insert_into(user::table)
+                .values(user::username.eq("retention-test"))
+                .returning(user::id)
+                .get_result(conn)?;
+
+            // the default, a lower override and one keeping everything
+            let mut repositories = Vec::new();
+            for (name, max_rows) in [("default", None), ("three", Some(3)), ("all", Some(0))] {
+                let id: Uuid = diesel::insert_into(repository::table)
+                    .values((
+                        repository::user_id.eq(owner),
+                        repository::name.eq(name),
+                        repository::git_source.eq(format!("https://a.example.com/{name}")),
+                        repository::git_target.eq(format!("https://b.example.com/{name}")),
+                        repository::git_clone_period_seconds.eq(60),
+                        repository::log_max_rows.eq(max_rows),
+                    ))
+                    .returning(repository::id)
+                    .get_result(conn)?;
+
+                // timestamps repeat, so the id decides between them
+                sql_query(
+                    "INSERT INTO repository_logs (repository_id, type, message, created_at) \
+                     SELECT $1, 'success_clone_job', 'cloned', \
+                        now() - (g % 3) * interval '1 minute' \
+                     FROM generate_series(1, $2) g",
+                )
+                .bind::<SqlUuid, _>(id)
+                .bind::<Integer, _>(LOGS_PER_REPOSITORY)
+                .execute(conn)?;
+
+                let before = log_ids(conn, id);
+                repositories.push((id, max_rows.unwrap_or(4), before));
+            }
+
+            let deleted = delete_excess_logs(conn, 4)?;
+            assert_eq!(deleted, 2 * LOGS_PER_REPOSITORY as usize - 4 - 3);
+
+            for (id, max_rows, before) in repositories {
+                let kept = if max_rows == 0 {
+                    before.len()
+                } else {
+                    max_rows as usize
+                };
+                assert_eq!(log_ids(conn, id), before[..kept]);
+            }
+            Ok(())
+        });
+    }
+}
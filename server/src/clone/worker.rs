@@ -2,6 +2,8 @@ use std::io::Read;
 use std::os::unix::fs::PermissionsExt;
 use std::panic::{AssertUnwindSafe, catch_unwind};
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
 
 use chrono::Utc;
 use rocket::tokio;
@@ -15,82 +17,181 @@ use diesel::{
 };
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
+use tracing::Instrument;
 use uuid::Uuid;
 
 use crate::schema::repository::dsl::*;
 use crate::utils::crypto::sanitize_ssh_key;
+use crate::utils::master_key::MasterKeys;
 use tokio::process::Command;
 
+use crate::config::{Config, StorageConfig};
+use crate::health::WorkerHeartbeat;
+use crate::metrics::{
+    Metrics, OUTCOME_FAILED, OUTCOME_PANICKED, OUTCOME_SUCCEEDED, PHASE_CLONE, PHASE_PUSH,
+};
 use crate::models::{InsertableRepositoryLogModel, RepositoryModel};
+use crate::notify::{Notifier, record_sync_result};
+
+/// Values of `repository.last_sync_status`.
+pub const SYNC_STATUS_SUCCEEDED: &str = "succeeded";
+pub const SYNC_STATUS_FAILED: &str = "failed";
 
-const CLONE_STORAGE_PATH: &str = "clone_storage/repositories/";
-const KEY_STORAGE_PATH: &str = "clone_storage/keys/";
+/// Repositories with a pending sync request or a finished cloning period.
+pub const DUE_SQL: &str = "(sync_requested_at IS NOT NULL
+      OR (coalesce(last_clone_at, 'epoch'::timestamptz)
+      + (git_clone_period_seconds || ' seconds')::interval)
+      <= now())";
 
 pub async fn clone_worker_fetch_due_repos(
     pool: &Pool<ConnectionManager<PgConnection>>,
+    batch_size: i64,
 ) -> Result<Vec<RepositoryModel>, diesel::result::Error> {
     let connection = &mut pool.get().unwrap();
 
     repository
         .filter(is_enabled.eq(true))
-        .filter(sql::<Bool>(
-            "(coalesce(last_clone_at, 'epoch'::timestamptz)
-                  + (git_clone_period_seconds || ' seconds')::interval)
-                  <= now()",
+        .filter(sql::<Bool>(DUE_SQL))
+        .order((
+            sync_requested_at.asc().nulls_last(),
+            sql::<Interval>("now() - coalesce(last_clone_at, 'epoch'::timestamptz) DESC"),
         ))
-        .order(sql::<Interval>(
-            "now() - coalesce(last_clone_at, 'epoch'::timestamptz) DESC",
-        ))
-        .limit(3)
+        .limit(batch_size)
         .load::<RepositoryModel>(connection)
 }
 
 pub async fn clone_worker_run(
     pool: &Pool<ConnectionManager<PgConnection>>,
+    notifier: &Arc<Notifier>,
+    metrics: &Arc<Metrics>,
+    heartbeat: &WorkerHeartbeat,
+    config: &Config,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let repositories_to_clone = clone_worker_fetch_due_repos(pool).await.unwrap();
+    let repositories_to_clone = clone_worker_fetch_due_repos(pool, config.worker.batch_size)
+        .await
+        .unwrap();
+
+    let keys = MasterKeys::from_config(&config.keys);
 
     for repo in repositories_to_clone {
-        let repo_id = repo.id;
-
-        insert_log(pool, repo_id, "starting_clone_job", "Starting clone job...").await?;
-
-        let future = catch_unwind(AssertUnwindSafe(|| {
-            clone_worker_run_single_repo(pool, repo)
-        }));
-
-        match future {
-            Ok(fut) => {
-                if let Err(e) = fut.await {
-                    eprintln!("Failed to clone repo {}: {:?}", repo_id, e);
-
-                    insert_log(pool, repo_id, "error_clone_job", "Cloning failed.").await?;
-                } else {
-                    insert_log(
-                        pool,
-                        repo_id,
-                        "finished_clone_job",
-                        "Cloning finished succesfully!",
-                    )
-                    .await?;
-                }
-            }
-            Err(panic) => {
-                eprintln!(
-                    "Panic occurred while creating clone future for repo {}: {:?}",
-                    repo_id, panic
-                );
-                insert_log(pool, repo_id, "panic_clone_job", "Cloning job panic!").await?;
+        heartbeat.beat();
+
+        // every log line and span event of one run carries the same job id
+        let job_id = Uuid::new_v4();
+        let span = tracing::info_span!("clone_job", repository_id = %repo.id, job_id = %job_id);
+
+        clone_worker_run_job(
+            pool,
+            notifier,
+            metrics,
+            &config.storage,
+            &keys,
+            repo,
+            job_id,
+        )
+        .instrument(span)
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn clone_worker_run_job(
+    pool: &Pool<ConnectionManager<PgConnection>>,
+    notifier: &Arc<Notifier>,
+    metrics: &Arc<Metrics>,
+    storage: &StorageConfig,
+    keys: &MasterKeys,
+    repo: RepositoryModel,
+    job_id: Uuid,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let repo_id = repo.id;
+
+    if repo.sync_requested_at.is_some() {
+        clone_worker_clear_sync_request(pool, repo_id).await?;
+    }
+
+    tracing::info!("starting clone job");
+    insert_log(
+        pool,
+        repo_id,
+        Some(job_id),
+        "starting_clone_job",
+        "Starting clone job...",
+    )
+    .await?;
+
+    let future = catch_unwind(AssertUnwindSafe(|| {
+        clone_worker_run_single_repo(pool, storage, keys, repo, metrics)
+    }));
+
+    match future {
+        Ok(fut) => {
+            if let Err(e) = fut.await {
+                tracing::warn!(error = %e, "clone job failed");
+                metrics.record_sync(OUTCOME_FAILED);
+
+                clone_worker_mark_repo_as_failed(pool, repo_id).await?;
+                insert_log(
+                    pool,
+                    repo_id,
+                    Some(job_id),
+                    "error_clone_job",
+                    "Cloning failed.",
+                )
+                .await?;
+                record_health(pool, notifier, repo_id, false).await;
+            } else {
+                tracing::info!("clone job finished");
+                metrics.record_sync(OUTCOME_SUCCEEDED);
+                insert_log(
+                    pool,
+                    repo_id,
+                    Some(job_id),
+                    "finished_clone_job",
+                    "Cloning finished succesfully!",
+                )
+                .await?;
+                record_health(pool, notifier, repo_id, true).await;
             }
         }
+        Err(panic) => {
+            tracing::error!(panic = ?panic, "clone job panicked while creating its future");
+            metrics.record_sync(OUTCOME_PANICKED);
+            clone_worker_mark_repo_as_failed(pool, repo_id).await?;
+            insert_log(
+                pool,
+                repo_id,
+                Some(job_id),
+                "panic_clone_job",
+                "Cloning job panic!",
+            )
+            .await?;
+            record_health(pool, notifier, repo_id, false).await;
+        }
     }
 
     Ok(())
 }
 
+/// Notification failures never stop the worker.
+async fn record_health(
+    pool: &Pool<ConnectionManager<PgConnection>>,
+    notifier: &Arc<Notifier>,
+    repo_id: Uuid,
+    succeeded: bool,
+) {
+    if let Err(e) = record_sync_result(pool, notifier, repo_id, succeeded).await {
+        tracing::error!(error = %e, "failed to record repository health");
+    }
+}
+
 pub async fn clone_worker_run_single_repo(
     pool: &Pool<ConnectionManager<PgConnection>>,
+    storage: &StorageConfig,
+    keys: &MasterKeys,
     repo: RepositoryModel,
+    metrics: &Metrics,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // check for ssh binary
     let ssh_check = Command::new("ssh").arg("-V").output().await;
@@ -102,20 +203,25 @@ pub async fn clone_worker_run_single_repo(
 
     let repo_id = repo.id.to_string();
 
-    let source_key_opt = repo
+    let source_key = repo
         .git_source_secret_key
-        .as_ref()
-        .map(|k| sanitize_ssh_key(k));
-    let target_key_opt = repo
+        .as_deref()
+        .map(|k| keys.open(k))
+        .transpose()?;
+    let target_key = repo
         .git_target_secret_key
-        .as_ref()
-        .map(|k| sanitize_ssh_key(k));
+        .as_deref()
+        .map(|k| keys.open(k))
+        .transpose()?;
 
-    let repo_dir = PathBuf::from(CLONE_STORAGE_PATH).join(format!("{}.git", repo_id));
+    let source_key_opt = source_key.as_ref().map(|k| sanitize_ssh_key(k));
+    let target_key_opt = target_key.as_ref().map(|k| sanitize_ssh_key(k));
+
+    let repo_dir = storage.repositories_path.join(format!("{}.git", repo_id));
 
     // Ensure clone & key directories exist
-    fs::create_dir_all(CLONE_STORAGE_PATH).await?;
-    fs::create_dir_all(KEY_STORAGE_PATH).await?;
+    fs::create_dir_all(&storage.repositories_path).await?;
+    fs::create_dir_all(&storage.keys_path).await?;
 
     // Cleanup target dir if it already exists
     if fs::metadata(&repo_dir).await.is_ok() {
@@ -123,14 +229,14 @@ pub async fn clone_worker_run_single_repo(
     }
 
     // Write source key to file
-    let source_key_path = PathBuf::from(KEY_STORAGE_PATH).join(format!("{}_source_key", repo_id));
-    if let Some(source_key) = repo.git_source_secret_key {
+    let source_key_path = storage.keys_path.join(format!("{}_source_key", repo_id));
+    if let Some(source_key) = source_key {
         write_key_file(&source_key_path, &source_key).await?;
     }
 
     // Write target key to file
-    let target_key_path = PathBuf::from(KEY_STORAGE_PATH).join(format!("{}_target_key", repo_id));
-    if let Some(target_key) = repo.git_target_secret_key {
+    let target_key_path = storage.keys_path.join(format!("{}_target_key", repo_id));
+    if let Some(target_key) = target_key {
         write_key_file(&target_key_path, &target_key).await?;
     }
 
@@ -152,6 +258,7 @@ pub async fn clone_worker_run_single_repo(
         Ok(())
     };
 
+    #[allow(clippy::collapsible_if)]
     if let Some(source_key) = source_key_opt.as_ref() {
         if !source_key.trim().is_empty() {
             check_key(&source_key_path)?;
@@ -188,7 +295,9 @@ pub async fn clone_worker_run_single_repo(
     if let Some(ref ssh_cmd) = git_ssh_source {
         cmd.env("GIT_SSH_COMMAND", ssh_cmd);
     }
+    let started = Instant::now();
     let output = cmd.output().await?;
+    metrics.observe_phase(PHASE_CLONE, started.elapsed());
     if !output.status.success() {
         cleanup_keys(
             &source_key_path.unwrap_or_default(),
@@ -224,13 +333,25 @@ pub async fn clone_worker_run_single_repo(
         .as_ref()
         .map(|p| format!("ssh -i {} -o StrictHostKeyChecking=no", p.display()));
 
+    // with ref filters only the matching refs are force pushed, and pruned on the target
+    // when they disappear from the source
     let mut cmd = Command::new("git");
-    cmd.current_dir(&repo_dir)
-        .args(["push", "--mirror", "origin"]);
+    cmd.current_dir(&repo_dir);
+    if repo.ref_filters.is_empty() {
+        cmd.args(["push", "--mirror", "origin"]);
+    } else {
+        cmd.args(["push", "--prune", "origin"]).args(
+            repo.ref_filters
+                .iter()
+                .map(|filter| format!("+{}:{}", filter, filter)),
+        );
+    }
     if let Some(ref ssh_cmd) = git_ssh_target {
         cmd.env("GIT_SSH_COMMAND", ssh_cmd);
     }
+    let started = Instant::now();
     let output = cmd.output().await?;
+    metrics.observe_phase(PHASE_PUSH, started.elapsed());
     if !output.status.success() {
         cleanup_keys(
             &source_key_path.unwrap_or_default(),
@@ -238,7 +359,7 @@ pub async fn clone_worker_run_single_repo(
         )
         .await;
         return Err(format!(
-            "git push --mirror failed: {}",
+            "git push failed: {}",
             String::from_utf8_lossy(&output.stderr)
         )
         .into());
@@ -266,7 +387,48 @@ pub async fn clone_worker_mark_repo_as_cloned(
             .get()
             .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)?;
         diesel::update(repository.filter(id.eq(repo_id)))
-            .set(last_clone_at.eq(Utc::now().naive_utc()))
+            .set((
+                last_clone_at.eq(Utc::now().naive_utc()),
+                last_sync_status.eq(SYNC_STATUS_SUCCEEDED),
+            ))
+            .execute(&mut conn)
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)?;
+        Ok(())
+    })
+    .await?
+}
+
+pub async fn clone_worker_mark_repo_as_failed(
+    pool: &Pool<ConnectionManager<PgConnection>>,
+    repo_id: uuid::Uuid,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let pool = pool.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool
+            .get()
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)?;
+        diesel::update(repository.filter(id.eq(repo_id)))
+            .set(last_sync_status.eq(SYNC_STATUS_FAILED))
+            .execute(&mut conn)
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)?;
+        Ok(())
+    })
+    .await?
+}
+
+pub async fn clone_worker_clear_sync_request(
+    pool: &Pool<ConnectionManager<PgConnection>>,
+    repo_id: uuid::Uuid,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let pool = pool.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool
+            .get()
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)?;
+        diesel::update(repository.filter(id.eq(repo_id)))
+            .set(sync_requested_at.eq(None::<chrono::DateTime<Utc>>))
             .execute(&mut conn)
             .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)?;
         Ok(())
@@ -300,6 +462,7 @@ async fn cleanup_keys(source: &PathBuf, target: &PathBuf) {
 pub async fn insert_log(
     pool: &Pool<ConnectionManager<PgConnection>>,
     repository_id: Uuid,
+    job_id: Option<Uuid>,
     log_type: &str,
     log_message: &str,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
@@ -317,6 +480,7 @@ pub async fn insert_log(
                 repository_id,
                 type_: &log_type,
                 message: &log_message,
+                job_id,
             };
 
             diesel::insert_into(crate::schema::repository_logs::table)
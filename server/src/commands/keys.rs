@@ -0,0 +1,111 @@
+use diesel::prelude::*;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::commands::{CommandOutput, CommandResult};
+use crate::config::KeysConfig;
+use crate::schema::repository;
+use crate::utils::master_key::MasterKeys;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RotateOutput {
+    /// Repositories whose keys were re-encrypted under the current master key.
+    rotated: usize,
+    unchanged: usize,
+}
+
+type StoredKeys = (Uuid, String, Option<String>, Option<String>);
+
+enum RotateError {
+    Database(diesel::result::Error),
+    Key(String),
+}
+
+impl From<diesel::result::Error> for RotateError {
+    fn from(e: diesel::result::Error) -> Self {
+        RotateError::Database(e)
+    }
+}
+
+/// Re-encrypts every repository key under `keys.master_key`, including keys stored before
+/// a master key was set. It runs in one transaction, so nothing is written when one of
+/// the keys is encrypted under a key missing from `keys.previous_master_keys`.
+pub fn rotate_master(connection: &mut PgConnection, config: &KeysConfig) -> CommandResult {
+    let keys = MasterKeys::from_config(config);
+    if !keys.is_configured() {
+        return Err("Set keys.master_key to the master key to rotate to".to_string());
+    }
+
+    let (rotated, unchanged) = connection
+        .transaction(|connection| reseal_keys(connection, &keys))
+        .map_err(|e| match e {
+            RotateError::Database(e) => format!("Failed to store re-encrypted keys: {}", e),
+            RotateError::Key(e) => e,
+        })?;
+
+    CommandOutput::new(
+        "Repository keys encrypted under the current master key",
+        RotateOutput { rotated, unchanged },
+    )
+}
+
+/// Locks the rows holding keys until the transaction ends, so neither the server nor a
+/// second rotation replaces a key between reading and re-encrypting it. Returns how many
+/// repositories were re-encrypted and how many already were.
+fn reseal_keys(
+    connection: &mut PgConnection,
+    keys: &MasterKeys,
+) -> Result<(usize, usize), RotateError> {
+    let stored = repository::table
+        .filter(
+            repository::git_source_secret_key
+                .is_not_null()
+                .or(repository::git_target_secret_key.is_not_null()),
+        )
+        .select((
+            repository::id,
+            repository::name,
+            repository::git_source_secret_key,
+            repository::git_target_secret_key,
+        ))
+        .for_update()
+        .load::<StoredKeys>(connection)?;
+
+    let mut rotated = 0;
+    for (id, name, source_key, target_key) in &stored {
+        let reseal = |key: &Option<String>| match key {
+            Some(key) if !keys.is_current(key) => keys
+                .open(key)
+                .map(|plain| Some(keys.seal(&plain)))
+                .map_err(|e| RotateError::Key(format!("Repository {} ({}): {}", name, id, e))),
+            other => Ok(other.clone()),
+        };
+        let (source, target) = (reseal(source_key)?, reseal(target_key)?);
+        if source == *source_key && target == *target_key {
+            continue;
+        }
+
+        // only replace the ciphertexts that were decrypted
+        let updated = diesel::update(
+            repository::table
+                .find(id)
+                .filter(repository::git_source_secret_key.is_not_distinct_from(source_key))
+                .filter(repository::git_target_secret_key.is_not_distinct_from(target_key)),
+        )
+        .set((
+            repository::git_source_secret_key.eq(source),
+            repository::git_target_secret_key.eq(target),
+        ))
+        .execute(connection)?;
+        if updated != 1 {
+            return Err(RotateError::Key(format!(
+                "Repository {} ({}) changed while rotating, nothing was stored",
+                name, id
+            )));
+        }
+        rotated += 1;
+    }
+
+    Ok((rotated, stored.len() - rotated))
+}
@@ -0,0 +1,105 @@
+pub mod keys;
+pub mod repo;
+pub mod storage;
+pub mod user;
+
+use diesel::PgConnection;
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::cli::Command;
+use crate::config::Config;
+use crate::db::DbConnection;
+use crate::db::migrations::run_pending_migrations;
+use crate::middlewares::client::ClientInfo;
+use crate::utils::response::ApiResponse;
+
+/// Recorded as the user agent of audit events written by commands, which have no actor.
+const CLI_USER_AGENT: &str = "gitmirrors-cli";
+
+/// Successful result of a command, printed as the `message` and `data` of an [`ApiResponse`].
+pub struct CommandOutput {
+    pub message: String,
+    pub data: Value,
+}
+
+impl CommandOutput {
+    pub fn new<T: Serialize>(message: &str, data: T) -> CommandResult {
+        Ok(CommandOutput {
+            message: message.to_string(),
+            data: serde_json::to_value(data).map_err(|e| e.to_string())?,
+        })
+    }
+}
+
+pub type CommandResult = Result<CommandOutput, String>;
+
+pub fn cli_client() -> ClientInfo {
+    ClientInfo {
+        user_agent: Some(CLI_USER_AGENT.to_string()),
+        ip_address: None,
+    }
+}
+
+fn connection(
+    pool: &DbConnection,
+) -> Result<PooledConnection<ConnectionManager<PgConnection>>, String> {
+    pool.get()
+        .map_err(|e| format!("Failed to get DB connection: {}", e))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MigrateOutput {
+    applied: Vec<String>,
+}
+
+/// Runs the command and prints its result as JSON on stdout. Returns the exit code.
+pub fn run(command: Command, config: &Config, pool: &DbConnection) -> i32 {
+    let result = connection(pool).and_then(|mut connection| {
+        let applied = run_pending_migrations(&mut connection)
+            .map_err(|e| format!("Database migration failed: {}", e))?;
+
+        // every other command needs the schema to be current as well
+        match command {
+            Command::DbMigrate => {
+                CommandOutput::new("Database schema is up to date", MigrateOutput { applied })
+            }
+            Command::UserCreate {
+                username,
+                admin,
+                password_stdin,
+            } => user::create(&mut connection, &username, admin, password_stdin),
+            Command::UserResetPassword {
+                username,
+                password_stdin,
+            } => user::reset_password(&mut connection, &username, password_stdin),
+            Command::UserDisable { username } => {
+                user::set_disabled(&mut connection, &username, true)
+            }
+            Command::UserEnable { username } => {
+                user::set_disabled(&mut connection, &username, false)
+            }
+            Command::RepoList => repo::list(&mut connection),
+            Command::RepoSync { id } => repo::sync(&mut connection, id),
+            Command::RepoEnable { id } => repo::set_enabled(&mut connection, id, true),
+            Command::RepoDisable { id } => repo::set_enabled(&mut connection, id, false),
+            Command::KeysRotateMaster => keys::rotate_master(&mut connection, &config.keys),
+            Command::StorageGc { dry_run } => {
+                storage::gc(&mut connection, &config.storage, dry_run)
+            }
+        }
+    });
+
+    let (response, code) = match result {
+        Ok(output) => (ApiResponse::success(&output.message, output.data), 0),
+        Err(message) => (ApiResponse::error(&message), 1),
+    };
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&response).expect("Failed to serialize command output")
+    );
+    code
+}
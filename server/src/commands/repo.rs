@@ -0,0 +1,145 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::commands::{CommandOutput, CommandResult, cli_client};
+use crate::models::RepositoryModel;
+use crate::schema::repository;
+use crate::utils::audit::{
+    AUDIT_REPOSITORY_DISABLE, AUDIT_REPOSITORY_ENABLE, AUDIT_REPOSITORY_SYNC,
+    AUDIT_TARGET_REPOSITORY, AuditChanges, AuditTarget, record_audit_event,
+};
+
+/// A repository without its git URLs and keys, which can carry credentials.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RepositorySummary {
+    id: Uuid,
+    name: String,
+    user_id: Option<Uuid>,
+    team_id: Option<Uuid>,
+    is_enabled: bool,
+    git_clone_period_seconds: i32,
+    last_clone_at: Option<DateTime<Utc>>,
+    last_sync_status: Option<String>,
+    health_state: String,
+    sync_requested_at: Option<DateTime<Utc>>,
+}
+
+impl From<RepositoryModel> for RepositorySummary {
+    fn from(repo: RepositoryModel) -> Self {
+        RepositorySummary {
+            id: repo.id,
+            name: repo.name,
+            user_id: repo.user_id,
+            team_id: repo.team_id,
+            is_enabled: repo.is_enabled,
+            git_clone_period_seconds: repo.git_clone_period_seconds,
+            last_clone_at: repo.last_clone_at,
+            last_sync_status: repo.last_sync_status,
+            health_state: repo.health_state,
+            sync_requested_at: repo.sync_requested_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RepositoryListOutput {
+    repositories: Vec<RepositorySummary>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RepositoryOutput {
+    repository: RepositorySummary,
+}
+
+fn find_repository(connection: &mut PgConnection, id: Uuid) -> Result<RepositoryModel, String> {
+    repository::table
+        .find(id)
+        .first::<RepositoryModel>(connection)
+        .optional()
+        .map_err(|e| format!("Database error: {}", e))?
+        .ok_or_else(|| format!("Repository {} not found", id))
+}
+
+pub fn list(connection: &mut PgConnection) -> CommandResult {
+    let repositories = repository::table
+        .order(repository::name.asc())
+        .load::<RepositoryModel>(connection)
+        .map_err(|e| format!("Failed to fetch repositories: {}", e))?;
+
+    CommandOutput::new(
+        "Success",
+        RepositoryListOutput {
+            repositories: repositories.into_iter().map(Into::into).collect(),
+        },
+    )
+}
+
+/// Same as the sync endpoint, the clone worker picks the request up on its next run.
+pub fn sync(connection: &mut PgConnection, id: Uuid) -> CommandResult {
+    let repo = find_repository(connection, id)?;
+    if !repo.is_enabled {
+        return Err(format!("Repository {} is disabled", id));
+    }
+
+    let updated = diesel::update(repository::table.find(id))
+        .set(repository::sync_requested_at.eq(Utc::now()))
+        .get_result::<RepositoryModel>(connection)
+        .map_err(|e| format!("Failed to request repository sync: {}", e))?;
+
+    record_audit_event(
+        connection,
+        None,
+        &cli_client(),
+        AUDIT_REPOSITORY_SYNC,
+        Some(AuditTarget::new(AUDIT_TARGET_REPOSITORY, updated.id)),
+        AuditChanges::none(),
+    );
+
+    CommandOutput::new(
+        "Repository sync requested",
+        RepositoryOutput {
+            repository: updated.into(),
+        },
+    )
+}
+
+pub fn set_enabled(connection: &mut PgConnection, id: Uuid, enabled: bool) -> CommandResult {
+    let repo = find_repository(connection, id)?;
+
+    let updated = diesel::update(repository::table.find(id))
+        .set((
+            repository::is_enabled.eq(enabled),
+            repository::updated_at.eq(Utc::now()),
+        ))
+        .get_result::<RepositoryModel>(connection)
+        .map_err(|e| format!("Failed to update repository: {}", e))?;
+
+    record_audit_event(
+        connection,
+        None,
+        &cli_client(),
+        if enabled {
+            AUDIT_REPOSITORY_ENABLE
+        } else {
+            AUDIT_REPOSITORY_DISABLE
+        },
+        Some(AuditTarget::new(AUDIT_TARGET_REPOSITORY, updated.id)),
+        AuditChanges::diff(Some(&repo), Some(&updated)),
+    );
+
+    CommandOutput::new(
+        if enabled {
+            "Repository enabled"
+        } else {
+            "Repository disabled"
+        },
+        RepositoryOutput {
+            repository: updated.into(),
+        },
+    )
+}
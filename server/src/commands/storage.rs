@@ -0,0 +1,109 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use diesel::prelude::*;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::commands::{CommandOutput, CommandResult};
+use crate::config::StorageConfig;
+use crate::metrics::directory_size;
+use crate::schema::repository;
+
+/// Suffixes of the key files the clone worker writes for a repository.
+const KEY_FILE_SUFFIXES: [&str; 2] = ["_source_key", "_target_key"];
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RemovedEntry {
+    path: PathBuf,
+    bytes: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GcOutput {
+    dry_run: bool,
+    removed: Vec<RemovedEntry>,
+    removed_bytes: u64,
+}
+
+/// Id of the repository a storage entry belongs to, if it is named like one.
+fn repository_id(name: &str) -> Option<Uuid> {
+    let stem = name.strip_suffix(".git").or_else(|| {
+        KEY_FILE_SUFFIXES
+            .iter()
+            .find_map(|suffix| name.strip_suffix(suffix))
+    })?;
+    Uuid::parse_str(stem).ok()
+}
+
+/// Entries of `dir` that belong to repositories which no longer exist. A missing directory
+/// has nothing to collect.
+fn orphaned_entries(dir: &Path, known: &HashSet<Uuid>) -> Result<Vec<PathBuf>, String> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", dir.display(), e)),
+    };
+
+    let mut orphaned: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .and_then(repository_id)
+                .is_some_and(|id| !known.contains(&id))
+        })
+        .map(|entry| entry.path())
+        .collect();
+    orphaned.sort();
+
+    Ok(orphaned)
+}
+
+/// Removes mirrors and key files left behind by deleted repositories.
+pub fn gc(connection: &mut PgConnection, storage: &StorageConfig, dry_run: bool) -> CommandResult {
+    let known: HashSet<Uuid> = repository::table
+        .select(repository::id)
+        .load::<Uuid>(connection)
+        .map_err(|e| format!("Failed to fetch repositories: {}", e))?
+        .into_iter()
+        .collect();
+
+    let mut removed = Vec::new();
+    for dir in [&storage.repositories_path, &storage.keys_path] {
+        for path in orphaned_entries(dir, &known)? {
+            let bytes = if path.is_dir() {
+                directory_size(&path)
+            } else {
+                std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0)
+            };
+
+            if !dry_run {
+                let result = if path.is_dir() {
+                    std::fs::remove_dir_all(&path)
+                } else {
+                    std::fs::remove_file(&path)
+                };
+                result.map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+            }
+
+            removed.push(RemovedEntry { path, bytes });
+        }
+    }
+
+    CommandOutput::new(
+        if dry_run {
+            "Storage garbage collection dry run"
+        } else {
+            "Storage garbage collected"
+        },
+        GcOutput {
+            dry_run,
+            removed_bytes: removed.iter().map(|e| e.bytes).sum(),
+            removed,
+        },
+    )
+}
@@ -0,0 +1,218 @@
+use std::io::Read;
+
+use chrono::Utc;
+use diesel::prelude::*;
+use serde::Serialize;
+use validator::Validate;
+
+use crate::commands::{CommandOutput, CommandResult, cli_client};
+use crate::models::{PublicUser, UserModel};
+use crate::routes::user::{
+    MAX_PASSWORD_LENGTH, MAX_USERNAME_LENGTH, MIN_PASSWORD_LENGTH, MIN_USERNAME_LENGTH,
+    UserChangePasswordForm, UserLoginForm,
+};
+use crate::schema::{session, user};
+use crate::utils::audit::{
+    AUDIT_TARGET_USER, AUDIT_USER_CREATE, AUDIT_USER_DISABLE, AUDIT_USER_ENABLE,
+    AUDIT_USER_PASSWORD_RESET, AuditChanges, AuditTarget, record_audit_event,
+};
+use crate::utils::crypto::{generate_random_string, hash_password};
+
+const GENERATED_PASSWORD_LENGTH: usize = 24;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UserOutput {
+    user: PublicUser,
+    /// Only set when the password was generated, it is not shown again.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    generated_password: Option<String>,
+}
+
+fn find_user(connection: &mut PgConnection, username: &str) -> Result<UserModel, String> {
+    user::table
+        .filter(user::username.eq(username))
+        .select(UserModel::as_select())
+        .first::<UserModel>(connection)
+        .optional()
+        .map_err(|e| format!("Database error: {}", e))?
+        .ok_or_else(|| format!("User {} not found", username))
+}
+
+/// Checked like the username of the login form, a user it rejects could never sign in.
+fn validate_username(username: &str) -> Result<(), String> {
+    let form = UserLoginForm {
+        username,
+        password: "",
+    };
+    match form.validate() {
+        Err(errors) if errors.field_errors().contains_key("username") => Err(format!(
+            "Username must be between {} and {} characters long",
+            MIN_USERNAME_LENGTH, MAX_USERNAME_LENGTH
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Reads the password from the first line of stdin, or generates one. Passing it as an
+/// argument would leave it in the shell history and the process list.
+fn new_password(password_stdin: bool) -> Result<(String, Option<String>), String> {
+    let password = if password_stdin {
+        let mut input = String::new();
+        std::io::stdin()
+            .read_to_string(&mut input)
+            .map_err(|e| format!("Failed to read the password from stdin: {}", e))?;
+        input.lines().next().unwrap_or_default().to_string()
+    } else {
+        generate_random_string(GENERATED_PASSWORD_LENGTH)
+    };
+
+    // the same limits as a password set through the API, so it can be used to log in
+    let form = UserChangePasswordForm {
+        password: &password,
+    };
+    if form.validate().is_err() {
+        return Err(format!(
+            "Password must be between {} and {} characters long",
+            MIN_PASSWORD_LENGTH, MAX_PASSWORD_LENGTH
+        ));
+    }
+
+    let hash = hash_password(&password).map_err(|_| "Failed to hash password".to_string())?;
+    Ok((hash, Some(password).filter(|_| !password_stdin)))
+}
+
+pub fn create(
+    connection: &mut PgConnection,
+    username: &str,
+    admin: bool,
+    password_stdin: bool,
+) -> CommandResult {
+    let username = username.trim();
+    validate_username(username)?;
+
+    let (password_hash, generated_password) = new_password(password_stdin)?;
+
+    let created = diesel::insert_into(user::table)
+        .values((
+            user::username.eq(username),
+            user::password_hash.eq(password_hash),
+            user::is_admin.eq(admin),
+        ))
+        .on_conflict(user::username)
+        .do_nothing()
+        .returning(UserModel::as_returning())
+        .get_result::<UserModel>(connection)
+        .optional()
+        .map_err(|e| format!("Failed to create user: {}", e))?
+        .ok_or_else(|| format!("User {} already exists", username))?;
+
+    record_audit_event(
+        connection,
+        None,
+        &cli_client(),
+        AUDIT_USER_CREATE,
+        Some(AuditTarget::new(AUDIT_TARGET_USER, created.id)),
+        AuditChanges::none(),
+    );
+
+    CommandOutput::new(
+        "User created",
+        UserOutput {
+            user: created.into(),
+            generated_password,
+        },
+    )
+}
+
+/// Also signs the user out everywhere. A login lockout is left to expire, failed attempts
+/// stay visible to admins.
+pub fn reset_password(
+    connection: &mut PgConnection,
+    username: &str,
+    password_stdin: bool,
+) -> CommandResult {
+    let dbusr = find_user(connection, username)?;
+    let (password_hash, generated_password) = new_password(password_stdin)?;
+
+    let updated = connection
+        .transaction::<_, diesel::result::Error, _>(|connection| {
+            let updated = diesel::update(user::table.filter(user::id.eq(dbusr.id)))
+                .set(user::password_hash.eq(password_hash))
+                .returning(UserModel::as_returning())
+                .get_result::<UserModel>(connection)?;
+
+            diesel::delete(session::table.filter(session::user_id.eq(dbusr.id)))
+                .execute(connection)?;
+
+            Ok(updated)
+        })
+        .map_err(|e| format!("Failed to reset password: {}", e))?;
+
+    record_audit_event(
+        connection,
+        None,
+        &cli_client(),
+        AUDIT_USER_PASSWORD_RESET,
+        Some(AuditTarget::new(AUDIT_TARGET_USER, updated.id)),
+        AuditChanges::none(),
+    );
+
+    CommandOutput::new(
+        "Password reset",
+        UserOutput {
+            user: updated.into(),
+            generated_password,
+        },
+    )
+}
+
+/// Disabling signs the user out. Their API tokens are kept but rejected until re-enabled.
+pub fn set_disabled(
+    connection: &mut PgConnection,
+    username: &str,
+    disabled: bool,
+) -> CommandResult {
+    let dbusr = find_user(connection, username)?;
+
+    let updated = connection
+        .transaction::<_, diesel::result::Error, _>(|connection| {
+            let updated = diesel::update(user::table.filter(user::id.eq(dbusr.id)))
+                .set(user::disabled_at.eq(disabled.then(Utc::now)))
+                .returning(UserModel::as_returning())
+                .get_result::<UserModel>(connection)?;
+
+            if disabled {
+                diesel::delete(session::table.filter(session::user_id.eq(dbusr.id)))
+                    .execute(connection)?;
+            }
+
+            Ok(updated)
+        })
+        .map_err(|e| format!("Failed to update user: {}", e))?;
+
+    record_audit_event(
+        connection,
+        None,
+        &cli_client(),
+        if disabled {
+            AUDIT_USER_DISABLE
+        } else {
+            AUDIT_USER_ENABLE
+        },
+        Some(AuditTarget::new(AUDIT_TARGET_USER, updated.id)),
+        AuditChanges::none(),
+    );
+
+    CommandOutput::new(
+        if disabled {
+            "User disabled"
+        } else {
+            "User enabled"
+        },
+        UserOutput {
+            user: updated.into(),
+            generated_password: None,
+        },
+    )
+}
@@ -0,0 +1,63 @@
+use std::collections::HashSet;
+
+use diesel::migration::MigrationSource;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::BigInt;
+use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
+
+type MigrationResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+/// Key of the session advisory lock held while migrating, "gitmirro" in ASCII.
+const MIGRATION_LOCK_KEY: i64 = 0x6769_746d_6972_726f;
+
+/// Applies pending migrations and returns their versions. Replicas starting at the same time
+/// wait for each other on an advisory lock, the ones that get it later find nothing to do.
+pub fn run_pending_migrations(connection: &mut PgConnection) -> MigrationResult<Vec<String>> {
+    sql_query("SELECT pg_advisory_lock($1)")
+        .bind::<BigInt, _>(MIGRATION_LOCK_KEY)
+        .execute(connection)?;
+
+    let result = migrate(connection);
+
+    // released explicitly, the connection goes back to the pool afterwards
+    let unlocked = sql_query("SELECT pg_advisory_unlock($1)")
+        .bind::<BigInt, _>(MIGRATION_LOCK_KEY)
+        .execute(connection);
+
+    let applied = result?;
+    unlocked?;
+    Ok(applied)
+}
+
+fn migrate(connection: &mut PgConnection) -> MigrationResult<Vec<String>> {
+    let known: HashSet<String> = MigrationSource::<Pg>::migrations(&MIGRATIONS)?
+        .iter()
+        .map(|m| m.name().version().to_string())
+        .collect();
+
+    // a newer binary already migrated this database, running against it could corrupt data
+    let mut unknown: Vec<String> = connection
+        .applied_migrations()?
+        .into_iter()
+        .map(|v| v.to_string())
+        .filter(|v| !known.contains(v))
+        .collect();
+    if !unknown.is_empty() {
+        unknown.sort();
+        return Err(format!(
+            "the database schema is newer than this binary, unknown migrations: {}",
+            unknown.join(", ")
+        )
+        .into());
+    }
+
+    Ok(connection
+        .run_pending_migrations(MIGRATIONS)?
+        .into_iter()
+        .map(|v| v.to_string())
+        .collect())
+}
@@ -1,3 +1,5 @@
+pub mod migrations;
+
 use diesel::prelude::*;
 use diesel::r2d2::{self, ConnectionManager};
 
@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use diesel::{RunQueryDsl, sql_query};
+use rocket::tokio;
+use serde::Serialize;
+use tokio::process::Command;
+
+use crate::config::HealthConfig;
+use crate::db::DbConnection;
+
+pub const STATUS_OK: &str = "ok";
+pub const STATUS_FAILING: &str = "failing";
+
+const DB_TIMEOUT: Duration = Duration::from_secs(2);
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Last time the clone worker loop made progress, as a unix timestamp.
+pub struct WorkerHeartbeat(AtomicI64);
+
+impl WorkerHeartbeat {
+    pub fn new() -> Self {
+        WorkerHeartbeat(AtomicI64::new(Utc::now().timestamp()))
+    }
+
+    pub fn beat(&self) {
+        self.0.store(Utc::now().timestamp(), Ordering::Relaxed);
+    }
+
+    fn age_seconds(&self) -> i64 {
+        Utc::now().timestamp() - self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentHealth {
+    pub status: &'static str,
+    /// Versions, paths and database errors are only logged, the endpoints are unauthenticated.
+    #[serde(skip_serializing)]
+    pub detail: String,
+}
+
+impl ComponentHealth {
+    fn ok(detail: impl Into<String>) -> Self {
+        ComponentHealth {
+            status: STATUS_OK,
+            detail: detail.into(),
+        }
+    }
+
+    fn failing(detail: impl Into<String>) -> Self {
+        ComponentHealth {
+            status: STATUS_FAILING,
+            detail: detail.into(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthReport {
+    pub status: &'static str,
+    pub components: BTreeMap<&'static str, ComponentHealth>,
+}
+
+impl HealthReport {
+    fn new(components: BTreeMap<&'static str, ComponentHealth>) -> Self {
+        let healthy = components.values().all(|c| c.status == STATUS_OK);
+        HealthReport {
+            status: if healthy { STATUS_OK } else { STATUS_FAILING },
+            components,
+        }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.status == STATUS_OK
+    }
+
+    /// Logs why components fail, since the report itself only carries their status.
+    pub fn log_failures(&self) {
+        for (component, health) in &self.components {
+            if health.status != STATUS_OK {
+                tracing::warn!(component, detail = %health.detail, "health check failing");
+            }
+        }
+    }
+}
+
+/// Only fails when the process should be restarted, that is when the worker is stuck.
+pub fn check_liveness(config: &HealthConfig, heartbeat: &WorkerHeartbeat) -> HealthReport {
+    HealthReport::new(BTreeMap::from([(
+        "worker",
+        check_worker(config, heartbeat),
+    )]))
+}
+
+/// Fails while any dependency needed to serve requests or sync mirrors is unavailable.
+pub async fn check_readiness(
+    config: &HealthConfig,
+    heartbeat: &WorkerHeartbeat,
+    pool: &DbConnection,
+    storage_path: &Path,
+) -> HealthReport {
+    let (database, git, ssh, storage) = tokio::join!(
+        check_database(pool),
+        check_command("git", "--version"),
+        check_command("ssh", "-V"),
+        check_storage(storage_path),
+    );
+
+    HealthReport::new(BTreeMap::from([
+        ("database", database),
+        ("pool", check_pool(pool)),
+        ("git", git),
+        ("ssh", ssh),
+        ("storage", storage),
+        ("disk", check_disk(config, storage_path)),
+        ("worker", check_worker(config, heartbeat)),
+    ]))
+}
+
+fn check_worker(config: &HealthConfig, heartbeat: &WorkerHeartbeat) -> ComponentHealth {
+    let age = heartbeat.age_seconds();
+    let detail = format!("last heartbeat {}s ago", age);
+
+    if age > config.worker_stuck_seconds {
+        ComponentHealth::failing(detail)
+    } else {
+        ComponentHealth::ok(detail)
+    }
+}
+
+async fn check_database(pool: &DbConnection) -> ComponentHealth {
+    let pool = pool.clone();
+
+    let result = tokio::task::spawn_blocking(move || -> Result<Duration, String> {
+        let started = Instant::now();
+        let mut conn = pool.get_timeout(DB_TIMEOUT).map_err(|e| e.to_string())?;
+        sql_query("SELECT 1")
+            .execute(&mut conn)
+            .map_err(|e| e.to_string())?;
+        Ok(started.elapsed())
+    })
+    .await;
+
+    match result {
+        Ok(Ok(elapsed)) => ComponentHealth::ok(format!("responded in {}ms", elapsed.as_millis())),
+        Ok(Err(e)) => ComponentHealth::failing(e),
+        Err(e) => ComponentHealth::failing(e.to_string()),
+    }
+}
+
+/// All connections being checked out means requests are queueing for one.
+fn check_pool(pool: &DbConnection) -> ComponentHealth {
+    let state = pool.state();
+    let in_use = state.connections - state.idle_connections;
+    let detail = format!("{} of {} connections in use", in_use, pool.max_size());
+
+    if in_use >= pool.max_size() {
+        ComponentHealth::failing(detail)
+    } else {
+        ComponentHealth::ok(detail)
+    }
+}
+
+async fn check_command(program: &str, version_arg: &str) -> ComponentHealth {
+    let output = tokio::time::timeout(
+        COMMAND_TIMEOUT,
+        Command::new(program)
+            .arg(version_arg)
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await;
+
+    match output {
+        Ok(Ok(output)) if output.status.success() => {
+            // ssh prints its version to stderr
+            let text = if output.stdout.is_empty() {
+                output.stderr
+            } else {
+                output.stdout
+            };
+            ComponentHealth::ok(String::from_utf8_lossy(&text).trim())
+        }
+        Ok(Ok(output)) => {
+            ComponentHealth::failing(format!("{} exited with {}", program, output.status))
+        }
+        Ok(Err(e)) => ComponentHealth::failing(format!("{} could not be run: {}", program, e)),
+        Err(_) => ComponentHealth::failing(format!("{} timed out", program)),
+    }
+}
+
+async fn check_storage(storage_path: &Path) -> ComponentHealth {
+    let probe = storage_path.join(".health-check");
+
+    let result = async {
+        tokio::fs::create_dir_all(storage_path).await?;
+        tokio::fs::write(&probe, b"ok").await?;
+        tokio::fs::remove_file(&probe).await
+    }
+    .await;
+
+    match result {
+        Ok(()) => ComponentHealth::ok(format!("{} is writable", storage_path.display())),
+        Err(e) => {
+            ComponentHealth::failing(format!("{} is not writable: {}", storage_path.display(), e))
+        }
+    }
+}
+
+fn check_disk(config: &HealthConfig, storage_path: &Path) -> ComponentHealth {
+    let path = match CString::new(storage_path.as_os_str().as_bytes()) {
+        Ok(path) => path,
+        Err(e) => return ComponentHealth::failing(e.to_string()),
+    };
+
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is a valid C string and `stat` is a writable statvfs
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return ComponentHealth::failing(format!(
+            "statvfs failed: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+
+    let free = stat.f_bavail as u64 * stat.f_frsize as u64;
+    let detail = format!(
+        "{} MiB free, {} MiB required",
+        free / 1024 / 1024,
+        config.min_free_disk_mb
+    );
+
+    if free < config.min_free_disk_bytes() {
+        ComponentHealth::failing(detail)
+    } else {
+        ComponentHealth::ok(detail)
+    }
+}
@@ -2,38 +2,106 @@ mod routes;
 use dotenv::dotenv;
 use rocket::tokio;
 
-use crate::utils::catchers::{internal_error, not_found, unauthorized};
+use crate::utils::catchers::{
+    forbidden, internal_error, not_found, too_many_requests, unauthorized,
+};
+mod auth;
+mod cli;
 mod clone;
+mod commands;
+mod config;
 mod db;
+mod health;
+mod metrics;
 mod middlewares;
 mod models;
+mod notify;
+mod provider;
 mod schema;
+mod telemetry;
 mod utils;
 use futures::FutureExt;
+use middlewares::request_id::with_request_spans;
+use rocket::fairing::AdHoc;
 use rocket_cors::{AllowedOrigins, CorsOptions};
-use std::{panic::AssertUnwindSafe, time::Duration};
+use std::{panic::AssertUnwindSafe, sync::Arc, time::Duration};
 
 #[macro_use]
 extern crate rocket;
 
-#[get("/health")]
-fn health() -> &'static str {
-    "OK"
+const LOG_PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const STALE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Flushes pending spans before exiting without launching Rocket.
+fn shutdown_tracer(provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>) {
+    if let Some(provider) = provider {
+        let _ = provider.shutdown();
+    }
 }
 
 #[launch]
 async fn rocket() -> _ {
     dotenv().ok();
-    let cors_urls = dotenv::var("CORS_URL").unwrap_or_default();
-    let cors_origins: Vec<&str> = cors_urls
-        .split(',')
-        .map(str::trim)
-        .filter(|s| !s.is_empty())
-        .collect();
 
-    println!("Allowed CORS origins: {:?}", cors_origins);
+    let args = match cli::Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if args.help {
+        println!("{}", cli::USAGE);
+        std::process::exit(0);
+    }
+
+    // checked before logging is set up, so the problems end up plainly on stderr
+    let config = match config::Config::load(args.config_path.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid configuration:\n{}", e);
+            std::process::exit(1);
+        }
+    };
+    if args.print_config {
+        print!("{}", config.to_masked_toml());
+        std::process::exit(0);
+    }
+
+    let tracer_provider = telemetry::init(&config.telemetry, args.command.is_some());
+
+    let pool = db::init_pool(&config.database.url);
+
+    if let Some(command) = args.command {
+        let code = commands::run(command, &config, &pool);
+        shutdown_tracer(tracer_provider);
+        std::process::exit(code);
+    }
+
+    let migrated = pool
+        .get()
+        .map_err(|e| e.into())
+        .and_then(|mut connection| db::migrations::run_pending_migrations(&mut connection));
+    match migrated {
+        Ok(versions) if versions.is_empty() => tracing::info!("database schema is up to date"),
+        Ok(versions) => tracing::info!(versions = ?versions, "applied database migrations"),
+        Err(e) => {
+            tracing::error!(error = %e, "database migration failed");
+            shutdown_tracer(tracer_provider);
+            std::process::exit(1);
+        }
+    }
+    if args.migrate_only {
+        shutdown_tracer(tracer_provider);
+        std::process::exit(0);
+    }
+
+    tracing::info!(origins = ?config.server.cors_origins, "allowed CORS origins");
+    if config.keys.master_key.is_none() {
+        tracing::warn!("keys.master_key is not set, repository keys are stored unencrypted");
+    }
 
-    let cors_allowed_origins = AllowedOrigins::some_exact(&cors_origins);
+    let cors_allowed_origins = AllowedOrigins::some_exact(&config.server.cors_origins);
     let cors = CorsOptions {
         allowed_origins: cors_allowed_origins,
         allow_credentials: true,
@@ -42,16 +110,45 @@ async fn rocket() -> _ {
     .to_cors()
     .expect("Failed to create CORS fairing");
 
-    let database_url = dotenv::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let pool = db::init_pool(&database_url);
+    let setup_state = middlewares::setup::init_setup_state(&pool, &config.setup);
+    let oidc_provider = config
+        .oidc
+        .enabled()
+        .then(|| auth::oidc::OidcProvider::new(config.oidc.clone()));
+    let ldap_authenticator = config
+        .ldap
+        .enabled()
+        .then(|| auth::ldap::LdapAuthenticator::new(config.ldap.clone()));
+    let mailer = config
+        .smtp
+        .enabled()
+        .then(|| notify::smtp::Mailer::new(&config.smtp).expect("Failed to create SMTP transport"));
+    let notifier = Arc::new(notify::Notifier::new(
+        mailer,
+        config.server.allow_internal_targets,
+    ));
+    let providers = Arc::new(provider::ProviderClient::new(
+        config.server.allow_internal_targets,
+    ));
+    let metrics = Arc::new(metrics::Metrics::new(config.metrics.token.clone()));
+    let health_config = config.health.clone();
+    let heartbeat = Arc::new(health::WorkerHeartbeat::new());
 
     rocket::tokio::spawn({
         let pool = pool.clone();
+        let notifier = notifier.clone();
+        let metrics = metrics.clone();
+        let heartbeat = heartbeat.clone();
+        let config = config.clone();
         async move {
             loop {
-                let result = AssertUnwindSafe(clone::worker::clone_worker_run(&pool))
-                    .catch_unwind()
-                    .await;
+                heartbeat.beat();
+                metrics.record_worker_iteration();
+                let result = AssertUnwindSafe(clone::worker::clone_worker_run(
+                    &pool, &notifier, &metrics, &heartbeat, &config,
+                ))
+                .catch_unwind()
+                .await;
 
                 match result {
                     Ok(Ok(())) => {
@@ -60,34 +157,111 @@ async fn rocket() -> _ {
 
                     // clone_worker_run returned Err(e)
                     Ok(Err(e)) => {
-                        eprintln!("clone_worker_run error: {:?}", e);
+                        metrics.record_worker_error();
+                        tracing::error!(error = %e, "clone worker run failed");
                     }
 
                     // it panicked
                     Err(panic_payload) => {
-                        eprintln!("clone_worker_run panicked: {:?}", panic_payload);
+                        metrics.record_worker_panic();
+                        tracing::error!(panic = ?panic_payload, "clone worker run panicked");
                     }
                 }
 
                 // always sleep and loop again
-                tokio::time::sleep(Duration::from_secs(5)).await;
+                tokio::time::sleep(config.worker.interval()).await;
             }
         }
     });
 
+    rocket::tokio::spawn({
+        let pool = pool.clone();
+        async move {
+            loop {
+                match clone::retention::prune_repository_logs(&pool).await {
+                    Ok(summary)
+                        if summary.expired
+                            + summary.excess
+                            + summary.webhook_deliveries
+                            + summary.notification_deliveries
+                            > 0 =>
+                    {
+                        tracing::info!(
+                            expired_logs = summary.expired,
+                            excess_logs = summary.excess,
+                            webhook_deliveries = summary.webhook_deliveries,
+                            notification_deliveries = summary.notification_deliveries,
+                            "pruned repository logs and deliveries"
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        tracing::error!(error = %e, "pruning repository logs failed");
+                    }
+                }
+
+                tokio::time::sleep(LOG_PRUNE_INTERVAL).await;
+            }
+        }
+    });
+
+    rocket::tokio::spawn({
+        let pool = pool.clone();
+        let notifier = notifier.clone();
+        async move {
+            loop {
+                if let Err(e) = notify::check_stale_repositories(&pool, &notifier).await {
+                    tracing::error!(error = %e, "checking for stale repositories failed");
+                }
+
+                tokio::time::sleep(STALE_CHECK_INTERVAL).await;
+            }
+        }
+    });
+
+    let port = config.server.port;
+
     rocket::build()
+        .attach(middlewares::request_id::RequestIdFairing)
         .attach(cors)
+        .attach(middlewares::setup::SetupFairing)
         .manage(pool)
+        .manage(config)
+        .manage(setup_state)
+        .manage(oidc_provider)
+        .manage(ldap_authenticator)
+        .manage(notifier)
+        .manage(providers)
+        .manage(metrics)
+        .manage(heartbeat)
+        .manage(health_config)
+        .attach(AdHoc::on_shutdown("Flush traces", |_| {
+            Box::pin(async move {
+                if let Some(provider) = tracer_provider {
+                    // the batch exporter blocks while flushing
+                    let _ = tokio::task::spawn_blocking(move || provider.shutdown()).await;
+                }
+            })
+        }))
         .configure(
-            rocket::Config::figment().merge((
-                "port",
-                dotenv::var("SERVER_PORT")
-                    .expect("SERVER_PORT must be set")
-                    .parse::<u16>()
-                    .expect("SERVER_PORT must be a valid u16"),
-            )),
+            rocket::Config::figment()
+                .merge(("port", port))
+                // Rocket logs through tracing, colors would end up in the log output
+                .merge(("cli_colors", false)),
+        )
+        .mount("/api/", with_request_spans(routes::routes()))
+        .mount(
+            "/",
+            with_request_spans(routes![routes::metrics::get_metrics]),
+        )
+        .register(
+            "/",
+            catchers![
+                not_found,
+                internal_error,
+                unauthorized,
+                forbidden,
+                too_many_requests
+            ],
         )
-        .mount("/api/", routes![health])
-        .mount("/api/", routes::routes())
-        .register("/", catchers![not_found, internal_error, unauthorized])
 }
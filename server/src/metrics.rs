@@ -0,0 +1,266 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicU64;
+use std::time::Duration;
+
+use chrono::Utc;
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::Bool;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::encoding::text::{encode_eof, encode_registry};
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{Histogram, exponential_buckets};
+use prometheus_client::registry::Registry;
+use rocket::tokio;
+use uuid::Uuid;
+
+use crate::clone::worker::DUE_SQL;
+use crate::db::DbConnection;
+use crate::routes::repository::OVERDUE_SQL;
+use crate::schema::repository;
+
+type MetricsResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Values of the `outcome` label of `gitmirrors_sync_total`.
+pub const OUTCOME_SUCCEEDED: &str = "succeeded";
+pub const OUTCOME_FAILED: &str = "failed";
+pub const OUTCOME_PANICKED: &str = "panicked";
+
+/// Values of the `phase` label of `gitmirrors_sync_duration_seconds`.
+pub const PHASE_CLONE: &str = "clone";
+pub const PHASE_PUSH: &str = "push";
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct OutcomeLabels {
+    outcome: &'static str,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct PhaseLabels {
+    phase: &'static str,
+}
+
+/// Only the id, names are private to their owners and `/metrics` may be public.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RepositoryLabels {
+    repository_id: String,
+}
+
+/// Process-wide metrics updated by the clone worker. Values read from the database and
+/// the disk are collected on each scrape instead.
+pub struct Metrics {
+    registry: Registry,
+    syncs: Family<OutcomeLabels, Counter>,
+    sync_duration: Family<PhaseLabels, Histogram>,
+    worker_iterations: Counter,
+    worker_errors: Counter,
+    worker_panics: Counter,
+    /// Bearer token required to scrape, when set.
+    pub token: Option<String>,
+}
+
+impl Metrics {
+    pub fn new(token: Option<String>) -> Self {
+        let mut registry = Registry::default();
+
+        let syncs = Family::<OutcomeLabels, Counter>::default();
+        registry.register(
+            "gitmirrors_sync",
+            "Finished clone jobs by outcome",
+            syncs.clone(),
+        );
+
+        // from half a second up to about 17 minutes
+        let sync_duration = Family::<PhaseLabels, Histogram>::new_with_constructor(
+            (|| Histogram::new(exponential_buckets(0.5, 2.0, 12))) as fn() -> Histogram,
+        );
+        registry.register(
+            "gitmirrors_sync_duration_seconds",
+            "Duration of the clone and push phases of clone jobs",
+            sync_duration.clone(),
+        );
+
+        let worker_iterations = Counter::default();
+        registry.register(
+            "gitmirrors_worker_iterations",
+            "Runs of the clone worker loop",
+            worker_iterations.clone(),
+        );
+        let worker_errors = Counter::default();
+        registry.register(
+            "gitmirrors_worker_errors",
+            "Clone worker loop runs that returned an error",
+            worker_errors.clone(),
+        );
+        let worker_panics = Counter::default();
+        registry.register(
+            "gitmirrors_worker_panics",
+            "Clone worker loop runs that panicked",
+            worker_panics.clone(),
+        );
+
+        Metrics {
+            registry,
+            syncs,
+            sync_duration,
+            worker_iterations,
+            worker_errors,
+            worker_panics,
+            token,
+        }
+    }
+
+    pub fn record_sync(&self, outcome: &'static str) {
+        self.syncs.get_or_create(&OutcomeLabels { outcome }).inc();
+    }
+
+    pub fn observe_phase(&self, phase: &'static str, duration: Duration) {
+        self.sync_duration
+            .get_or_create(&PhaseLabels { phase })
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn record_worker_iteration(&self) {
+        self.worker_iterations.inc();
+    }
+
+    pub fn record_worker_error(&self) {
+        self.worker_errors.inc();
+    }
+
+    pub fn record_worker_panic(&self) {
+        self.worker_panics.inc();
+    }
+
+    /// Renders all metrics in the OpenMetrics text format.
+    pub async fn render(
+        &self,
+        pool: &DbConnection,
+        repositories_path: &Path,
+    ) -> MetricsResult<String> {
+        let collected = collect(pool, repositories_path.to_path_buf()).await?;
+
+        let mut output = String::new();
+        encode_registry(&mut output, &self.registry)?;
+        encode_registry(&mut output, &collected)?;
+        encode_eof(&mut output)?;
+        Ok(output)
+    }
+}
+
+/// Builds a registry of point-in-time gauges from the database, the pool and the disk.
+async fn collect(pool: &DbConnection, repositories_path: PathBuf) -> MetricsResult<Registry> {
+    let pool = pool.clone();
+
+    tokio::task::spawn_blocking(move || -> MetricsResult<Registry> {
+        let mut registry = Registry::default();
+
+        let state = pool.state();
+        let pool_connections = Gauge::<i64>::default();
+        pool_connections.set(state.connections.into());
+        registry.register(
+            "gitmirrors_db_pool_connections",
+            "Open database connections",
+            pool_connections,
+        );
+        let pool_idle = Gauge::<i64>::default();
+        pool_idle.set(state.idle_connections.into());
+        registry.register(
+            "gitmirrors_db_pool_idle_connections",
+            "Idle database connections",
+            pool_idle,
+        );
+        let pool_max = Gauge::<i64>::default();
+        pool_max.set(pool.max_size().into());
+        registry.register(
+            "gitmirrors_db_pool_max_connections",
+            "Maximum database connections",
+            pool_max,
+        );
+
+        let conn = &mut pool.get()?;
+
+        let due = repository::table
+            .filter(repository::is_enabled.eq(true))
+            .filter(sql::<Bool>(DUE_SQL))
+            .count()
+            .get_result::<i64>(conn)?;
+        let due_gauge = Gauge::<i64>::default();
+        due_gauge.set(due);
+        registry.register(
+            "gitmirrors_repositories_due",
+            "Enabled repositories waiting for the clone worker",
+            due_gauge,
+        );
+
+        let overdue = repository::table
+            .filter(sql::<Bool>(OVERDUE_SQL))
+            .count()
+            .get_result::<i64>(conn)?;
+        let overdue_gauge = Gauge::<i64>::default();
+        overdue_gauge.set(overdue);
+        registry.register(
+            "gitmirrors_repositories_overdue",
+            "Enabled repositories that missed at least one whole cloning period",
+            overdue_gauge,
+        );
+
+        let repositories = repository::table
+            .select((repository::id, repository::last_clone_at))
+            .load::<(Uuid, Option<chrono::DateTime<Utc>>)>(conn)?;
+
+        let now = Utc::now();
+        let since_success = Family::<RepositoryLabels, Gauge<f64, AtomicU64>>::default();
+        let mirror_size = Family::<RepositoryLabels, Gauge<i64>>::default();
+        for (id, last_clone_at) in repositories {
+            let labels = RepositoryLabels {
+                repository_id: id.to_string(),
+            };
+
+            // repositories that never synced have no value
+            if let Some(last_clone_at) = last_clone_at {
+                let age = (now - last_clone_at).num_milliseconds() as f64 / 1000.0;
+                since_success.get_or_create(&labels).set(age);
+            }
+
+            let mirror = repositories_path.join(format!("{}.git", id));
+            if mirror.is_dir() {
+                mirror_size
+                    .get_or_create(&labels)
+                    .set(directory_size(&mirror) as i64);
+            }
+        }
+        registry.register(
+            "gitmirrors_repository_seconds_since_last_success",
+            "Seconds since the repository last synced successfully",
+            since_success,
+        );
+        registry.register(
+            "gitmirrors_repository_mirror_size_bytes",
+            "Size of the repository's mirror in clone storage",
+            mirror_size,
+        );
+
+        Ok(registry)
+    })
+    .await?
+}
+
+/// Total size of the files below `path`. Unreadable entries are skipped.
+pub fn directory_size(path: &Path) -> u64 {
+    let entries = match std::fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(t) if t.is_dir() => directory_size(&entry.path()),
+            Ok(t) if t.is_file() => entry.metadata().map(|m| m.len()).unwrap_or(0),
+            _ => 0,
+        })
+        .sum()
+}
@@ -1,30 +1,160 @@
+use std::marker::PhantomData;
+
 use crate::db::DbConnection;
-use crate::models::UserModel;
-use crate::schema::user::dsl::{session_token, user};
+use crate::models::{ApiTokenModel, SessionModel, UserModel};
+use crate::schema::{api_token, session, user};
+use crate::utils::crypto::hash_token;
+use crate::utils::session::SESSION_COOKIE_NAME;
+use crate::utils::settings::{SETTING_REQUIRE_TOTP, get_bool_setting};
+use chrono::{Duration, Utc};
 use diesel::prelude::*;
 use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome, Request};
+use uuid::Uuid;
+
+pub const API_TOKEN_PREFIX: &str = "gmp_";
+
+/// Permission an API token can be granted. Browser sessions implicitly hold all of them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ApiTokenScope {
+    Read,
+    RepositoriesWrite,
+    SyncTrigger,
+}
+
+impl ApiTokenScope {
+    pub const ALL: [ApiTokenScope; 3] = [
+        ApiTokenScope::Read,
+        ApiTokenScope::RepositoriesWrite,
+        ApiTokenScope::SyncTrigger,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiTokenScope::Read => "read",
+            ApiTokenScope::RepositoriesWrite => "repositories:write",
+            ApiTokenScope::SyncTrigger => "sync:trigger",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.as_str() == value)
+    }
+}
+
+/// Scope a route requires, selected through the type parameter of [`AuthGuard`].
+pub trait RequiredScope {
+    /// `None` means the route is only reachable with a browser session.
+    const SCOPE: Option<ApiTokenScope>;
+}
+
+pub mod scope {
+    use super::{ApiTokenScope, RequiredScope};
 
-pub struct AuthGuard(pub UserModel);
+    pub struct SessionOnly;
+    pub struct Read;
+    pub struct RepositoriesWrite;
+    pub struct SyncTrigger;
+
+    impl RequiredScope for SessionOnly {
+        const SCOPE: Option<ApiTokenScope> = None;
+    }
+
+    impl RequiredScope for Read {
+        const SCOPE: Option<ApiTokenScope> = Some(ApiTokenScope::Read);
+    }
+
+    impl RequiredScope for RepositoriesWrite {
+        const SCOPE: Option<ApiTokenScope> = Some(ApiTokenScope::RepositoriesWrite);
+    }
+
+    impl RequiredScope for SyncTrigger {
+        const SCOPE: Option<ApiTokenScope> = Some(ApiTokenScope::SyncTrigger);
+    }
+}
+
+/// How the request was authenticated, carrying the id of the session or API token.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AuthSource {
+    Session(Uuid),
+    ApiToken(Uuid),
+}
+
+/// Authenticated user. Routes choose which API token scope they accept with `S`;
+/// plain `AuthGuard` only accepts browser sessions.
+pub struct AuthGuard<S: RequiredScope = scope::SessionOnly>(
+    pub UserModel,
+    pub AuthSource,
+    PhantomData<S>,
+);
+
+impl<S: RequiredScope> AuthGuard<S> {
+    pub fn session_id(&self) -> Option<Uuid> {
+        match self.1 {
+            AuthSource::Session(id) => Some(id),
+            AuthSource::ApiToken(_) => None,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum AuthGuardError {
     MissingSessionToken,
     Unauthorized,
+    InsufficientScope,
+    TotpEnrollmentRequired,
     GenericError,
 }
 
+/// Explains a guard failure to the 401/403 catchers, which only see the status otherwise.
+pub struct AuthErrorMessage(pub &'static str);
+
+/// Paths a user without TOTP can still reach while an admin enforces two-factor authentication.
+const TOTP_ENROLLMENT_PATHS: [&str; 3] = ["/api/user/me", "/api/user/logout", "/api/user/totp/"];
+
+fn check_totp_enrollment(
+    req: &Request<'_>,
+    connection: &mut PgConnection,
+    dbusr: &UserModel,
+) -> Result<(), (Status, AuthGuardError)> {
+    if dbusr.totp_enabled {
+        return Ok(());
+    }
+
+    let path = req.uri().path().as_str();
+    if TOTP_ENROLLMENT_PATHS
+        .iter()
+        .any(|allowed| path == *allowed || (allowed.ends_with('/') && path.starts_with(allowed)))
+    {
+        return Ok(());
+    }
+
+    match get_bool_setting(connection, SETTING_REQUIRE_TOTP) {
+        Ok(false) => Ok(()),
+        Ok(true) => {
+            req.local_cache(|| AuthErrorMessage("Two-factor authentication enrollment required"));
+            Err((Status::Forbidden, AuthGuardError::TotpEnrollmentRequired))
+        }
+        Err(_) => Err((Status::InternalServerError, AuthGuardError::GenericError)),
+    }
+}
+
 #[rocket::async_trait]
-impl<'r> FromRequest<'r> for AuthGuard {
+impl<'r, S: RequiredScope> FromRequest<'r> for AuthGuard<S> {
     type Error = AuthGuardError;
 
     async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        let session_token_from_cookie = match req.cookies().get("gitmirrors_session_token") {
-            Some(cookie) => cookie.value(),
-            None => {
-                return Outcome::Error((Status::Unauthorized, AuthGuardError::MissingSessionToken));
-            }
-        };
+        let bearer_token = req
+            .headers()
+            .get_one("Authorization")
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .map(str::trim);
+
+        let session_token_from_cookie = req.cookies().get(SESSION_COOKIE_NAME).map(|c| c.value());
+
+        if bearer_token.is_none() && session_token_from_cookie.is_none() {
+            return Outcome::Error((Status::Unauthorized, AuthGuardError::MissingSessionToken));
+        }
 
         let pool = match req.rocket().state::<DbConnection>() {
             Some(pool) => pool,
@@ -40,13 +170,105 @@ impl<'r> FromRequest<'r> for AuthGuard {
             }
         };
 
-        let get_user_result = user
-            .filter(session_token.eq(session_token_from_cookie))
-            .select(UserModel::as_select())
-            .first::<UserModel>(&mut connection);
+        let now = Utc::now();
+
+        if let Some(token) = bearer_token {
+            let required_scope = match S::SCOPE {
+                Some(scope) => scope,
+                None => {
+                    req.local_cache(|| {
+                        AuthErrorMessage("This endpoint requires a browser session")
+                    });
+                    return Outcome::Error((Status::Forbidden, AuthGuardError::InsufficientScope));
+                }
+            };
+
+            let get_token_result = api_token::table
+                .inner_join(user::table)
+                .filter(api_token::token_hash.eq(hash_token(token)))
+                .filter(user::disabled_at.is_null())
+                .filter(
+                    api_token::expires_at
+                        .is_null()
+                        .or(api_token::expires_at.gt(now)),
+                )
+                .select((ApiTokenModel::as_select(), UserModel::as_select()))
+                .first::<(ApiTokenModel, UserModel)>(&mut connection);
+
+            return match get_token_result {
+                Ok((dbtoken, dbusr)) => {
+                    if !dbtoken
+                        .scopes
+                        .split_whitespace()
+                        .any(|s| s == required_scope.as_str())
+                    {
+                        req.local_cache(|| {
+                            AuthErrorMessage("API token is missing the required scope")
+                        });
+                        return Outcome::Error((
+                            Status::Forbidden,
+                            AuthGuardError::InsufficientScope,
+                        ));
+                    }
+
+                    if dbtoken
+                        .last_used_at
+                        .is_none_or(|t| now - t > Duration::seconds(60))
+                    {
+                        let _ =
+                            diesel::update(api_token::table.filter(api_token::id.eq(dbtoken.id)))
+                                .set(api_token::last_used_at.eq(now))
+                                .execute(&mut connection);
+                    }
+
+                    if let Err(e) = check_totp_enrollment(req, &mut connection, &dbusr) {
+                        return Outcome::Error(e);
+                    }
+
+                    Outcome::Success(AuthGuard(
+                        dbusr,
+                        AuthSource::ApiToken(dbtoken.id),
+                        PhantomData,
+                    ))
+                }
+                Err(diesel::result::Error::NotFound) => {
+                    Outcome::Error((Status::Unauthorized, AuthGuardError::Unauthorized))
+                }
+                Err(_) => {
+                    Outcome::Error((Status::InternalServerError, AuthGuardError::GenericError))
+                }
+            };
+        }
 
-        match get_user_result {
-            Ok(dbusr) => Outcome::Success(AuthGuard(dbusr)),
+        let session_token_from_cookie = session_token_from_cookie.unwrap_or_default();
+
+        let get_session_result = session::table
+            .inner_join(user::table)
+            .filter(session::token_hash.eq(hash_token(session_token_from_cookie)))
+            .filter(user::disabled_at.is_null())
+            .filter(session::expires_at.gt(now))
+            .select((SessionModel::as_select(), UserModel::as_select()))
+            .first::<(SessionModel, UserModel)>(&mut connection);
+
+        match get_session_result {
+            Ok((dbsession, dbusr)) => {
+                // avoid a write on every request, last seen only needs minute precision
+                if now - dbsession.last_seen_at > Duration::seconds(60) {
+                    let _ = diesel::update(session::table.filter(session::id.eq(dbsession.id)))
+                        .set(session::last_seen_at.eq(now))
+                        .execute(&mut connection);
+                }
+
+                if let Err(e) = check_totp_enrollment(req, &mut connection, &dbusr) {
+                    return Outcome::Error(e);
+                }
+
+                Outcome::Success(AuthGuard(
+                    dbusr,
+                    AuthSource::Session(dbsession.id),
+                    PhantomData,
+                ))
+            }
             Err(diesel::result::Error::NotFound) => {
                 Outcome::Error((Status::Unauthorized, AuthGuardError::Unauthorized))
             }
@@ -54,3 +276,26 @@ impl<'r> FromRequest<'r> for AuthGuard {
         }
     }
 }
+
+/// Authenticated administrator. Only accepts browser sessions.
+pub struct AdminGuard(pub UserModel);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminGuard {
+    type Error = AuthGuardError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let auth = match req.guard::<AuthGuard>().await {
+            Outcome::Success(auth) => auth,
+            Outcome::Error(e) => return Outcome::Error(e),
+            Outcome::Forward(s) => return Outcome::Forward(s),
+        };
+
+        if !auth.0.is_admin {
+            req.local_cache(|| AuthErrorMessage("Administrator access required"));
+            return Outcome::Error((Status::Forbidden, AuthGuardError::InsufficientScope));
+        }
+
+        Outcome::Success(AdminGuard(auth.0))
+    }
+}
@@ -0,0 +1,25 @@
+use rocket::request::{FromRequest, Outcome, Request};
+
+/// Connection metadata recorded alongside sessions.
+#[derive(Clone)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientInfo {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let user_agent = req
+            .headers()
+            .get_one("User-Agent")
+            .map(|ua| ua.chars().take(512).collect());
+
+        Outcome::Success(ClientInfo {
+            user_agent,
+            ip_address: req.client_ip().map(|ip| ip.to_string()),
+        })
+    }
+}
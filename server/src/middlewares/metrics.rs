@@ -0,0 +1,37 @@
+use std::sync::Arc;
+
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+
+use crate::metrics::Metrics;
+use crate::utils::crypto::constant_time_eq;
+
+/// Admits a scrape when `metrics.token` is unset or sent as `Authorization: Bearer`.
+pub struct MetricsAuth;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for MetricsAuth {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let expected = match req.rocket().state::<Arc<Metrics>>() {
+            Some(metrics) => metrics.token.as_deref(),
+            None => return Outcome::Error((Status::InternalServerError, ())),
+        };
+
+        let Some(expected) = expected else {
+            return Outcome::Success(MetricsAuth);
+        };
+
+        let provided = req
+            .headers()
+            .get_one("Authorization")
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .map(str::trim);
+
+        match provided {
+            Some(provided) if constant_time_eq(provided, expected) => Outcome::Success(MetricsAuth),
+            _ => Outcome::Error((Status::Unauthorized, ())),
+        }
+    }
+}
@@ -1 +1,7 @@
 pub mod auth;
+pub mod client;
+pub mod metrics;
+pub mod passphrase;
+pub mod request_id;
+pub mod setup;
+pub mod webhook;
@@ -0,0 +1,19 @@
+use rocket::request::{FromRequest, Outcome, Request};
+
+/// `X-Export-Passphrase`, kept out of the URL so it does not end up in access logs.
+pub struct ExportPassphrase(pub Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ExportPassphrase {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let passphrase = req
+            .headers()
+            .get_one("X-Export-Passphrase")
+            .filter(|p| !p.is_empty())
+            .map(String::from);
+
+        Outcome::Success(ExportPassphrase(passphrase))
+    }
+}
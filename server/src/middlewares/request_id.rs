@@ -0,0 +1,114 @@
+use std::time::Instant;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::request::{FromRequest, Outcome};
+use rocket::route::{self, Handler};
+use rocket::{Data, Request, Response, Route};
+use tracing::Instrument;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Longest request id accepted from a client or proxy.
+const MAX_REQUEST_ID_LENGTH: usize = 64;
+
+/// Id of the current request, taken from a trusted `X-Request-Id` or generated.
+pub struct RequestId(pub String);
+
+struct RequestStart(Instant);
+
+fn is_valid_request_id(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= MAX_REQUEST_ID_LENGTH
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+}
+
+fn request_id<'r>(req: &'r Request<'_>) -> &'r RequestId {
+    req.local_cache(|| {
+        let id = req
+            .headers()
+            .get_one(REQUEST_ID_HEADER)
+            .filter(|v| is_valid_request_id(v))
+            .map(str::to_string)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        RequestId(id)
+    })
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r RequestId {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(request_id(req))
+    }
+}
+
+/// Assigns every request an id, echoes it in the response and logs the finished request.
+pub struct RequestIdFairing;
+
+#[rocket::async_trait]
+impl Fairing for RequestIdFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request id",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        request_id(req);
+        req.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let id = &request_id(req).0;
+        let started = req.local_cache(|| RequestStart(Instant::now())).0;
+        let status = res.status().code;
+
+        // the path without its query, which can carry tokens
+        tracing::info!(
+            request_id = %id,
+            method = %req.method(),
+            path = %req.uri().path(),
+            route = req.route().and_then(|r| r.name.as_deref()),
+            status,
+            duration_ms = started.elapsed().as_secs_f64() * 1000.0,
+            "request finished"
+        );
+
+        res.set_header(Header::new(REQUEST_ID_HEADER, id.clone()));
+    }
+}
+
+/// Runs a route's handler inside a span carrying the request id, so everything logged
+/// while handling the request can be correlated.
+#[derive(Clone)]
+struct TracedHandler(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for TracedHandler {
+    async fn handle<'r>(&self, req: &'r Request<'_>, data: Data<'r>) -> route::Outcome<'r> {
+        let span = tracing::info_span!(
+            "request",
+            request_id = %request_id(req).0,
+            method = %req.method(),
+            route = req.route().and_then(|r| r.name.as_deref()),
+        );
+
+        self.0.handle(req, data).instrument(span).await
+    }
+}
+
+pub fn with_request_spans(routes: Vec<Route>) -> Vec<Route> {
+    routes
+        .into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(TracedHandler(route.handler));
+            route
+        })
+        .collect()
+}
@@ -0,0 +1,141 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use diesel::prelude::*;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Method;
+use rocket::http::uri::Origin;
+use rocket::{Data, Request, tokio};
+
+use crate::config::SetupConfig;
+use crate::db::DbConnection;
+use crate::schema::user;
+use crate::utils::crypto::generate_random_string;
+
+pub const SETUP_ADMIN_USERNAME: &str = "admin";
+
+/// How long a "setup required" answer is trusted before the database is asked again, so
+/// replicas that did not serve the setup request notice it was completed.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct SetupState {
+    required: AtomicBool,
+    checked_at: Mutex<Instant>,
+    pub bootstrap_token: String,
+}
+
+impl SetupState {
+    pub fn is_required(&self) -> bool {
+        self.required.load(Ordering::SeqCst)
+    }
+
+    pub fn mark_completed(&self) {
+        self.required.store(false, Ordering::SeqCst);
+    }
+
+    /// Whether setup is still required and was last read from the database longer than
+    /// `REFRESH_INTERVAL` ago. Claims the refresh, so only one request per interval does it.
+    fn claim_refresh(&self) -> bool {
+        if !self.is_required() {
+            return false;
+        }
+        let mut checked_at = self.checked_at.lock().expect("Setup state lock poisoned");
+        if checked_at.elapsed() < REFRESH_INTERVAL {
+            return false;
+        }
+        *checked_at = Instant::now();
+        true
+    }
+}
+
+/// Setup is required while the bootstrap admin account still has no password.
+fn read_setup_required(connection: &mut PgConnection) -> QueryResult<bool> {
+    user::table
+        .filter(user::username.eq(SETUP_ADMIN_USERNAME))
+        .filter(user::password_hash.is_null())
+        .count()
+        .get_result::<i64>(connection)
+        .map(|count| count > 0)
+}
+
+pub fn init_setup_state(pool: &DbConnection, config: &SetupConfig) -> SetupState {
+    let connection = &mut pool.get().expect("Failed to get DB Connection");
+
+    let required = read_setup_required(connection).expect("Failed to check setup state");
+
+    let bootstrap_token = match &config.token {
+        Some(token) => token.trim().to_string(),
+        None => {
+            let token = generate_random_string(32);
+            if required {
+                tracing::warn!(setup_token = %token, "setup required, use this one-time setup token");
+            }
+            token
+        }
+    };
+
+    if required {
+        tracing::warn!("complete setup with POST /api/setup before using this instance");
+    }
+
+    SetupState {
+        required: AtomicBool::new(required),
+        checked_at: Mutex::new(Instant::now()),
+        bootstrap_token,
+    }
+}
+
+/// Reroutes every request except setup, health checks and metrics to the "setup required" route
+/// until the instance has been set up. Setup may be completed through another replica, so
+/// while it is required the database is asked again every `REFRESH_INTERVAL`.
+pub struct SetupFairing;
+
+#[rocket::async_trait]
+impl Fairing for SetupFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "First-run setup",
+            kind: Kind::Request,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        let Some(state) = req.rocket().state::<SetupState>() else {
+            return;
+        };
+        if state.claim_refresh()
+            && let Some(pool) = req.rocket().state::<DbConnection>()
+        {
+            let pool = pool.clone();
+            let refreshed = tokio::task::spawn_blocking(move || {
+                let connection = &mut pool.get().map_err(|e| e.to_string())?;
+                read_setup_required(connection).map_err(|e| e.to_string())
+            })
+            .await;
+            match refreshed {
+                Ok(Ok(false)) => state.mark_completed(),
+                Ok(Ok(true)) => {}
+                Ok(Err(e)) => tracing::warn!(error = %e, "failed to refresh the setup state"),
+                Err(e) => tracing::warn!(error = %e, "failed to refresh the setup state"),
+            }
+        }
+        let required = state.is_required();
+
+        if !required || req.method() == Method::Options {
+            return;
+        }
+
+        let path = req.uri().path().as_str();
+        if path == "/api/setup"
+            || path == "/api/health"
+            || path.starts_with("/api/health/")
+            || path == "/metrics"
+        {
+            return;
+        }
+
+        req.set_method(Method::Get);
+        req.set_uri(Origin::parse("/api/setup/required").unwrap());
+    }
+}
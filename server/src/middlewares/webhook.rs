@@ -0,0 +1,74 @@
+use rocket::request::{FromRequest, Outcome, Request};
+
+/// Git host that sent a webhook, told apart by its event header.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WebhookProvider {
+    GitHub,
+    GitLab,
+    Gitea,
+}
+
+impl WebhookProvider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookProvider::GitHub => "github",
+            WebhookProvider::GitLab => "gitlab",
+            WebhookProvider::Gitea => "gitea",
+        }
+    }
+}
+
+/// Provider specific headers of an incoming webhook delivery.
+pub struct WebhookHeaders {
+    pub provider: Option<WebhookProvider>,
+    pub event: Option<String>,
+    pub delivery_id: Option<String>,
+    /// `X-Hub-Signature-256`, `X-Gitea-Signature` or `X-Gitlab-Token` depending on the provider.
+    pub signature: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for WebhookHeaders {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let header = |name: &str| {
+            req.headers()
+                .get_one(name)
+                .map(|v| v.chars().take(128).collect::<String>())
+        };
+
+        // Gitea also sends GitHub's headers for compatibility, so it is checked first
+        let headers = if let Some(event) = header("X-Gitea-Event") {
+            WebhookHeaders {
+                provider: Some(WebhookProvider::Gitea),
+                event: Some(event),
+                delivery_id: header("X-Gitea-Delivery"),
+                signature: header("X-Gitea-Signature"),
+            }
+        } else if let Some(event) = header("X-Gitlab-Event") {
+            WebhookHeaders {
+                provider: Some(WebhookProvider::GitLab),
+                event: Some(event),
+                delivery_id: header("X-Gitlab-Event-UUID"),
+                signature: header("X-Gitlab-Token"),
+            }
+        } else if let Some(event) = header("X-GitHub-Event") {
+            WebhookHeaders {
+                provider: Some(WebhookProvider::GitHub),
+                event: Some(event),
+                delivery_id: header("X-GitHub-Delivery"),
+                signature: header("X-Hub-Signature-256"),
+            }
+        } else {
+            WebhookHeaders {
+                provider: None,
+                event: None,
+                delivery_id: None,
+                signature: None,
+            }
+        };
+
+        Outcome::Success(headers)
+    }
+}
@@ -10,9 +10,15 @@ pub struct UserModel {
     pub id: Uuid,
     pub username: String,
     pub password_hash: Option<String>,
-    pub session_token: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub is_admin: bool,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    pub totp_last_used_step: Option<i64>,
+    pub oidc_subject: Option<String>,
+    pub ldap_dn: Option<String>,
+    pub disabled_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Serialize)]
@@ -20,8 +26,11 @@ pub struct UserModel {
 pub struct PublicUser {
     pub id: Uuid,
     pub username: String,
+    pub is_admin: bool,
+    pub totp_enabled: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub disabled_at: Option<DateTime<Utc>>,
 }
 
 impl From<UserModel> for PublicUser {
@@ -29,8 +38,11 @@ impl From<UserModel> for PublicUser {
         PublicUser {
             id: user.id,
             username: user.username,
+            is_admin: user.is_admin,
+            totp_enabled: user.totp_enabled,
             created_at: user.created_at,
             updated_at: user.updated_at,
+            disabled_at: user.disabled_at,
         }
     }
 }
@@ -44,7 +56,7 @@ impl From<UserModel> for PublicUser {
 #[serde(rename_all = "camelCase")]
 pub struct RepositoryModel {
     pub id: Uuid,
-    pub user_id: Uuid,
+    pub user_id: Option<Uuid>,
     pub name: String,
     pub url: Option<String>,
     pub is_enabled: bool,
@@ -56,12 +68,79 @@ pub struct RepositoryModel {
     pub last_clone_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub sync_requested_at: Option<DateTime<Utc>>,
+    pub team_id: Option<Uuid>,
+    pub tags: Vec<String>,
+    pub last_sync_status: Option<String>,
+    pub source_host: Option<String>,
+    pub log_retention_days: Option<i32>,
+    pub log_max_rows: Option<i32>,
+    pub health_state: String,
+    pub ref_filters: Vec<String>,
+}
+
+/// Repository as returned by the API, telling whether keys are set without returning them.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicRepository {
+    pub id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub name: String,
+    pub url: Option<String>,
+    pub is_enabled: bool,
+    pub git_source: String,
+    pub has_source_key: bool,
+    pub git_target: String,
+    pub has_target_key: bool,
+    pub git_clone_period_seconds: i32,
+    pub last_clone_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub sync_requested_at: Option<DateTime<Utc>>,
+    pub team_id: Option<Uuid>,
+    pub tags: Vec<String>,
+    pub last_sync_status: Option<String>,
+    pub source_host: Option<String>,
+    pub log_retention_days: Option<i32>,
+    pub log_max_rows: Option<i32>,
+    pub health_state: String,
+    pub ref_filters: Vec<String>,
+}
+
+impl From<RepositoryModel> for PublicRepository {
+    fn from(repo: RepositoryModel) -> Self {
+        PublicRepository {
+            id: repo.id,
+            user_id: repo.user_id,
+            name: repo.name,
+            url: repo.url,
+            is_enabled: repo.is_enabled,
+            git_source: repo.git_source,
+            has_source_key: repo.git_source_secret_key.is_some(),
+            git_target: repo.git_target,
+            has_target_key: repo.git_target_secret_key.is_some(),
+            git_clone_period_seconds: repo.git_clone_period_seconds,
+            last_clone_at: repo.last_clone_at,
+            created_at: repo.created_at,
+            updated_at: repo.updated_at,
+            sync_requested_at: repo.sync_requested_at,
+            team_id: repo.team_id,
+            tags: repo.tags,
+            last_sync_status: repo.last_sync_status,
+            source_host: repo.source_host,
+            log_retention_days: repo.log_retention_days,
+            log_max_rows: repo.log_max_rows,
+            health_state: repo.health_state,
+            ref_filters: repo.ref_filters,
+        }
+    }
 }
 
 #[derive(Insertable)]
 #[diesel(table_name = crate::schema::repository)]
 pub struct InsertableRepositoryModel<'a> {
-    pub user_id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub team_id: Option<Uuid>,
     pub name: &'a str,
     pub url: Option<&'a str>,
     pub is_enabled: bool,
@@ -70,6 +149,27 @@ pub struct InsertableRepositoryModel<'a> {
     pub git_target: &'a str,
     pub git_target_secret_key: Option<&'a str>,
     pub git_clone_period_seconds: i32,
+    pub tags: Vec<String>,
+    pub log_retention_days: Option<i32>,
+    pub log_max_rows: Option<i32>,
+    pub ref_filters: Vec<String>,
+}
+
+/// Fields left `None` are not touched, `Some(None)` clears a nullable column.
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = crate::schema::repository)]
+pub struct RepositoryChangeset<'a> {
+    pub name: Option<&'a str>,
+    pub url: Option<Option<&'a str>>,
+    pub is_enabled: Option<bool>,
+    pub git_source: Option<&'a str>,
+    pub git_source_secret_key: Option<Option<&'a str>>,
+    pub git_target: Option<&'a str>,
+    pub git_target_secret_key: Option<&'a str>,
+    pub git_clone_period_seconds: Option<i32>,
+    pub tags: Option<Vec<String>>,
+    pub ref_filters: Option<Vec<String>>,
+    pub updated_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Queryable, Identifiable, Insertable, Serialize, Deserialize)]
@@ -87,6 +187,7 @@ pub struct RepositoryLogModel {
     pub message: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub job_id: Option<Uuid>,
 }
 
 #[derive(Insertable)]
@@ -95,4 +196,385 @@ pub struct InsertableRepositoryLogModel<'a> {
     pub repository_id: Uuid,
     pub type_: &'a str,
     pub message: &'a str,
+    pub job_id: Option<Uuid>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, PartialEq, Debug)]
+#[diesel(table_name = crate::schema::session)]
+#[diesel(belongs_to(UserModel, foreign_key = user_id))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct SessionModel {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::session)]
+pub struct InsertableSessionModel<'a> {
+    pub user_id: Uuid,
+    pub token_hash: &'a str,
+    pub user_agent: Option<&'a str>,
+    pub ip_address: Option<&'a str>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicSession {
+    pub id: Uuid,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub is_current: bool,
+}
+
+impl PublicSession {
+    pub fn from_model(session: SessionModel, current_session_id: Option<Uuid>) -> Self {
+        PublicSession {
+            is_current: current_session_id == Some(session.id),
+            id: session.id,
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+            last_seen_at: session.last_seen_at,
+            expires_at: session.expires_at,
+            created_at: session.created_at,
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, PartialEq, Debug)]
+#[diesel(table_name = crate::schema::api_token)]
+#[diesel(belongs_to(UserModel, foreign_key = user_id))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ApiTokenModel {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub token_prefix: String,
+    pub token_hash: String,
+    pub scopes: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::api_token)]
+pub struct InsertableApiTokenModel<'a> {
+    pub user_id: Uuid,
+    pub name: &'a str,
+    pub token_prefix: &'a str,
+    pub token_hash: &'a str,
+    pub scopes: &'a str,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicApiToken {
+    pub id: Uuid,
+    pub name: String,
+    pub token_prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<ApiTokenModel> for PublicApiToken {
+    fn from(token: ApiTokenModel) -> Self {
+        PublicApiToken {
+            id: token.id,
+            name: token.name,
+            token_prefix: token.token_prefix,
+            scopes: token.scopes.split_whitespace().map(String::from).collect(),
+            expires_at: token.expires_at,
+            last_used_at: token.last_used_at,
+            created_at: token.created_at,
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Identifiable, PartialEq, Debug, Serialize)]
+#[diesel(table_name = crate::schema::login_attempt)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+pub struct LoginAttemptModel {
+    pub id: Uuid,
+    pub username: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub succeeded: bool,
+    pub failure_reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::login_attempt)]
+pub struct InsertableLoginAttemptModel<'a> {
+    pub username: &'a str,
+    pub ip_address: Option<&'a str>,
+    pub user_agent: Option<&'a str>,
+    pub succeeded: bool,
+    pub failure_reason: Option<&'a str>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, PartialEq, Debug, Serialize)]
+#[diesel(table_name = crate::schema::audit_event)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEventModel {
+    pub id: Uuid,
+    pub actor_user_id: Option<Uuid>,
+    pub actor_username: Option<String>,
+    pub action: String,
+    pub target_type: Option<String>,
+    pub target_id: Option<Uuid>,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::audit_event)]
+pub struct InsertableAuditEventModel<'a> {
+    pub actor_user_id: Option<Uuid>,
+    pub actor_username: Option<&'a str>,
+    pub action: &'a str,
+    pub target_type: Option<&'a str>,
+    pub target_id: Option<Uuid>,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub ip_address: Option<&'a str>,
+    pub user_agent: Option<&'a str>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, PartialEq, Debug)]
+#[diesel(table_name = crate::schema::login_challenge)]
+#[diesel(belongs_to(UserModel, foreign_key = user_id))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct LoginChallengeModel {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub attempts: i32,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::login_challenge)]
+pub struct InsertableLoginChallengeModel<'a> {
+    pub user_id: Uuid,
+    pub token_hash: &'a str,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::recovery_code)]
+pub struct InsertableRecoveryCodeModel<'a> {
+    pub user_id: Uuid,
+    pub code_hash: &'a str,
+}
+
+#[derive(Queryable, Selectable, Identifiable, PartialEq, Debug)]
+#[diesel(table_name = crate::schema::oidc_login_state)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct OidcLoginStateModel {
+    pub id: Uuid,
+    pub state: String,
+    pub nonce: String,
+    pub code_verifier: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::oidc_login_state)]
+pub struct InsertableOidcLoginStateModel<'a> {
+    pub state: &'a str,
+    pub nonce: &'a str,
+    pub code_verifier: &'a str,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, PartialEq, Debug, Serialize)]
+#[diesel(table_name = crate::schema::repository_transfer)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+pub struct RepositoryTransferModel {
+    pub id: Uuid,
+    pub repository_id: Uuid,
+    pub requested_by: Option<Uuid>,
+    pub from_user_id: Option<Uuid>,
+    pub from_team_id: Option<Uuid>,
+    pub to_user_id: Option<Uuid>,
+    pub to_team_id: Option<Uuid>,
+    pub status: String,
+    pub resolved_by: Option<Uuid>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::repository_transfer)]
+pub struct InsertableRepositoryTransferModel {
+    pub repository_id: Uuid,
+    pub requested_by: Option<Uuid>,
+    pub from_user_id: Option<Uuid>,
+    pub from_team_id: Option<Uuid>,
+    pub to_user_id: Option<Uuid>,
+    pub to_team_id: Option<Uuid>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, PartialEq, Debug, Serialize)]
+#[diesel(table_name = crate::schema::notification_channel)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationChannelModel {
+    pub id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub team_id: Option<Uuid>,
+    pub name: String,
+    pub kind: String,
+    pub target: String,
+    #[serde(skip_serializing)]
+    pub secret: Option<String>,
+    pub is_enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::notification_channel)]
+pub struct InsertableNotificationChannelModel<'a> {
+    pub user_id: Option<Uuid>,
+    pub team_id: Option<Uuid>,
+    pub name: &'a str,
+    pub kind: &'a str,
+    pub target: &'a str,
+    pub secret: Option<&'a str>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, PartialEq, Debug, Serialize)]
+#[diesel(table_name = crate::schema::notification_delivery)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationDeliveryModel {
+    pub id: Uuid,
+    pub channel_id: Uuid,
+    pub repository_id: Option<Uuid>,
+    pub event: String,
+    pub succeeded: bool,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::notification_delivery)]
+pub struct InsertableNotificationDeliveryModel<'a> {
+    pub channel_id: Uuid,
+    pub repository_id: Option<Uuid>,
+    pub event: &'a str,
+    pub succeeded: bool,
+    pub error: Option<&'a str>,
+}
+
+/// Not serialized, the secret is only returned once when it is generated.
+#[derive(Queryable, Selectable, PartialEq, Debug)]
+#[diesel(table_name = crate::schema::repository_webhook)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct RepositoryWebhookModel {
+    pub repository_id: Uuid,
+    pub secret: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, PartialEq, Debug, Serialize)]
+#[diesel(table_name = crate::schema::webhook_delivery)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookDeliveryModel {
+    pub id: Uuid,
+    pub repository_id: Uuid,
+    pub provider: Option<String>,
+    pub event: Option<String>,
+    pub delivery_id: Option<String>,
+    pub outcome: String,
+    pub message: String,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::webhook_delivery)]
+pub struct InsertableWebhookDeliveryModel<'a> {
+    pub repository_id: Uuid,
+    pub provider: Option<&'a str>,
+    pub event: Option<&'a str>,
+    pub delivery_id: Option<&'a str>,
+    pub outcome: &'a str,
+    pub message: &'a str,
+    pub ip_address: Option<&'a str>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, PartialEq, Debug, Serialize)]
+#[diesel(table_name = crate::schema::team)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+pub struct TeamModel {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::team)]
+pub struct InsertableTeamModel<'a> {
+    pub name: &'a str,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, PartialEq, Debug)]
+#[diesel(table_name = crate::schema::team_member)]
+#[diesel(belongs_to(TeamModel, foreign_key = team_id))]
+#[diesel(belongs_to(UserModel, foreign_key = user_id))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct TeamMemberModel {
+    pub id: Uuid,
+    pub team_id: Uuid,
+    pub user_id: Uuid,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::team_member)]
+pub struct InsertableTeamMemberModel<'a> {
+    pub team_id: Uuid,
+    pub user_id: Uuid,
+    pub role: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicTeamMember {
+    pub user_id: Uuid,
+    pub username: String,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
 }
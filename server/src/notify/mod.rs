@@ -0,0 +1,592 @@
+pub mod smtp;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Integer};
+use hmac::{Hmac, Mac};
+use rocket::tokio;
+use serde::Serialize;
+use sha2::Sha256;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::db::DbConnection;
+use crate::models::{
+    InsertableNotificationDeliveryModel, NotificationChannelModel, NotificationDeliveryModel,
+    RepositoryModel,
+};
+use crate::notify::smtp::Mailer;
+use crate::schema::{notification_channel, notification_delivery, repository};
+use crate::utils::http::OutboundClient;
+use crate::utils::settings::{
+    DEFAULT_STALE_AFTER_SECONDS, SETTING_STALE_AFTER_SECONDS, get_int_setting,
+};
+
+type NotifyResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+pub const CHANNEL_WEBHOOK: &str = "webhook";
+pub const CHANNEL_SLACK: &str = "slack";
+pub const CHANNEL_EMAIL: &str = "email";
+
+/// Values of `repository.health_state`.
+pub const HEALTH_HEALTHY: &str = "healthy";
+pub const HEALTH_FAILING: &str = "failing";
+pub const HEALTH_STALE: &str = "stale";
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+const USER_AGENT: &str = concat!("gitmirrors/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NotificationEvent {
+    Failing,
+    Recovered,
+    Stale,
+    /// Sent on request to check a channel's configuration.
+    Test,
+}
+
+impl NotificationEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationEvent::Failing => "repository.failing",
+            NotificationEvent::Recovered => "repository.recovered",
+            NotificationEvent::Stale => "repository.stale",
+            NotificationEvent::Test => "test",
+        }
+    }
+
+    fn message(&self, repo: Option<&RepositoryModel>) -> String {
+        let name = repo.map(|r| r.name.as_str()).unwrap_or_default();
+        match self {
+            NotificationEvent::Failing => format!("Mirror {} is failing to sync", name),
+            NotificationEvent::Recovered => format!("Mirror {} synced successfully again", name),
+            NotificationEvent::Stale => format!("Mirror {} has not synced for too long", name),
+            NotificationEvent::Test => "Test notification from GitMirrors".to_string(),
+        }
+    }
+}
+
+/// Repository fields sent to channels, without credentials.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NotificationRepository<'a> {
+    id: Uuid,
+    name: &'a str,
+    url: Option<&'a str>,
+    health_state: &'a str,
+    last_clone_at: Option<chrono::DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WebhookPayload<'a> {
+    event: &'a str,
+    message: &'a str,
+    repository: Option<NotificationRepository<'a>>,
+    occurred_at: chrono::DateTime<Utc>,
+}
+
+/// Slack and Mattermost incoming webhooks both accept a plain `text` field.
+#[derive(Serialize)]
+struct SlackPayload<'a> {
+    text: &'a str,
+}
+
+pub struct Notifier {
+    http: OutboundClient,
+    mailer: Option<Mailer>,
+}
+
+impl Notifier {
+    pub fn new(mailer: Option<Mailer>, allow_internal_targets: bool) -> Self {
+        Notifier {
+            http: OutboundClient::new(HTTP_TIMEOUT, USER_AGENT, allow_internal_targets),
+            mailer,
+        }
+    }
+
+    pub fn email_enabled(&self) -> bool {
+        self.mailer.is_some()
+    }
+
+    /// Whether a webhook or chat channel may send to the URL.
+    pub fn accepts_url(&self, url: &str) -> bool {
+        self.http.accepts_url(url)
+    }
+
+    /// Delivers one event to one channel.
+    pub async fn send(
+        &self,
+        channel: &NotificationChannelModel,
+        event: NotificationEvent,
+        repo: Option<&RepositoryModel>,
+    ) -> NotifyResult<()> {
+        let message = event.message(repo);
+
+        match channel.kind.as_str() {
+            CHANNEL_WEBHOOK => {
+                let payload = WebhookPayload {
+                    event: event.as_str(),
+                    message: &message,
+                    repository: repo.map(|r| NotificationRepository {
+                        id: r.id,
+                        name: &r.name,
+                        url: r.url.as_deref(),
+                        health_state: &r.health_state,
+                        last_clone_at: r.last_clone_at,
+                    }),
+                    occurred_at: Utc::now(),
+                };
+                let body = serde_json::to_vec(&payload)?;
+
+                let mut request = self
+                    .http
+                    .post(&channel.target)?
+                    .header("Content-Type", "application/json")
+                    .header("X-GitMirrors-Event", event.as_str());
+                // same scheme as GitHub, so receivers can reuse their verification code
+                if let Some(secret) = channel.secret.as_deref() {
+                    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+                        .expect("HMAC accepts keys of any length");
+                    mac.update(&body);
+                    request = request.header(
+                        "X-GitMirrors-Signature-256",
+                        format!("sha256={}", hex::encode(mac.finalize().into_bytes())),
+                    );
+                }
+
+                request.body(body).send().await?.error_for_status()?;
+                Ok(())
+            }
+            CHANNEL_SLACK => {
+                self.http
+                    .post(&channel.target)?
+                    .json(&SlackPayload { text: &message })
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                Ok(())
+            }
+            CHANNEL_EMAIL => {
+                let mailer = self.mailer.as_ref().ok_or("SMTP is not configured")?;
+                let subject = format!("[GitMirrors] {}", message);
+                let mut body = format!("{}.\n", message);
+                if let Some(repo) = repo {
+                    body.push_str(&format!(
+                        "\nRepository: {}\nLast successful sync: {}\n",
+                        repo.id,
+                        repo.last_clone_at
+                            .map(|t| t.to_rfc3339())
+                            .unwrap_or_else(|| "never".to_string())
+                    ));
+                }
+                mailer.send(&channel.target, &subject, &body).await
+            }
+            other => Err(format!("Unknown channel kind {}", other).into()),
+        }
+    }
+}
+
+async fn with_connection<T, F>(pool: &DbConnection, f: F) -> NotifyResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut PgConnection) -> QueryResult<T> + Send + 'static,
+{
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || -> NotifyResult<T> {
+        let mut conn = pool.get()?;
+        Ok(f(&mut conn)?)
+    })
+    .await?
+}
+
+/// Moves the repository to the state matching its latest clone result. Only an actual
+/// change notifies, so a mirror failing on every retry is reported once.
+pub async fn record_sync_result(
+    pool: &DbConnection,
+    notifier: &Arc<Notifier>,
+    repo_id: Uuid,
+    succeeded: bool,
+) -> NotifyResult<()> {
+    let (state, event) = match succeeded {
+        true => (HEALTH_HEALTHY, NotificationEvent::Recovered),
+        false => (HEALTH_FAILING, NotificationEvent::Failing),
+    };
+
+    let changed = with_connection(pool, move |conn| {
+        diesel::update(
+            repository::table
+                .filter(repository::id.eq(repo_id))
+                .filter(repository::health_state.ne(state)),
+        )
+        .set(repository::health_state.eq(state))
+        .get_result::<RepositoryModel>(conn)
+        .optional()
+    })
+    .await?;
+
+    if let Some(repo) = changed {
+        notify_owner(pool, notifier, repo, event);
+    }
+    Ok(())
+}
+
+/// Marks healthy repositories stale once their last sync is more than the configured
+/// grace period past due.
+pub async fn check_stale_repositories(
+    pool: &DbConnection,
+    notifier: &Arc<Notifier>,
+) -> NotifyResult<()> {
+    let stale = with_connection(pool, |conn| {
+        let grace = get_int_setting(
+            conn,
+            SETTING_STALE_AFTER_SECONDS,
+            DEFAULT_STALE_AFTER_SECONDS,
+        )?;
+        if grace <= 0 {
+            return Ok(Vec::new());
+        }
+
+        diesel::update(
+            repository::table
+                .filter(repository::is_enabled.eq(true))
+                .filter(repository::health_state.eq(HEALTH_HEALTHY))
+                .filter(
+                    sql::<Bool>(
+                        "(coalesce(last_clone_at, created_at)
+                          + (git_clone_period_seconds + ",
+                    )
+                    .bind::<Integer, _>(grace)
+                    .sql(") * interval '1 second' < now())"),
+                ),
+        )
+        .set(repository::health_state.eq(HEALTH_STALE))
+        .get_results::<RepositoryModel>(conn)
+    })
+    .await?;
+
+    for repo in stale {
+        notify_owner(pool, notifier, repo, NotificationEvent::Stale);
+    }
+    Ok(())
+}
+
+/// Sends the event to the enabled channels of the repository's owner in the background
+/// and records each delivery.
+fn notify_owner(
+    pool: &DbConnection,
+    notifier: &Arc<Notifier>,
+    repo: RepositoryModel,
+    event: NotificationEvent,
+) {
+    let pool = pool.clone();
+    let notifier = notifier.clone();
+
+    // created here, so it stays a child of the clone job span
+    let span = tracing::info_span!("notify", repository_id = %repo.id, event = event.as_str());
+
+    tokio::spawn(
+        async move {
+        let (user_id, team_id) = (repo.user_id, repo.team_id);
+        let channels = with_connection(&pool, move |conn| {
+            notification_channel::table
+                .filter(notification_channel::is_enabled.eq(true))
+                .filter(
+                    notification_channel::user_id
+                        .eq(user_id)
+                        .or(notification_channel::team_id.eq(team_id)),
+                )
+                .select(NotificationChannelModel::as_select())
+                .load::<NotificationChannelModel>(conn)
+        })
+        .await;
+
+        let channels = match channels {
+            Ok(channels) => channels,
+            Err(e) => {
+                tracing::error!(error = %e, "failed to load notification channels");
+                return;
+            }
+        };
+
+        for channel in channels {
+            let result = notifier.send(&channel, event, Some(&repo)).await;
+            if let Err(e) = record_delivery(&pool, channel.id, Some(repo.id), event, &result).await
+            {
+                tracing::error!(channel_id = %channel.id, error = %e, "failed to record notification delivery");
+            }
+        }
+    }
+        .instrument(span),
+    );
+}
+
+pub async fn record_delivery(
+    pool: &DbConnection,
+    channel_id: Uuid,
+    repository_id: Option<Uuid>,
+    event: NotificationEvent,
+    result: &NotifyResult<()>,
+) -> NotifyResult<NotificationDeliveryModel> {
+    let error = result.as_ref().err().map(|e| e.to_string());
+
+    with_connection(pool, move |conn| {
+        diesel::insert_into(notification_delivery::table)
+            .values(&InsertableNotificationDeliveryModel {
+                channel_id,
+                repository_id,
+                event: event.as_str(),
+                succeeded: error.is_none(),
+                error: error.as_deref(),
+            })
+            .returning(NotificationDeliveryModel::as_returning())
+            .get_result::<NotificationDeliveryModel>(conn)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// A request as the stand-in receiver saw it, with lowercase header names.
+    struct Received {
+        request_line: String,
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    }
+
+    impl Received {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        }
+    }
+
+    /// Accepts one HTTP request, answers it with `status` and returns it.
+    async fn receiver_stand_in(listener: TcpListener, status: u16) -> Received {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (read, mut write) = stream.into_split();
+        let mut reader = BufReader::new(read);
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).await.unwrap();
+        let mut headers = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+            match line.trim_end().split_once(':') {
+                Some((name, value)) => {
+                    headers.push((name.to_ascii_lowercase(), value.trim().to_string()))
+                }
+                None => break,
+            }
+        }
+        let length = headers
+            .iter()
+            .find(|(name, _)| name == "content-length")
+            .map_or(0, |(_, value)| value.parse().unwrap());
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).await.unwrap();
+
+        write
+            .write_all(
+                format!(
+                    "HTTP/1.1 {status} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap();
+
+        Received {
+            request_line: request_line.trim_end().to_string(),
+            headers,
+            body,
+        }
+    }
+
+    fn channel(kind: &str, target: String, secret: Option<&str>) -> NotificationChannelModel {
+        NotificationChannelModel {
+            id: Uuid::new_v4(),
+            user_id: Some(Uuid::new_v4()),
+            team_id: None,
+            name: "ops".to_string(),
+            kind: kind.to_string(),
+            target,
+            secret: secret.map(str::to_string),
+            is_enabled: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn repository() -> RepositoryModel {
+        RepositoryModel {
+            id: Uuid::new_v4(),
+            user_id: Some(Uuid::new_v4()),
+            name: "backend".to_string(),
+            url: Some("https://github.com/example/backend".to_string()),
+            is_enabled: true,
+            git_source: "https://github.com/example/backend.git".to_string(),
+            git_source_secret_key: Some("source-secret".to_string()),
+            git_target: "https://git.example.com/mirrors/backend.git".to_string(),
+            git_target_secret_key: Some("target-secret".to_string()),
+            git_clone_period_seconds: 300,
+            last_clone_at: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            sync_requested_at: None,
+            team_id: None,
+            tags: vec![],
+            last_sync_status: Some("failed".to_string()),
+            source_host: Some("github.com".to_string()),
+            log_retention_days: None,
+            log_max_rows: None,
+            health_state: HEALTH_FAILING.to_string(),
+            ref_filters: vec![],
+        }
+    }
+
+    async fn listen() -> (TcpListener, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hooks/mirrors", listener.local_addr().unwrap());
+        (listener, url)
+    }
+
+    #[rocket::async_test]
+    async fn posts_signed_webhook_payloads() {
+        let (listener, url) = listen().await;
+        let receiver = tokio::spawn(receiver_stand_in(listener, 200));
+        let repo = repository();
+
+        Notifier::new(None, true)
+            .send(
+                &channel(CHANNEL_WEBHOOK, url, Some("hook-secret")),
+                NotificationEvent::Failing,
+                Some(&repo),
+            )
+            .await
+            .unwrap();
+
+        let received = receiver.await.unwrap();
+        assert_eq!(received.request_line, "POST /hooks/mirrors HTTP/1.1");
+        assert_eq!(received.header("content-type"), Some("application/json"));
+        assert_eq!(
+            received.header("x-gitmirrors-event"),
+            Some("repository.failing")
+        );
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"hook-secret").unwrap();
+        mac.update(&received.body);
+        let signature = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+        assert_eq!(
+            received.header("x-gitmirrors-signature-256"),
+            Some(signature.as_str())
+        );
+
+        let payload: Value = serde_json::from_slice(&received.body).unwrap();
+        assert_eq!(payload["event"], "repository.failing");
+        assert_eq!(payload["message"], "Mirror backend is failing to sync");
+        assert_eq!(payload["repository"]["id"], repo.id.to_string());
+        assert_eq!(payload["repository"]["name"], "backend");
+        assert_eq!(payload["repository"]["healthState"], "failing");
+        assert!(payload["occurredAt"].is_string());
+        // credentials never leave the server
+        let body = String::from_utf8(received.body).unwrap();
+        assert!(!body.contains("secret") && !body.contains("gitSource"));
+    }
+
+    #[rocket::async_test]
+    async fn posts_unsigned_webhooks_without_a_secret() {
+        let (listener, url) = listen().await;
+        let receiver = tokio::spawn(receiver_stand_in(listener, 200));
+
+        Notifier::new(None, true)
+            .send(
+                &channel(CHANNEL_WEBHOOK, url, None),
+                NotificationEvent::Test,
+                None,
+            )
+            .await
+            .unwrap();
+
+        let received = receiver.await.unwrap();
+        assert_eq!(received.header("x-gitmirrors-signature-256"), None);
+        let payload: Value = serde_json::from_slice(&received.body).unwrap();
+        assert_eq!(payload["event"], "test");
+        assert!(payload["repository"].is_null());
+    }
+
+    #[rocket::async_test]
+    async fn posts_slack_and_mattermost_text() {
+        let (listener, url) = listen().await;
+        let receiver = tokio::spawn(receiver_stand_in(listener, 200));
+
+        Notifier::new(None, true)
+            .send(
+                &channel(CHANNEL_SLACK, url, None),
+                NotificationEvent::Recovered,
+                Some(&repository()),
+            )
+            .await
+            .unwrap();
+
+        let received = receiver.await.unwrap();
+        assert_eq!(received.request_line, "POST /hooks/mirrors HTTP/1.1");
+        assert_eq!(received.header("content-type"), Some("application/json"));
+        let payload: Value = serde_json::from_slice(&received.body).unwrap();
+        assert_eq!(
+            payload,
+            serde_json::json!({"text": "Mirror backend synced successfully again"})
+        );
+    }
+
+    #[rocket::async_test]
+    async fn reports_receiver_errors() {
+        let (listener, url) = listen().await;
+        tokio::spawn(receiver_stand_in(listener, 500));
+
+        let error = Notifier::new(None, true)
+            .send(
+                &channel(CHANNEL_SLACK, url, None),
+                NotificationEvent::Test,
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("500"));
+    }
+
+    #[rocket::async_test]
+    async fn refuses_internal_targets_unless_allowed() {
+        let (listener, url) = listen().await;
+        let notifier = Notifier::new(None, false);
+        assert!(!notifier.accepts_url(&url));
+
+        for kind in [CHANNEL_WEBHOOK, CHANNEL_SLACK] {
+            let error = notifier
+                .send(
+                    &channel(kind, url.clone(), None),
+                    NotificationEvent::Test,
+                    None,
+                )
+                .await
+                .unwrap_err();
+            assert!(error.to_string().contains("internal address"));
+        }
+
+        // nothing reached the receiver
+        let accepted = tokio::time::timeout(Duration::from_millis(100), listener.accept()).await;
+        assert!(accepted.is_err());
+    }
+}
@@ -0,0 +1,163 @@
+use std::time::Duration;
+
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+
+use crate::config::SmtpConfig;
+
+type SmtpResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Plain connection upgraded with `STARTTLS`, usually port 587.
+    StartTls,
+    /// TLS from the first byte, usually port 465.
+    #[serde(rename = "tls")]
+    Implicit,
+    /// Unencrypted, only meant for local relays and test servers.
+    None,
+}
+
+/// Sends plain text emails through the configured relay.
+pub struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: String,
+}
+
+impl Mailer {
+    pub fn new(config: &SmtpConfig) -> SmtpResult<Self> {
+        let builder = match config.tls {
+            SmtpTls::StartTls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?
+            }
+            SmtpTls::Implicit => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+        };
+        let mut builder = builder.port(config.port()).timeout(Some(SMTP_TIMEOUT));
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(Mailer {
+            transport: builder.build(),
+            from: config.from.clone(),
+        })
+    }
+
+    pub async fn send(&self, to: &str, subject: &str, body: &str) -> SmtpResult<()> {
+        let message = Message::builder()
+            .from(self.from.parse()?)
+            .to(to.parse()?)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body.to_string())?;
+
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::tokio;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Accepts one SMTP session and returns the message it received.
+    async fn smtp_stand_in(listener: TcpListener) -> String {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (read, mut write) = stream.into_split();
+        let mut lines = BufReader::new(read).lines();
+        let mut data = String::new();
+        let mut in_data = false;
+
+        write.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            if in_data {
+                if line == "." {
+                    in_data = false;
+                    write.write_all(b"250 queued\r\n").await.unwrap();
+                } else {
+                    data.push_str(&line);
+                    data.push('\n');
+                }
+                continue;
+            }
+
+            let reply: &[u8] = match line.split_whitespace().next().unwrap_or_default() {
+                "EHLO" => b"250-localhost\r\n250 AUTH PLAIN\r\n",
+                "AUTH" => b"235 authenticated\r\n",
+                "DATA" => {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                }
+                "QUIT" => {
+                    write.write_all(b"221 bye\r\n").await.unwrap();
+                    break;
+                }
+                _ => b"250 ok\r\n",
+            };
+            write.write_all(reply).await.unwrap();
+        }
+        data
+    }
+
+    #[rocket::async_test]
+    async fn sends_a_plain_text_message() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(smtp_stand_in(listener));
+
+        let mailer = Mailer::new(&SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port: Some(port),
+            tls: SmtpTls::None,
+            username: Some("user".to_string()),
+            password: Some("secret".to_string()),
+            from: "gitmirrors@example.com".to_string(),
+        })
+        .unwrap();
+        mailer
+            .send(
+                "ops@example.com",
+                "[GitMirrors] Sync failed",
+                "Sync failed.\n",
+            )
+            .await
+            .unwrap();
+        drop(mailer);
+
+        let data = server.await.unwrap();
+        assert!(data.contains("From: gitmirrors@example.com"));
+        assert!(data.contains("To: ops@example.com"));
+        assert!(data.contains("Subject: [GitMirrors] Sync failed"));
+        assert!(data.contains("Content-Type: text/plain"));
+        assert!(data.contains("Sync failed."));
+    }
+
+    #[rocket::async_test]
+    async fn rejects_invalid_recipients() {
+        let mailer = Mailer::new(&SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port: Some(1),
+            tls: SmtpTls::None,
+            username: None,
+            password: None,
+            from: "gitmirrors@example.com".to_string(),
+        })
+        .unwrap();
+
+        assert!(
+            mailer
+                .send("not an address", "subject", "body")
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,675 @@
+//! Repository listings of git hosting providers, for importing an organization in bulk.
+//!
+//! The API URL is always passed in, so self-hosted instances and local mock servers work
+//! the same as the hosted services.
+
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, LINK};
+use reqwest::{RequestBuilder, StatusCode};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+use crate::utils::http::OutboundClient;
+
+type ProviderResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(15);
+/// GitHub rejects API requests without a user agent.
+const USER_AGENT: &str = concat!("gitmirrors/", env!("CARGO_PKG_VERSION"));
+/// Upper bound on followed pages, 10,000 repositories for GitHub and GitLab.
+const MAX_PAGES: usize = 100;
+
+pub const VISIBILITY_PUBLIC: &str = "public";
+pub const VISIBILITY_PRIVATE: &str = "private";
+pub const VISIBILITY_INTERNAL: &str = "internal";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Provider {
+    GitHub,
+    GitLab,
+    Gitea,
+}
+
+impl Provider {
+    pub const ALL: [Provider; 3] = [Provider::GitHub, Provider::GitLab, Provider::Gitea];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Provider::ALL.into_iter().find(|p| p.as_str() == value)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Provider::GitHub => "github",
+            Provider::GitLab => "gitlab",
+            Provider::Gitea => "gitea",
+        }
+    }
+
+    /// API of the hosted service. Gitea is only self-hosted.
+    pub fn default_api_url(&self) -> Option<&'static str> {
+        match self {
+            Provider::GitHub => Some("https://api.github.com"),
+            Provider::GitLab => Some("https://gitlab.com"),
+            Provider::Gitea => None,
+        }
+    }
+
+    /// Whether the listing continues past the page `headers` came with. GitHub and Gitea
+    /// link the next page in `Link`, GitLab sends its number in `X-Next-Page`, empty on
+    /// the last page.
+    fn has_next_page(&self, headers: &HeaderMap) -> bool {
+        match self {
+            Provider::GitHub | Provider::Gitea => headers
+                .get_all(LINK)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .any(links_next_page),
+            Provider::GitLab => headers
+                .get("X-Next-Page")
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|page| !page.trim().is_empty()),
+        }
+    }
+
+    /// Largest page each API returns.
+    fn page_size(&self) -> usize {
+        match self {
+            Provider::GitHub | Provider::GitLab => 100,
+            Provider::Gitea => 50,
+        }
+    }
+
+    /// Listing endpoints for an organization or group, then for a user of that name.
+    fn list_urls(&self, api_url: &str, owner: &str) -> [String; 2] {
+        let owner: String = url::form_urlencoded::byte_serialize(owner.as_bytes()).collect();
+        match self {
+            Provider::GitHub => [
+                format!("{}/orgs/{}/repos", api_url, owner),
+                format!("{}/users/{}/repos", api_url, owner),
+            ],
+            Provider::GitLab => [
+                format!("{}/api/v4/groups/{}/projects", api_url, owner),
+                format!("{}/api/v4/users/{}/projects", api_url, owner),
+            ],
+            Provider::Gitea => [
+                format!("{}/api/v1/orgs/{}/repos", api_url, owner),
+                format!("{}/api/v1/users/{}/repos", api_url, owner),
+            ],
+        }
+    }
+
+    fn authenticate(&self, request: RequestBuilder, token: &str) -> RequestBuilder {
+        match self {
+            Provider::GitHub => request.bearer_auth(token),
+            Provider::GitLab => request.header("PRIVATE-TOKEN", token),
+            Provider::Gitea => request.header("Authorization", format!("token {}", token)),
+        }
+    }
+}
+
+/// A repository as listed by a provider.
+#[derive(Clone, Debug)]
+pub struct ProviderRepository {
+    /// Organization, user or full GitLab group path.
+    pub owner: String,
+    pub name: String,
+    pub https_url: String,
+    pub ssh_url: String,
+    pub web_url: String,
+    pub archived: bool,
+    /// `public`, `private` or `internal`.
+    pub visibility: String,
+}
+
+impl ProviderRepository {
+    /// Path of the repository below the imported `owner`, such as `backend/api` for a
+    /// project in the `acme/backend` subgroup of `acme`. Unlike the name it is unique
+    /// across subgroups, and it stays the same when the import runs again.
+    pub fn path_below(&self, owner: &str) -> String {
+        let owner = owner.trim_matches('/');
+        let subgroup = match self.owner.get(..owner.len()) {
+            Some(prefix) if prefix.eq_ignore_ascii_case(owner) => &self.owner[owner.len()..],
+            _ => return format!("{}/{}", self.owner, self.name),
+        };
+        match subgroup.strip_prefix('/') {
+            Some(subgroup) => format!("{}/{}", subgroup, self.name),
+            None if subgroup.is_empty() => self.name.clone(),
+            None => format!("{}/{}", self.owner, self.name),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AccountOwner {
+    login: String,
+}
+
+/// GitHub's repository object, which Gitea mirrors.
+#[derive(Deserialize)]
+struct GitHubRepository {
+    name: String,
+    owner: AccountOwner,
+    clone_url: String,
+    ssh_url: String,
+    html_url: String,
+    #[serde(default)]
+    archived: bool,
+    #[serde(default)]
+    private: bool,
+    /// GitHub only, set to `internal` on enterprise instances.
+    visibility: Option<String>,
+    /// Gitea only.
+    #[serde(default)]
+    internal: bool,
+}
+
+impl From<GitHubRepository> for ProviderRepository {
+    fn from(repo: GitHubRepository) -> Self {
+        let visibility = match repo.visibility {
+            Some(visibility) => visibility,
+            None if repo.internal => VISIBILITY_INTERNAL.to_string(),
+            None if repo.private => VISIBILITY_PRIVATE.to_string(),
+            None => VISIBILITY_PUBLIC.to_string(),
+        };
+
+        ProviderRepository {
+            owner: repo.owner.login,
+            name: repo.name,
+            https_url: repo.clone_url,
+            ssh_url: repo.ssh_url,
+            web_url: repo.html_url,
+            archived: repo.archived,
+            visibility,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GitLabNamespace {
+    full_path: String,
+}
+
+#[derive(Deserialize)]
+struct GitLabProject {
+    path: String,
+    namespace: GitLabNamespace,
+    http_url_to_repo: String,
+    ssh_url_to_repo: String,
+    web_url: String,
+    #[serde(default)]
+    archived: bool,
+    visibility: String,
+}
+
+impl From<GitLabProject> for ProviderRepository {
+    fn from(project: GitLabProject) -> Self {
+        ProviderRepository {
+            owner: project.namespace.full_path,
+            name: project.path,
+            https_url: project.http_url_to_repo,
+            ssh_url: project.ssh_url_to_repo,
+            web_url: project.web_url,
+            archived: project.archived,
+            visibility: project.visibility,
+        }
+    }
+}
+
+pub struct ProviderClient {
+    http: OutboundClient,
+}
+
+impl ProviderClient {
+    pub fn new(allow_internal_targets: bool) -> Self {
+        ProviderClient {
+            http: OutboundClient::new(HTTP_TIMEOUT, USER_AGENT, allow_internal_targets),
+        }
+    }
+
+    /// Whether listings may be requested from the API URL.
+    pub fn accepts_url(&self, api_url: &str) -> bool {
+        self.http.accepts_url(api_url)
+    }
+
+    /// Every repository of `owner`, following pagination. Organizations and groups are
+    /// tried first, then users. GitLab includes the projects of subgroups.
+    pub async fn list_repositories(
+        &self,
+        provider: Provider,
+        api_url: &str,
+        owner: &str,
+        token: Option<&str>,
+    ) -> ProviderResult<Vec<ProviderRepository>> {
+        let api_url = api_url.trim_end_matches('/');
+        let mut repositories = Vec::new();
+
+        for url in provider.list_urls(api_url, owner) {
+            for page in 1..=MAX_PAGES {
+                let listed = match provider {
+                    Provider::GitHub | Provider::Gitea => self
+                        .fetch_page::<GitHubRepository>(provider, &url, token, page)
+                        .await?
+                        .map(|(repos, next)| {
+                            (
+                                repos.into_iter().map(ProviderRepository::from).collect(),
+                                next,
+                            )
+                        }),
+                    Provider::GitLab => self
+                        .fetch_page::<GitLabProject>(provider, &url, token, page)
+                        .await?
+                        .map(|(repos, next)| {
+                            (
+                                repos.into_iter().map(ProviderRepository::from).collect(),
+                                next,
+                            )
+                        }),
+                };
+                let (listed, has_next_page): (Vec<ProviderRepository>, bool) = match listed {
+                    Some(listed) => listed,
+                    // not an organization, try the user endpoint
+                    None if page == 1 => break,
+                    None => return Err(format!("{} disappeared while listing", url).into()),
+                };
+
+                repositories.extend(listed);
+                if !has_next_page {
+                    return Ok(repositories);
+                }
+            }
+
+            if !repositories.is_empty() {
+                return Err(format!("{} lists more than {} pages", url, MAX_PAGES).into());
+            }
+        }
+
+        Err(format!(
+            "{} has no organization, group or user named {}",
+            provider.as_str(),
+            owner
+        )
+        .into())
+    }
+
+    /// One page of a listing and whether another follows, `None` when the endpoint
+    /// responds 404.
+    async fn fetch_page<T: DeserializeOwned>(
+        &self,
+        provider: Provider,
+        url: &str,
+        token: Option<&str>,
+        page: usize,
+    ) -> ProviderResult<Option<(Vec<T>, bool)>> {
+        let page_size = provider.page_size().to_string();
+        let page = page.to_string();
+        let mut query = vec![("page", page.as_str())];
+        match provider {
+            Provider::GitHub => query.push(("per_page", &page_size)),
+            Provider::GitLab => {
+                query.push(("per_page", &page_size));
+                query.push(("include_subgroups", "true"));
+            }
+            Provider::Gitea => query.push(("limit", &page_size)),
+        }
+
+        let mut request = self.http.get(url)?.query(&query);
+        if let Some(token) = token {
+            request = provider.authenticate(request, token);
+        }
+
+        let response = request.send().await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if !status.is_success() => Err(format!(
+                "{} responded with HTTP {} to {}",
+                provider.as_str(),
+                status.as_u16(),
+                url
+            )
+            .into()),
+            _ => {
+                let has_next_page = provider.has_next_page(response.headers());
+                Ok(Some((response.json().await?, has_next_page)))
+            }
+        }
+    }
+}
+
+/// Whether a `Link` header, such as `<https://…?page=2>; rel="next", <…>; rel="last"`,
+/// has a `next` relation.
+fn links_next_page(link: &str) -> bool {
+    link.split(',').any(|entry| {
+        entry.split(';').skip(1).any(|param| {
+            param.trim().strip_prefix("rel=").is_some_and(|rel| {
+                rel.trim_matches('"')
+                    .split_whitespace()
+                    .any(|r| r == "next")
+            })
+        })
+    })
+}
+
+/// Shell-style match of a whole name, `*` is any run of characters and `?` any single one.
+/// Case-insensitive, as providers treat repository names.
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    // position after the last `*` and the name position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_n)) => {
+                    backtrack = Some((star_p, star_n + 1));
+                    p = star_p;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use rocket::tokio;
+    use serde_json::{Value, json};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    type Reply = (u16, Vec<(&'static str, String)>, Value);
+
+    /// Serves `reply` for the path and query of every request, returning the base URL and
+    /// the requests it received.
+    async fn provider_stand_in(
+        reply: impl Fn(&str) -> Reply + Send + Sync + 'static,
+    ) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let received = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let (read, mut write) = stream.into_split();
+                let mut lines = BufReader::new(read).lines();
+                let request_line = lines.next_line().await.unwrap().unwrap_or_default();
+                while let Some(line) = lines.next_line().await.unwrap() {
+                    if line.is_empty() {
+                        break;
+                    }
+                }
+
+                let target = request_line
+                    .split(' ')
+                    .nth(1)
+                    .unwrap_or_default()
+                    .to_string();
+                let (status, headers, body) = reply(&target);
+                received.lock().unwrap().push(target);
+
+                let body = body.to_string();
+                let mut response = format!(
+                    "HTTP/1.1 {} OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+                    status,
+                    body.len()
+                );
+                for (name, value) in headers {
+                    response.push_str(&format!("{}: {}\r\n", name, value));
+                }
+                response.push_str("\r\n");
+                response.push_str(&body);
+                write.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        (base_url, requests)
+    }
+
+    fn github_repos(names: &[&str]) -> Value {
+        names
+            .iter()
+            .map(|name| {
+                json!({
+                    "name": name,
+                    "owner": {"login": "acme"},
+                    "clone_url": format!("https://github.com/acme/{}.git", name),
+                    "ssh_url": format!("git@github.com:acme/{}.git", name),
+                    "html_url": format!("https://github.com/acme/{}", name),
+                    "private": true,
+                })
+            })
+            .collect()
+    }
+
+    fn gitlab_projects(names: &[&str]) -> Value {
+        names
+            .iter()
+            .map(|name| {
+                json!({
+                    "path": name,
+                    "namespace": {"full_path": "acme/platform"},
+                    "http_url_to_repo": format!("https://gitlab.com/acme/platform/{}.git", name),
+                    "ssh_url_to_repo": format!("git@gitlab.com:acme/platform/{}.git", name),
+                    "web_url": format!("https://gitlab.com/acme/platform/{}", name),
+                    "visibility": "internal",
+                })
+            })
+            .collect()
+    }
+
+    fn names(repositories: &[ProviderRepository]) -> Vec<&str> {
+        repositories.iter().map(|r| r.name.as_str()).collect()
+    }
+
+    #[rocket::async_test]
+    async fn follows_github_next_links() {
+        let (base_url, requests) = provider_stand_in(|target| {
+            // short pages that still link a next one must not end the listing
+            if target.contains("page=1&") {
+                let next = "<http://api/orgs/acme/repos?page=2>; rel=\"next\", <http://api/orgs/acme/repos?page=2>; rel=\"last\"";
+                (200, vec![("Link", next.to_string())], github_repos(&["api"]))
+            } else {
+                let prev = "<http://api/orgs/acme/repos?page=1>; rel=\"prev\"";
+                (200, vec![("Link", prev.to_string())], github_repos(&["web"]))
+            }
+        })
+        .await;
+
+        let listed = ProviderClient::new(true)
+            .list_repositories(Provider::GitHub, &base_url, "acme", Some("token"))
+            .await
+            .unwrap();
+
+        assert_eq!(names(&listed), ["api", "web"]);
+        assert_eq!(listed[0].visibility, VISIBILITY_PRIVATE);
+        assert_eq!(listed[0].ssh_url, "git@github.com:acme/api.git");
+        assert_eq!(
+            *requests.lock().unwrap(),
+            [
+                "/orgs/acme/repos?page=1&per_page=100",
+                "/orgs/acme/repos?page=2&per_page=100",
+            ]
+        );
+    }
+
+    #[rocket::async_test]
+    async fn follows_gitlab_next_page_headers() {
+        let (base_url, requests) = provider_stand_in(|target| {
+            let (next, projects) = if target.contains("page=1&") {
+                ("2", gitlab_projects(&["api", "web"]))
+            } else {
+                ("", gitlab_projects(&["docs"]))
+            };
+            (200, vec![("X-Next-Page", next.to_string())], projects)
+        })
+        .await;
+
+        let listed = ProviderClient::new(true)
+            .list_repositories(Provider::GitLab, &base_url, "acme/platform", None)
+            .await
+            .unwrap();
+
+        assert_eq!(names(&listed), ["api", "web", "docs"]);
+        assert_eq!(listed[0].owner, "acme/platform");
+        assert_eq!(listed[0].visibility, VISIBILITY_INTERNAL);
+        assert_eq!(requests.lock().unwrap().len(), 2);
+        assert!(
+            requests.lock().unwrap()[0].starts_with("/api/v4/groups/acme%2Fplatform/projects?")
+        );
+    }
+
+    #[rocket::async_test]
+    async fn falls_back_to_the_user_of_that_name() {
+        let (base_url, requests) = provider_stand_in(|target| {
+            if target.starts_with("/api/v1/orgs/") {
+                (404, Vec::new(), json!({"message": "Not Found"}))
+            } else {
+                (200, Vec::new(), github_repos(&["dotfiles"]))
+            }
+        })
+        .await;
+
+        let listed = ProviderClient::new(true)
+            .list_repositories(Provider::Gitea, &format!("{}/", base_url), "jane", None)
+            .await
+            .unwrap();
+
+        assert_eq!(names(&listed), ["dotfiles"]);
+        assert_eq!(
+            *requests.lock().unwrap(),
+            [
+                "/api/v1/orgs/jane/repos?page=1&limit=50",
+                "/api/v1/users/jane/repos?page=1&limit=50",
+            ]
+        );
+    }
+
+    #[rocket::async_test]
+    async fn reports_unknown_owners_and_errors() {
+        let (base_url, _) =
+            provider_stand_in(|_| (404, Vec::new(), json!({"message": "Not Found"}))).await;
+        let error = ProviderClient::new(true)
+            .list_repositories(Provider::GitHub, &base_url, "nobody", None)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "github has no organization, group or user named nobody"
+        );
+
+        let (base_url, _) =
+            provider_stand_in(|_| (401, Vec::new(), json!({"message": "Unauthorized"}))).await;
+        let error = ProviderClient::new(true)
+            .list_repositories(Provider::GitLab, &base_url, "acme", Some("expired"))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("responded with HTTP 401"));
+    }
+
+    #[rocket::async_test]
+    async fn refuses_internal_api_urls_unless_allowed() {
+        let (base_url, requests) =
+            provider_stand_in(|_| (200, Vec::new(), github_repos(&["api"]))).await;
+        let providers = ProviderClient::new(false);
+        assert!(!providers.accepts_url(&base_url));
+        assert!(!providers.accepts_url("http://169.254.169.254/latest"));
+        assert!(providers.accepts_url("https://gitlab.example.com"));
+
+        let error = providers
+            .list_repositories(Provider::GitHub, &base_url, "acme", None)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("internal address"));
+        assert!(requests.lock().unwrap().is_empty());
+    }
+
+    fn listed(owner: &str, name: &str) -> ProviderRepository {
+        ProviderRepository {
+            owner: owner.to_string(),
+            name: name.to_string(),
+            https_url: String::new(),
+            ssh_url: String::new(),
+            web_url: String::new(),
+            archived: false,
+            visibility: VISIBILITY_PUBLIC.to_string(),
+        }
+    }
+
+    #[test]
+    fn names_repositories_by_their_path_below_the_owner() {
+        assert_eq!(listed("acme", "api").path_below("acme"), "api");
+        assert_eq!(listed("Acme", "api").path_below("acme/"), "api");
+        // same name in two subgroups
+        assert_eq!(
+            listed("acme/billing", "api").path_below("acme"),
+            "billing/api"
+        );
+        assert_eq!(
+            listed("acme/shop/eu", "api").path_below("acme"),
+            "shop/eu/api"
+        );
+        // only whole group names are stripped
+        assert_eq!(
+            listed("acme-labs", "api").path_below("acme"),
+            "acme-labs/api"
+        );
+        assert_eq!(listed("other", "api").path_below("acme"), "other/api");
+    }
+
+    #[test]
+    fn finds_next_links() {
+        assert!(links_next_page(
+            "<https://api.github.com/orgs/acme/repos?page=2>; rel=\"next\""
+        ));
+        assert!(links_next_page(
+            "<https://a/?page=1>; rel=\"prev\", <https://a/?page=3>; rel=\"next\""
+        ));
+        assert!(!links_next_page(
+            "<https://a/?page=1>; rel=\"first\", <https://a/?page=2>; rel=\"prev\""
+        ));
+        assert!(!links_next_page(
+            "<https://a/?page=2&rel=next>; rel=\"last\""
+        ));
+        assert!(!links_next_page(""));
+    }
+
+    #[test]
+    fn matches_whole_names_with_wildcards() {
+        assert!(matches_pattern("service-*", "service-billing"));
+        assert!(matches_pattern("service-*", "service-"));
+        assert!(!matches_pattern("service-*", "legacy-service-billing"));
+        assert!(matches_pattern("*-api", "billing-api"));
+        assert!(matches_pattern("*", ""));
+        assert!(matches_pattern("svc-??", "svc-eu"));
+        assert!(!matches_pattern("svc-??", "svc-eu1"));
+        assert!(matches_pattern("a*b*c", "axxbyybzc"));
+        assert!(!matches_pattern("a*b*c", "axxbyy"));
+        assert!(matches_pattern("web", "web"));
+        assert!(!matches_pattern("web", "webapp"));
+    }
+
+    #[test]
+    fn matches_case_insensitively() {
+        assert!(matches_pattern("Service-*", "SERVICE-Billing"));
+        assert!(matches_pattern("ÉTÉ-?", "été-1"));
+    }
+}
@@ -0,0 +1,335 @@
+use diesel::prelude::*;
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::db::DbConnection;
+use crate::middlewares::auth::AdminGuard;
+use crate::middlewares::client::ClientInfo;
+use crate::models::{AuditEventModel, LoginAttemptModel};
+use crate::schema::{audit_event, login_attempt};
+use crate::utils::audit::{
+    AUDIT_SETTINGS_UPDATE, AUDIT_TARGET_SETTINGS, AuditChanges, AuditTarget, record_audit_event,
+};
+use crate::utils::pagination::{page_limit, parse_time_filter};
+use crate::utils::response::ApiResponse;
+use crate::utils::settings::{
+    DEFAULT_LOG_MAX_ROWS, DEFAULT_LOG_RETENTION_DAYS, DEFAULT_STALE_AFTER_SECONDS,
+    SETTING_LOG_MAX_ROWS, SETTING_LOG_RETENTION_DAYS, SETTING_REQUIRE_TOTP,
+    SETTING_STALE_AFTER_SECONDS, get_bool_setting, get_int_setting, set_bool_setting,
+    set_int_setting,
+};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetLoginAttemptsResponse {
+    pub login_attempts: Vec<LoginAttemptModel>,
+    pub total: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetAuditEventsResponse {
+    pub audit_events: Vec<AuditEventModel>,
+    pub total: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminSettings {
+    pub require_totp: bool,
+    pub log_retention_days: i32,
+    pub log_max_rows: i32,
+    pub stale_after_seconds: i32,
+}
+
+/// Settings left out keep their current value.
+#[derive(Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateAdminSettingsForm {
+    pub require_totp: Option<bool>,
+
+    #[validate(range(min = 0, message = "Log retention days cannot be negative"))]
+    pub log_retention_days: Option<i32>,
+
+    #[validate(range(min = 0, message = "Log max rows cannot be negative"))]
+    pub log_max_rows: Option<i32>,
+
+    #[validate(range(min = 0, message = "Stale threshold cannot be negative"))]
+    pub stale_after_seconds: Option<i32>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminSettingsResponse {
+    pub settings: AdminSettings,
+}
+
+pub fn load_admin_settings(connection: &mut PgConnection) -> QueryResult<AdminSettings> {
+    Ok(AdminSettings {
+        require_totp: get_bool_setting(connection, SETTING_REQUIRE_TOTP)?,
+        log_retention_days: get_int_setting(
+            connection,
+            SETTING_LOG_RETENTION_DAYS,
+            DEFAULT_LOG_RETENTION_DAYS,
+        )?,
+        log_max_rows: get_int_setting(connection, SETTING_LOG_MAX_ROWS, DEFAULT_LOG_MAX_ROWS)?,
+        stale_after_seconds: get_int_setting(
+            connection,
+            SETTING_STALE_AFTER_SECONDS,
+            DEFAULT_STALE_AFTER_SECONDS,
+        )?,
+    })
+}
+
+#[get("/admin/settings")]
+pub fn get_admin_settings(
+    db: &State<DbConnection>,
+    _admin: AdminGuard,
+) -> Custom<Json<ApiResponse<AdminSettingsResponse>>> {
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    match load_admin_settings(connection) {
+        Ok(settings) => Custom(
+            Status::Ok,
+            Json(ApiResponse::success(
+                "Settings fetched successfully",
+                AdminSettingsResponse { settings },
+            )),
+        ),
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to fetch settings")),
+        ),
+    }
+}
+
+#[put("/admin/settings", format = "application/json", data = "<form>")]
+pub fn update_admin_settings(
+    db: &State<DbConnection>,
+    admin: AdminGuard,
+    client: ClientInfo,
+    form: Json<UpdateAdminSettingsForm>,
+) -> Custom<Json<ApiResponse<AdminSettingsResponse>>> {
+    if let Err(_e) = form.validate() {
+        return Custom(Status::BadRequest, Json(ApiResponse::error("Bad request")));
+    }
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let before = match load_admin_settings(connection) {
+        Ok(settings) => settings,
+        Err(_e) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Failed to fetch settings")),
+            );
+        }
+    };
+
+    let result = connection.transaction::<_, diesel::result::Error, _>(|conn| {
+        if let Some(require_totp) = form.require_totp {
+            set_bool_setting(conn, SETTING_REQUIRE_TOTP, require_totp)?;
+        }
+        if let Some(days) = form.log_retention_days {
+            set_int_setting(conn, SETTING_LOG_RETENTION_DAYS, days)?;
+        }
+        if let Some(max_rows) = form.log_max_rows {
+            set_int_setting(conn, SETTING_LOG_MAX_ROWS, max_rows)?;
+        }
+        if let Some(seconds) = form.stale_after_seconds {
+            set_int_setting(conn, SETTING_STALE_AFTER_SECONDS, seconds)?;
+        }
+        load_admin_settings(conn)
+    });
+
+    match result {
+        Ok(settings) => {
+            record_audit_event(
+                connection,
+                Some(&admin.0),
+                &client,
+                AUDIT_SETTINGS_UPDATE,
+                Some(AuditTarget {
+                    target_type: AUDIT_TARGET_SETTINGS,
+                    target_id: None,
+                }),
+                AuditChanges::diff(Some(&before), Some(&settings)),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "Settings updated successfully",
+                    AdminSettingsResponse { settings },
+                )),
+            )
+        }
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to update settings")),
+        ),
+    }
+}
+
+#[get("/admin/login-attempts?<username>&<ip>&<succeeded>&<limit>&<offset>")]
+pub fn get_login_attempts(
+    db: &State<DbConnection>,
+    _admin: AdminGuard,
+    username: Option<&str>,
+    ip: Option<&str>,
+    succeeded: Option<bool>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Custom<Json<ApiResponse<GetLoginAttemptsResponse>>> {
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let filtered = || {
+        let mut query = login_attempt::table.into_boxed();
+        if let Some(username) = username {
+            query = query.filter(login_attempt::username.eq(username));
+        }
+        if let Some(ip) = ip {
+            query = query.filter(login_attempt::ip_address.eq(ip));
+        }
+        if let Some(succeeded) = succeeded {
+            query = query.filter(login_attempt::succeeded.eq(succeeded));
+        }
+        query
+    };
+
+    let total = match filtered().count().get_result::<i64>(connection) {
+        Ok(total) => total,
+        Err(_e) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Failed to fetch login attempts")),
+            );
+        }
+    };
+
+    match filtered()
+        .order(login_attempt::created_at.desc())
+        .limit(page_limit(limit))
+        .offset(offset.unwrap_or(0).max(0))
+        .select(LoginAttemptModel::as_select())
+        .load::<LoginAttemptModel>(connection)
+    {
+        Ok(login_attempts) => Custom(
+            Status::Ok,
+            Json(ApiResponse::success(
+                "Login attempts fetched successfully",
+                GetLoginAttemptsResponse {
+                    login_attempts,
+                    total,
+                },
+            )),
+        ),
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to fetch login attempts")),
+        ),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[get(
+    "/admin/audit-events?<actor>&<action>&<target_type>&<target_id>&<since>&<until>&<limit>&<offset>"
+)]
+pub fn get_audit_events(
+    db: &State<DbConnection>,
+    _admin: AdminGuard,
+    actor: Option<&str>,
+    action: Option<&str>,
+    target_type: Option<&str>,
+    target_id: Option<&str>,
+    since: Option<&str>,
+    until: Option<&str>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Custom<Json<ApiResponse<GetAuditEventsResponse>>> {
+    let target_id = match target_id.map(uuid::Uuid::parse_str).transpose() {
+        Ok(target_id) => target_id,
+        Err(_) => {
+            return Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error("Invalid target ID")),
+            );
+        }
+    };
+
+    let (since, until) = match (parse_time_filter(since), parse_time_filter(until)) {
+        (Ok(since), Ok(until)) => (since, until),
+        _ => {
+            return Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error("Timestamps must be RFC 3339")),
+            );
+        }
+    };
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let filtered = || {
+        let mut query = audit_event::table.into_boxed();
+        if let Some(actor) = actor {
+            query = query.filter(audit_event::actor_username.eq(actor));
+        }
+        // `repository.` matches every repository action
+        if let Some(action) = action {
+            query = match action.strip_suffix('.') {
+                Some(prefix) => query.filter(audit_event::action.like(format!("{}.%", prefix))),
+                None => query.filter(audit_event::action.eq(action)),
+            };
+        }
+        if let Some(target_type) = target_type {
+            query = query.filter(audit_event::target_type.eq(target_type));
+        }
+        if let Some(target_id) = target_id {
+            query = query.filter(audit_event::target_id.eq(target_id));
+        }
+        if let Some(since) = since {
+            query = query.filter(audit_event::created_at.ge(since));
+        }
+        if let Some(until) = until {
+            query = query.filter(audit_event::created_at.lt(until));
+        }
+        query
+    };
+
+    let total = match filtered().count().get_result::<i64>(connection) {
+        Ok(total) => total,
+        Err(_e) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Failed to fetch audit events")),
+            );
+        }
+    };
+
+    match filtered()
+        .order(audit_event::created_at.desc())
+        .limit(page_limit(limit))
+        .offset(offset.unwrap_or(0).max(0))
+        .select(AuditEventModel::as_select())
+        .load::<AuditEventModel>(connection)
+    {
+        Ok(audit_events) => Custom(
+            Status::Ok,
+            Json(ApiResponse::success(
+                "Audit events fetched successfully",
+                GetAuditEventsResponse {
+                    audit_events,
+                    total,
+                },
+            )),
+        ),
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to fetch audit events")),
+        ),
+    }
+}
@@ -7,13 +7,14 @@ use rocket::serde::json::Json;
 use serde::Serialize;
 
 use crate::db::DbConnection;
-use crate::middlewares::auth::AuthGuard;
-use crate::models::RepositoryModel;
+use crate::middlewares::auth::{AuthGuard, scope};
+use crate::models::{PublicRepository, RepositoryModel};
 use crate::schema::repository;
+use crate::utils::access::{TeamRole, accessible_repositories};
 use crate::utils::response::ApiResponse;
 use diesel::deserialize::QueryableByName;
 use diesel::sql_query;
-use diesel::sql_types::{BigInt, Date, Timestamptz, Uuid as SqlUuid};
+use diesel::sql_types::{BigInt, Date, Uuid as SqlUuid};
 
 #[derive(QueryableByName)]
 struct DayCountResult {
@@ -36,7 +37,7 @@ pub struct DashboardData {
     pub total_repositories: i64,
     pub enabled: i64,
     pub disabled: i64,
-    pub last_cloned_repos: Vec<RepositoryModel>,
+    pub last_cloned_repos: Vec<PublicRepository>,
     pub daily_logs: Vec<DailyLogCount>,
     pub daily_error_logs: Vec<DailyLogCount>,
 }
@@ -50,21 +51,19 @@ pub struct GetDashboardDataResponse {
 #[get("/aggregate/dashboard")]
 pub fn get_dashboard_data(
     db: &State<DbConnection>,
-    user: AuthGuard,
+    user: AuthGuard<scope::Read>,
 ) -> Custom<Json<ApiResponse<GetDashboardDataResponse>>> {
     let conn = &mut db.get().unwrap();
     let now = Utc::now();
     let week_ago = now - Duration::days(7);
 
     // Counts for repositories
-    let total_repositories = repository::dsl::repository
-        .filter(repository::dsl::user_id.eq(user.0.id))
+    let total_repositories = accessible_repositories(user.0.id, TeamRole::Viewer)
         .count()
         .get_result::<i64>(conn)
         .unwrap_or(0);
 
-    let enabled = repository::dsl::repository
-        .filter(repository::dsl::user_id.eq(user.0.id))
+    let enabled = accessible_repositories(user.0.id, TeamRole::Viewer)
         .filter(repository::dsl::is_enabled.eq(true))
         .count()
         .get_result::<i64>(conn)
@@ -73,39 +72,44 @@ pub fn get_dashboard_data(
     let disabled = total_repositories - enabled;
 
     // Fetch last cloned repositories
-    let last_cloned_repos = repository::dsl::repository
-        .filter(repository::dsl::user_id.eq(user.0.id))
+    let last_cloned_repos = accessible_repositories(user.0.id, TeamRole::Viewer)
         .filter(repository::dsl::last_clone_at.is_not_null())
         .order(repository::dsl::last_clone_at.desc())
         .limit(10)
         .load::<RepositoryModel>(conn)
-        .unwrap_or_default();
+        .unwrap_or_default()
+        .into_iter()
+        .map(PublicRepository::from)
+        .collect();
 
-    // Chart data: daily log counts for past 7 days (all logs)
+    // Chart data: daily log counts for past 7 days (all logs), read from the
+    // summary table because old logs may already be pruned
     let logs_query = "\
-        SELECT date(l.created_at) as day, count(*) as count \
-        FROM repository_logs l \
-        JOIN repository r ON r.id = l.repository_id \
-        WHERE r.user_id = $1 AND l.created_at >= $2 \
-        GROUP BY day ORDER BY day";
+        SELECT c.day, sum(c.count)::bigint as count \
+        FROM repository_log_daily_count c \
+        JOIN repository r ON r.id = c.repository_id \
+        WHERE (r.user_id = $1 OR r.team_id IN (SELECT team_id FROM team_member WHERE user_id = $1)) \
+        AND c.day >= $2 \
+        GROUP BY c.day ORDER BY c.day";
 
     let raw_daily_logs: Vec<DayCountResult> = sql_query(logs_query)
         .bind::<SqlUuid, _>(user.0.id)
-        .bind::<Timestamptz, _>(week_ago)
+        .bind::<Date, _>(week_ago.date_naive())
         .load(conn)
         .unwrap_or_else(|_| vec![]);
 
     // Chart data: daily error log counts for past 7 days (type = 'error_clone_job')
     let errors_query = "\
-        SELECT date(l.created_at) as day, count(*) as count \
-        FROM repository_logs l \
-        JOIN repository r ON r.id = l.repository_id \
-        WHERE r.user_id = $1 AND l.created_at >= $2 AND l.type = 'error_clone_job' \
-        GROUP BY day ORDER BY day";
+        SELECT c.day, sum(c.count)::bigint as count \
+        FROM repository_log_daily_count c \
+        JOIN repository r ON r.id = c.repository_id \
+        WHERE (r.user_id = $1 OR r.team_id IN (SELECT team_id FROM team_member WHERE user_id = $1)) \
+        AND c.day >= $2 AND c.type = 'error_clone_job' \
+        GROUP BY c.day ORDER BY c.day";
 
     let raw_daily_errors: Vec<DayCountResult> = sql_query(errors_query)
         .bind::<SqlUuid, _>(user.0.id)
-        .bind::<Timestamptz, _>(week_ago)
+        .bind::<Date, _>(week_ago.date_naive())
         .load(conn)
         .unwrap_or_else(|_| vec![]);
 
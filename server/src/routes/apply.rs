@@ -0,0 +1,507 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::Utc;
+use diesel::prelude::*;
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::config::Config;
+use crate::db::DbConnection;
+use crate::middlewares::auth::{AuthGuard, scope};
+use crate::middlewares::client::ClientInfo;
+use crate::models::{InsertableRepositoryModel, RepositoryChangeset, RepositoryModel};
+use crate::routes::repository::{
+    INVALID_REF_FILTERS_MESSAGE, normalize_ref_filters, normalize_tags,
+};
+use crate::schema::repository;
+use crate::utils::access::{TeamRole, team_role};
+use crate::utils::audit::{
+    AUDIT_REPOSITORY_CREATE, AUDIT_REPOSITORY_DELETE, AUDIT_REPOSITORY_UPDATE,
+    AUDIT_TARGET_REPOSITORY, AuditChanges, AuditTarget, MASKED_VALUE, record_audit_event,
+};
+use crate::utils::master_key::MasterKeys;
+use crate::utils::response::ApiResponse;
+
+const ACTION_CREATE: &str = "create";
+const ACTION_UPDATE: &str = "update";
+const ACTION_DELETE: &str = "delete";
+const ACTION_UNCHANGED: &str = "unchanged";
+
+/// One repository of a declarative configuration, matched to existing ones by name.
+/// Credential references are resolved by the client, keys arrive as values.
+#[derive(Deserialize, Validate)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RepositorySpec {
+    #[validate(length(min = 3, max = 200))]
+    pub name: String,
+
+    #[validate(length(max = 512))]
+    pub url: Option<String>,
+
+    #[validate(length(min = 3, max = 512))]
+    pub git_source: String,
+
+    #[validate(length(max = 512))]
+    pub git_source_secret_key: Option<String>,
+
+    #[validate(length(min = 3, max = 512))]
+    pub git_target: String,
+
+    #[validate(length(min = 3))]
+    pub git_target_secret_key: String,
+
+    pub git_clone_period_seconds: u32,
+
+    #[serde(default)]
+    #[validate(length(max = 20))]
+    pub tags: Vec<String>,
+
+    #[serde(default)]
+    #[validate(length(max = 20))]
+    pub ref_filters: Vec<String>,
+
+    #[serde(default = "enabled_by_default")]
+    pub is_enabled: bool,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+/// The complete set of repositories of the current user, or of `teamId`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ApplyForm {
+    pub team_id: Option<Uuid>,
+    pub repositories: Vec<RepositorySpec>,
+}
+
+#[derive(Serialize)]
+pub struct FieldChange {
+    pub before: Value,
+    pub after: Value,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedChange {
+    /// `create`, `update`, `delete` or `unchanged`.
+    pub action: &'static str,
+    pub name: String,
+    pub repository_id: Option<Uuid>,
+    /// Changed fields by their API name, keys are masked.
+    pub changes: BTreeMap<&'static str, FieldChange>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplyResponse {
+    pub dry_run: bool,
+    pub plan: Vec<PlannedChange>,
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    pub unchanged: usize,
+}
+
+/// A spec with tags and ref filters normalized, in the shape of a repository row.
+struct DesiredRepository {
+    name: String,
+    url: Option<String>,
+    git_source: String,
+    git_source_secret_key: Option<String>,
+    git_target: String,
+    git_target_secret_key: String,
+    /// The keys encrypted under the master key, as they are written.
+    sealed_source_key: Option<String>,
+    sealed_target_key: String,
+    git_clone_period_seconds: i32,
+    tags: Vec<String>,
+    ref_filters: Vec<String>,
+    is_enabled: bool,
+}
+
+impl DesiredRepository {
+    fn changeset(&self) -> RepositoryChangeset<'_> {
+        RepositoryChangeset {
+            name: Some(&self.name),
+            url: Some(self.url.as_deref()),
+            is_enabled: Some(self.is_enabled),
+            git_source: Some(&self.git_source),
+            git_source_secret_key: Some(self.sealed_source_key.as_deref()),
+            git_target: Some(&self.git_target),
+            git_target_secret_key: Some(&self.sealed_target_key),
+            git_clone_period_seconds: Some(self.git_clone_period_seconds),
+            tags: Some(self.tags.clone()),
+            ref_filters: Some(self.ref_filters.clone()),
+            updated_at: Some(Utc::now()),
+        }
+    }
+}
+
+fn masked(secret: Option<&str>) -> Value {
+    match secret {
+        Some(_) => json!(MASKED_VALUE),
+        None => Value::Null,
+    }
+}
+
+/// Fields of `existing` that differ from `desired`. With no existing row every field is new.
+fn diff(
+    existing: Option<&RepositoryModel>,
+    desired: &DesiredRepository,
+) -> BTreeMap<&'static str, FieldChange> {
+    let mut changes = BTreeMap::new();
+    let mut compare = |field: &'static str, before: Option<Value>, after: Value| {
+        if before.as_ref() != Some(&after) {
+            changes.insert(
+                field,
+                FieldChange {
+                    before: before.unwrap_or(Value::Null),
+                    after,
+                },
+            );
+        }
+    };
+
+    compare("url", existing.map(|r| json!(r.url)), json!(desired.url));
+    compare(
+        "gitSource",
+        existing.map(|r| json!(r.git_source)),
+        json!(desired.git_source),
+    );
+    compare(
+        "gitTarget",
+        existing.map(|r| json!(r.git_target)),
+        json!(desired.git_target),
+    );
+    compare(
+        "gitClonePeriodSeconds",
+        existing.map(|r| json!(r.git_clone_period_seconds)),
+        json!(desired.git_clone_period_seconds),
+    );
+    compare("tags", existing.map(|r| json!(r.tags)), json!(desired.tags));
+    compare(
+        "refFilters",
+        existing.map(|r| json!(r.ref_filters)),
+        json!(desired.ref_filters),
+    );
+    compare(
+        "isEnabled",
+        existing.map(|r| json!(r.is_enabled)),
+        json!(desired.is_enabled),
+    );
+
+    // keys are compared in full but only shown masked
+    let source_key_changed = existing.is_none_or(|r| {
+        r.git_source_secret_key.as_deref() != desired.git_source_secret_key.as_deref()
+    });
+    if source_key_changed && (existing.is_some() || desired.git_source_secret_key.is_some()) {
+        changes.insert(
+            "gitSourceSecretKey",
+            FieldChange {
+                before: masked(existing.and_then(|r| r.git_source_secret_key.as_deref())),
+                after: masked(desired.git_source_secret_key.as_deref()),
+            },
+        );
+    }
+    if existing.is_none_or(|r| {
+        r.git_target_secret_key.as_deref() != Some(desired.git_target_secret_key.as_str())
+    }) {
+        changes.insert(
+            "gitTargetSecretKey",
+            FieldChange {
+                before: masked(existing.and_then(|r| r.git_target_secret_key.as_deref())),
+                after: masked(Some(&desired.git_target_secret_key)),
+            },
+        );
+    }
+
+    changes
+}
+
+fn bad_request<T>(message: &str) -> Custom<Json<ApiResponse<T>>> {
+    Custom(Status::BadRequest, Json(ApiResponse::error(message)))
+}
+
+/// Reconciles the repositories of the current user, or of a team they maintain, with a
+/// declarative configuration. Repositories are matched by name, missing ones are created
+/// and differing ones updated. With `prune` repositories absent from the configuration are
+/// deleted. With `dry_run` only the plan is returned.
+#[post(
+    "/repository/apply?<dry_run>&<prune>",
+    format = "application/json",
+    data = "<form>"
+)]
+pub fn apply_repositories(
+    db: &State<DbConnection>,
+    config: &State<Config>,
+    user: AuthGuard<scope::RepositoriesWrite>,
+    client: ClientInfo,
+    dry_run: Option<bool>,
+    prune: Option<bool>,
+    form: Json<ApplyForm>,
+) -> Custom<Json<ApiResponse<ApplyResponse>>> {
+    let dry_run = dry_run.unwrap_or(false);
+    let prune = prune.unwrap_or(false);
+    let periods = &config.repository;
+    let keys = MasterKeys::from_config(&config.keys);
+
+    let mut desired: Vec<DesiredRepository> = Vec::with_capacity(form.repositories.len());
+    for spec in &form.repositories {
+        if spec.validate().is_err() {
+            return bad_request(&format!("Repository {} is invalid", spec.name));
+        }
+        if desired.iter().any(|d| d.name == spec.name) {
+            return bad_request(&format!("Repository {} is listed twice", spec.name));
+        }
+        if !(periods.min_period_seconds..=periods.max_period_seconds)
+            .contains(&spec.git_clone_period_seconds)
+        {
+            return bad_request(&format!(
+                "Repository {}: cloning period must be between {} and {} seconds",
+                spec.name, periods.min_period_seconds, periods.max_period_seconds
+            ));
+        }
+        let Some(tags) = normalize_tags(&spec.tags) else {
+            return bad_request(&format!(
+                "Repository {}: tags must be between 1 and 50 characters long",
+                spec.name
+            ));
+        };
+        let Some(ref_filters) = normalize_ref_filters(&spec.ref_filters) else {
+            return bad_request(&format!(
+                "Repository {}: {}",
+                spec.name, INVALID_REF_FILTERS_MESSAGE
+            ));
+        };
+
+        let git_source_secret_key = spec.git_source_secret_key.clone().filter(|k| !k.is_empty());
+        desired.push(DesiredRepository {
+            name: spec.name.clone(),
+            url: spec.url.clone().filter(|u| !u.is_empty()),
+            git_source: spec.git_source.clone(),
+            sealed_source_key: git_source_secret_key.as_deref().map(|k| keys.seal(k)),
+            git_source_secret_key,
+            git_target: spec.git_target.clone(),
+            sealed_target_key: keys.seal(&spec.git_target_secret_key),
+            git_target_secret_key: spec.git_target_secret_key.clone(),
+            git_clone_period_seconds: spec.git_clone_period_seconds as i32,
+            tags,
+            ref_filters,
+            is_enabled: spec.is_enabled,
+        });
+    }
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    if let Some(team_id) = form.team_id {
+        match team_role(connection, team_id, user.0.id) {
+            Ok(Some(TeamRole::Maintainer)) => {}
+            Ok(_) => {
+                return Custom(
+                    Status::Forbidden,
+                    Json(ApiResponse::error(
+                        "Only team maintainers can apply a team configuration",
+                    )),
+                );
+            }
+            Err(_e) => {
+                return Custom(
+                    Status::InternalServerError,
+                    Json(ApiResponse::error("Database error")),
+                );
+            }
+        }
+    }
+
+    // personal repositories, or those of the team, never both
+    let existing = match form.team_id {
+        Some(team_id) => repository::table
+            .filter(repository::team_id.eq(team_id))
+            .order(repository::name.asc())
+            .load::<RepositoryModel>(connection),
+        None => repository::table
+            .filter(repository::user_id.eq(user.0.id))
+            .filter(repository::team_id.is_null())
+            .order(repository::name.asc())
+            .load::<RepositoryModel>(connection),
+    };
+    let mut existing = match existing {
+        Ok(existing) => existing,
+        Err(_e) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Failed to fetch repositories")),
+            );
+        }
+    };
+    // compared in plain text, a key that cannot be decrypted counts as changed and is replaced
+    for repo in &mut existing {
+        repo.git_source_secret_key = repo
+            .git_source_secret_key
+            .as_deref()
+            .and_then(|k| keys.open(k).ok());
+        repo.git_target_secret_key = repo
+            .git_target_secret_key
+            .as_deref()
+            .and_then(|k| keys.open(k).ok());
+    }
+
+    let mut by_name: HashMap<&str, &RepositoryModel> = HashMap::new();
+    for repo in &existing {
+        if by_name.insert(repo.name.as_str(), repo).is_some()
+            && desired.iter().any(|d| d.name == repo.name)
+        {
+            return Custom(
+                Status::Conflict,
+                Json(ApiResponse::error(&format!(
+                    "Several repositories are named {}, rename them before applying",
+                    repo.name
+                ))),
+            );
+        }
+    }
+
+    let mut plan: Vec<PlannedChange> = desired
+        .iter()
+        .map(|d| {
+            let current = by_name.get(d.name.as_str()).copied();
+            let changes = diff(current, d);
+            PlannedChange {
+                action: match current {
+                    None => ACTION_CREATE,
+                    Some(_) if changes.is_empty() => ACTION_UNCHANGED,
+                    Some(_) => ACTION_UPDATE,
+                },
+                name: d.name.clone(),
+                repository_id: current.map(|r| r.id),
+                changes,
+            }
+        })
+        .collect();
+
+    if prune {
+        let wanted: HashSet<&str> = desired.iter().map(|d| d.name.as_str()).collect();
+        plan.extend(
+            existing
+                .iter()
+                .filter(|r| !wanted.contains(r.name.as_str()))
+                .map(|r| PlannedChange {
+                    action: ACTION_DELETE,
+                    name: r.name.clone(),
+                    repository_id: Some(r.id),
+                    changes: BTreeMap::new(),
+                }),
+        );
+    }
+
+    let count = |action: &str| plan.iter().filter(|c| c.action == action).count();
+    let mut response = ApplyResponse {
+        dry_run,
+        created: count(ACTION_CREATE),
+        updated: count(ACTION_UPDATE),
+        deleted: count(ACTION_DELETE),
+        unchanged: count(ACTION_UNCHANGED),
+        plan: Vec::new(),
+    };
+
+    if dry_run {
+        response.plan = plan;
+        return Custom(
+            Status::Ok,
+            Json(ApiResponse::success("Plan computed", response)),
+        );
+    }
+
+    // (action, before, after) of every change, audited once everything is committed
+    let result = connection.transaction::<_, diesel::result::Error, _>(|connection| {
+        let mut applied: Vec<(
+            &'static str,
+            Option<&RepositoryModel>,
+            Option<RepositoryModel>,
+        )> = Vec::new();
+
+        for (change, d) in plan.iter_mut().zip(&desired) {
+            let current = by_name.get(d.name.as_str()).copied();
+            match change.action {
+                ACTION_CREATE => {
+                    let new_repo = InsertableRepositoryModel {
+                        user_id: Some(user.0.id).filter(|_| form.team_id.is_none()),
+                        team_id: form.team_id,
+                        name: &d.name,
+                        url: d.url.as_deref(),
+                        is_enabled: d.is_enabled,
+                        git_source: &d.git_source,
+                        git_source_secret_key: d.sealed_source_key.as_deref(),
+                        git_target: &d.git_target,
+                        git_target_secret_key: Some(&d.sealed_target_key),
+                        git_clone_period_seconds: d.git_clone_period_seconds,
+                        tags: d.tags.clone(),
+                        log_retention_days: None,
+                        log_max_rows: None,
+                        ref_filters: d.ref_filters.clone(),
+                    };
+                    let inserted = diesel::insert_into(repository::table)
+                        .values(&new_repo)
+                        .get_result::<RepositoryModel>(connection)?;
+                    change.repository_id = Some(inserted.id);
+                    applied.push((AUDIT_REPOSITORY_CREATE, None, Some(inserted)));
+                }
+                ACTION_UPDATE => {
+                    let before = current.expect("updates have an existing repository");
+                    let updated = diesel::update(repository::table.find(before.id))
+                        .set(&d.changeset())
+                        .get_result::<RepositoryModel>(connection)?;
+                    applied.push((AUDIT_REPOSITORY_UPDATE, Some(before), Some(updated)));
+                }
+                _ => {}
+            }
+        }
+
+        for change in plan.iter().filter(|c| c.action == ACTION_DELETE) {
+            let before = existing
+                .iter()
+                .find(|r| Some(r.id) == change.repository_id)
+                .expect("deletes have an existing repository");
+            diesel::delete(repository::table.find(before.id)).execute(connection)?;
+            applied.push((AUDIT_REPOSITORY_DELETE, Some(before), None));
+        }
+
+        Ok(applied)
+    });
+
+    let applied = match result {
+        Ok(applied) => applied,
+        Err(_e) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Failed to apply the configuration")),
+            );
+        }
+    };
+
+    for (action, before, after) in applied {
+        let target_id = after.as_ref().or(before).map(|r| r.id);
+        record_audit_event(
+            connection,
+            Some(&user.0),
+            &client,
+            action,
+            target_id.map(|id| AuditTarget::new(AUDIT_TARGET_REPOSITORY, id)),
+            AuditChanges::diff(before, after.as_ref()),
+        );
+    }
+
+    response.plan = plan;
+    Custom(
+        Status::Ok,
+        Json(ApiResponse::success("Configuration applied", response)),
+    )
+}
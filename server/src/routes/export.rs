@@ -0,0 +1,741 @@
+use std::collections::{HashMap, HashSet};
+
+use argon2::Params;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use rand::{Rng, rng};
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::config::{Config, RepositoryConfig};
+use crate::db::DbConnection;
+use crate::middlewares::auth::{AuthGuard, scope};
+use crate::middlewares::client::ClientInfo;
+use crate::middlewares::passphrase::ExportPassphrase;
+use crate::models::{InsertableRepositoryModel, RepositoryModel};
+use crate::routes::admin::load_admin_settings;
+use crate::routes::repository::{
+    INVALID_REF_FILTERS_MESSAGE, normalize_ref_filters, normalize_tags,
+};
+use crate::schema::{repository, team, user};
+use crate::utils::access::{TeamRole, accessible_repositories, team_role};
+use crate::utils::audit::{
+    AUDIT_REPOSITORY_EXPORT, AUDIT_REPOSITORY_IMPORT, AUDIT_SETTINGS_UPDATE,
+    AUDIT_TARGET_REPOSITORY, AUDIT_TARGET_SETTINGS, AuditChanges, AuditTarget, record_audit_event,
+};
+use crate::utils::crypto::{decrypt_secret, derive_key, encrypt_secret};
+use crate::utils::master_key::{MasterKeyError, MasterKeys};
+use crate::utils::response::ApiResponse;
+use crate::utils::settings::{
+    SETTING_LOG_MAX_ROWS, SETTING_LOG_RETENTION_DAYS, SETTING_STALE_AFTER_SECONDS, set_int_setting,
+};
+
+pub const EXPORT_FORMAT: &str = "gitmirrors-export";
+/// Bumped whenever the document changes incompatibly, imports reject other versions.
+pub const EXPORT_VERSION: u32 = 1;
+
+const ENCRYPTION_ALGORITHM: &str = "aes-256-gcm";
+const ENCRYPTION_KDF: &str = "argon2id";
+const MIN_PASSPHRASE_LENGTH: usize = 12;
+/// Upper bounds for the Argon2 parameters of imported documents, so an altered export
+/// cannot make the server spend unbounded memory or time deriving its key.
+const MAX_IMPORT_MEMORY_COST: u32 = 256 * 1024;
+const MAX_IMPORT_TIME_COST: u32 = 16;
+const MAX_IMPORT_PARALLELISM: u32 = 16;
+
+/// How the keys of an export are encrypted.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportEncryption {
+    pub algorithm: String,
+    pub kdf: String,
+    /// Base64 salt of the key derivation.
+    pub salt: String,
+    /// Argon2 memory cost in KiB, documents without it used the Argon2 defaults.
+    #[serde(default = "default_memory_cost")]
+    pub memory_cost: u32,
+    /// Argon2 iterations.
+    #[serde(default = "default_time_cost")]
+    pub time_cost: u32,
+    /// Argon2 lanes.
+    #[serde(default = "default_parallelism")]
+    pub parallelism: u32,
+}
+
+fn default_memory_cost() -> u32 {
+    Params::DEFAULT_M_COST
+}
+
+fn default_time_cost() -> u32 {
+    Params::DEFAULT_T_COST
+}
+
+fn default_parallelism() -> u32 {
+    Params::DEFAULT_P_COST
+}
+
+impl ExportEncryption {
+    /// Argon2 parameters of the document, `None` when they are invalid or above the
+    /// import limits.
+    fn params(&self) -> Option<Params> {
+        if self.memory_cost > MAX_IMPORT_MEMORY_COST
+            || self.time_cost > MAX_IMPORT_TIME_COST
+            || self.parallelism > MAX_IMPORT_PARALLELISM
+        {
+            return None;
+        }
+        Params::new(self.memory_cost, self.time_cost, self.parallelism, None).ok()
+    }
+}
+
+/// Server wide settings of `/admin/settings` carried by admin exports of every repository.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedSettings {
+    pub log_retention_days: i32,
+    pub log_max_rows: i32,
+    pub stale_after_seconds: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedRepository {
+    pub name: String,
+    pub url: Option<String>,
+    /// Username of the owner, unset for team repositories.
+    pub owner: Option<String>,
+    /// Name of the owning team.
+    pub team: Option<String>,
+    pub is_enabled: bool,
+    pub git_source: String,
+    pub git_target: String,
+    pub git_clone_period_seconds: i32,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub ref_filters: Vec<String>,
+    pub log_retention_days: Option<i32>,
+    pub log_max_rows: Option<i32>,
+    /// Encrypted with the export passphrase, left out without one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_source_secret_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_target_secret_key: Option<String>,
+}
+
+/// Mirror definitions of one server, to be imported into another.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportDocument {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    /// Set when the document includes keys.
+    pub encryption: Option<ExportEncryption>,
+    /// Only included in admin exports with `all`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<ExportedSettings>,
+    pub repositories: Vec<ExportedRepository>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportForm {
+    pub document: ExportDocument,
+    /// Required when the document includes keys.
+    pub passphrase: Option<String>,
+    /// Owner usernames of the document mapped to users of this server, unmapped names are
+    /// looked up as they are.
+    #[serde(default)]
+    pub user_map: HashMap<String, String>,
+    /// Team names of the document mapped to teams of this server.
+    #[serde(default)]
+    pub team_map: HashMap<String, String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedRepository {
+    pub name: String,
+    pub owner: Option<String>,
+    pub team: Option<String>,
+    /// Unset on dry runs.
+    pub repository_id: Option<Uuid>,
+    /// Imported disabled because the document holds no target key.
+    pub needs_credentials: bool,
+}
+
+/// A repository of the document that was not imported.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportConflict {
+    pub name: String,
+    pub owner: Option<String>,
+    pub team: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResponse {
+    pub dry_run: bool,
+    /// Whether the settings of the document were (or on dry runs would be) applied.
+    pub settings_imported: bool,
+    pub imported: Vec<ImportedRepository>,
+    pub conflicts: Vec<ImportConflict>,
+}
+
+fn bad_request<T>(message: &str) -> Custom<Json<ApiResponse<T>>> {
+    Custom(Status::BadRequest, Json(ApiResponse::error(message)))
+}
+
+fn database_error<T>() -> Custom<Json<ApiResponse<T>>> {
+    Custom(
+        Status::InternalServerError,
+        Json(ApiResponse::error("Database error")),
+    )
+}
+
+/// Checks an exported repository like `AddRepositoryForm`, returning its normalized tags
+/// and ref filters.
+fn validate_exported(
+    exported: &ExportedRepository,
+    periods: &RepositoryConfig,
+) -> Result<(Vec<String>, Vec<String>), String> {
+    let length = |value: &str, min: usize, max: usize| (min..=max).contains(&value.chars().count());
+
+    if !length(&exported.name, 3, 200) {
+        return Err("Name must be between 3 and 200 characters long".to_string());
+    }
+    if exported.url.as_deref().is_some_and(|u| !length(u, 0, 512)) {
+        return Err("Url must be less than 512 characters long".to_string());
+    }
+    if !length(&exported.git_source, 3, 512) || !length(&exported.git_target, 3, 512) {
+        return Err("git Source and Target must be between 3 and 512 characters long".to_string());
+    }
+    let period = exported.git_clone_period_seconds;
+    if !u32::try_from(period)
+        .is_ok_and(|p| (periods.min_period_seconds..=periods.max_period_seconds).contains(&p))
+    {
+        return Err(format!(
+            "Cloning period must be between {} and {} seconds",
+            periods.min_period_seconds, periods.max_period_seconds
+        ));
+    }
+    if exported.log_retention_days.is_some_and(|d| d < 0)
+        || exported.log_max_rows.is_some_and(|r| r < 0)
+    {
+        return Err("Log retention cannot be negative".to_string());
+    }
+
+    let tags = normalize_tags(&exported.tags)
+        .filter(|_| exported.tags.len() <= 20)
+        .ok_or("Tags must be between 1 and 50 characters long, at most 20 of them")?;
+    let ref_filters = normalize_ref_filters(&exported.ref_filters)
+        .filter(|_| exported.ref_filters.len() <= 20)
+        .ok_or(INVALID_REF_FILTERS_MESSAGE)?;
+
+    Ok((tags, ref_filters))
+}
+
+/// Exports the repositories the current user maintains, or with `all` every repository for
+/// admins. Keys are only included when a passphrase is sent in `X-Export-Passphrase`, and
+/// are then encrypted with a key derived from it.
+#[get("/export?<all>")]
+pub fn export_repositories(
+    db: &State<DbConnection>,
+    config: &State<Config>,
+    user: AuthGuard<scope::RepositoriesWrite>,
+    client: ClientInfo,
+    passphrase: ExportPassphrase,
+    all: Option<bool>,
+) -> Custom<Json<ApiResponse<ExportDocument>>> {
+    let all = all.unwrap_or(false);
+    if all && !user.0.is_admin {
+        return Custom(
+            Status::Forbidden,
+            Json(ApiResponse::error(
+                "Only admins can export every repository",
+            )),
+        );
+    }
+    if passphrase
+        .0
+        .as_ref()
+        .is_some_and(|p| p.chars().count() < MIN_PASSPHRASE_LENGTH)
+    {
+        return bad_request(&format!(
+            "The passphrase must be at least {} characters long",
+            MIN_PASSPHRASE_LENGTH
+        ));
+    }
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let query = if all {
+        repository::table.into_boxed()
+    } else {
+        accessible_repositories(user.0.id, TeamRole::Maintainer)
+    };
+    let repositories = match query
+        .order(repository::name.asc())
+        .load::<RepositoryModel>(connection)
+    {
+        Ok(repositories) => repositories,
+        Err(_e) => return database_error(),
+    };
+
+    let user_ids: Vec<Uuid> = repositories.iter().filter_map(|r| r.user_id).collect();
+    let team_ids: Vec<Uuid> = repositories.iter().filter_map(|r| r.team_id).collect();
+    let usernames = user::table
+        .filter(user::id.eq_any(&user_ids))
+        .select((user::id, user::username))
+        .load::<(Uuid, String)>(connection);
+    let team_names = team::table
+        .filter(team::id.eq_any(&team_ids))
+        .select((team::id, team::name))
+        .load::<(Uuid, String)>(connection);
+    let (Ok(usernames), Ok(team_names)) = (usernames, team_names) else {
+        return database_error();
+    };
+    let usernames: HashMap<Uuid, String> = usernames.into_iter().collect();
+    let team_names: HashMap<Uuid, String> = team_names.into_iter().collect();
+
+    let settings = if all {
+        match load_admin_settings(connection) {
+            Ok(settings) => Some(ExportedSettings {
+                log_retention_days: settings.log_retention_days,
+                log_max_rows: settings.log_max_rows,
+                stale_after_seconds: settings.stale_after_seconds,
+            }),
+            Err(_e) => return database_error(),
+        }
+    } else {
+        None
+    };
+
+    let mut encryption = None;
+    let mut key = None;
+    if let Some(passphrase) = &passphrase.0 {
+        let salt: [u8; 16] = rng().random();
+        let params = Params::default();
+        match derive_key(passphrase, &salt, params.clone()) {
+            Ok(derived) => key = Some(derived),
+            Err(_e) => {
+                return Custom(
+                    Status::InternalServerError,
+                    Json(ApiResponse::error("Failed to derive the export key")),
+                );
+            }
+        }
+        encryption = Some(ExportEncryption {
+            algorithm: ENCRYPTION_ALGORITHM.to_string(),
+            kdf: ENCRYPTION_KDF.to_string(),
+            salt: STANDARD.encode(salt),
+            memory_cost: params.m_cost(),
+            time_cost: params.t_cost(),
+            parallelism: params.p_cost(),
+        });
+    }
+    // re-encrypted from the master key to the passphrase, plain keys never leave the server
+    let keys = MasterKeys::from_config(&config.keys);
+    let mut exported_keys = Vec::with_capacity(repositories.len());
+    for r in &repositories {
+        let encrypt = |secret: Option<&str>| -> Result<Option<String>, MasterKeyError> {
+            match (&key, secret) {
+                (Some(key), Some(secret)) => Ok(Some(encrypt_secret(key, &keys.open(secret)?))),
+                _ => Ok(None),
+            }
+        };
+        match (
+            encrypt(r.git_source_secret_key.as_deref()),
+            encrypt(r.git_target_secret_key.as_deref()),
+        ) {
+            (Ok(source_key), Ok(target_key)) => exported_keys.push((source_key, target_key)),
+            (Err(e), _) | (_, Err(e)) => {
+                tracing::error!(repository_id = %r.id, error = %e, "failed to export repository keys");
+                return Custom(
+                    Status::InternalServerError,
+                    Json(ApiResponse::error(&format!(
+                        "The keys of repository {} could not be decrypted",
+                        r.name
+                    ))),
+                );
+            }
+        }
+    }
+
+    let document = ExportDocument {
+        format: EXPORT_FORMAT.to_string(),
+        version: EXPORT_VERSION,
+        exported_at: Utc::now(),
+        encryption,
+        settings,
+        repositories: repositories
+            .iter()
+            .zip(exported_keys)
+            .map(|(r, (source_key, target_key))| ExportedRepository {
+                name: r.name.clone(),
+                url: r.url.clone(),
+                owner: r
+                    .user_id
+                    .filter(|_| r.team_id.is_none())
+                    .and_then(|id| usernames.get(&id).cloned()),
+                team: r.team_id.and_then(|id| team_names.get(&id).cloned()),
+                is_enabled: r.is_enabled,
+                git_source: r.git_source.clone(),
+                git_target: r.git_target.clone(),
+                git_clone_period_seconds: r.git_clone_period_seconds,
+                tags: r.tags.clone(),
+                ref_filters: r.ref_filters.clone(),
+                log_retention_days: r.log_retention_days,
+                log_max_rows: r.log_max_rows,
+                git_source_secret_key: source_key,
+                git_target_secret_key: target_key,
+            })
+            .collect(),
+    };
+
+    record_audit_event(
+        connection,
+        Some(&user.0),
+        &client,
+        AUDIT_REPOSITORY_EXPORT,
+        None,
+        AuditChanges {
+            before: None,
+            after: Some(json!({
+                "repositories": document.repositories.len(),
+                "all": all,
+                "encrypted": document.encryption.is_some(),
+                "settings": document.settings.is_some(),
+            })),
+        },
+    );
+
+    Custom(
+        Status::Ok,
+        Json(ApiResponse::success("Repositories exported", document)),
+    )
+}
+
+/// Imports the repositories of an export. Owners are matched by username and teams by name,
+/// after `userMap` and `teamMap`. Non-admins import personal repositories as their own and
+/// team repositories only into teams they maintain. Repositories that are invalid, whose
+/// owner is unknown, or whose name is taken for that owner are reported as conflicts and
+/// skipped. Without keys in the document repositories are imported disabled.
+#[post("/import?<dry_run>", format = "application/json", data = "<form>")]
+pub fn import_repositories(
+    db: &State<DbConnection>,
+    config: &State<Config>,
+    user: AuthGuard<scope::RepositoriesWrite>,
+    client: ClientInfo,
+    dry_run: Option<bool>,
+    form: Json<ImportForm>,
+) -> Custom<Json<ApiResponse<ImportResponse>>> {
+    let dry_run = dry_run.unwrap_or(false);
+    let document = &form.document;
+
+    if document.format != EXPORT_FORMAT {
+        return bad_request("Not a gitmirrors export");
+    }
+    if document.version != EXPORT_VERSION {
+        return bad_request(&format!(
+            "Unsupported export version {}, expected {}",
+            document.version, EXPORT_VERSION
+        ));
+    }
+
+    let key = match (&document.encryption, &form.passphrase) {
+        (None, _) => {
+            if document
+                .repositories
+                .iter()
+                .any(|r| r.git_source_secret_key.is_some() || r.git_target_secret_key.is_some())
+            {
+                return bad_request("The export holds keys but no encryption settings");
+            }
+            None
+        }
+        (Some(_), None) => {
+            return bad_request("The export holds encrypted keys, a passphrase is required");
+        }
+        (Some(encryption), Some(passphrase)) => {
+            if encryption.algorithm != ENCRYPTION_ALGORITHM || encryption.kdf != ENCRYPTION_KDF {
+                return bad_request("Unsupported export encryption");
+            }
+            let Ok(salt) = STANDARD.decode(&encryption.salt) else {
+                return bad_request("Invalid export encryption salt");
+            };
+            let Some(params) = encryption.params() else {
+                return bad_request("Unsupported export encryption parameters");
+            };
+            match derive_key(passphrase, &salt, params) {
+                Ok(key) => Some(key),
+                Err(_e) => return bad_request("Invalid export encryption salt"),
+            }
+        }
+    };
+    let keys = MasterKeys::from_config(&config.keys);
+
+    // server wide, so only admins carry them over
+    let settings = document.settings.as_ref().filter(|_| user.0.is_admin);
+    if settings.is_some_and(|s| {
+        s.log_retention_days < 0 || s.log_max_rows < 0 || s.stale_after_seconds < 0
+    }) {
+        return bad_request("Settings cannot be negative");
+    }
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let users = user::table
+        .select((user::username, user::id))
+        .load::<(String, Uuid)>(connection);
+    let teams = team::table
+        .select((team::name, team::id))
+        .load::<(String, Uuid)>(connection);
+    let taken = repository::table
+        .select((repository::user_id, repository::team_id, repository::name))
+        .load::<(Option<Uuid>, Option<Uuid>, String)>(connection);
+    let (Ok(users), Ok(teams), Ok(taken)) = (users, teams, taken) else {
+        return database_error();
+    };
+    let users: HashMap<String, Uuid> = users.into_iter().collect();
+    let teams: HashMap<String, Uuid> = teams.into_iter().collect();
+    // names are unique per owner, a team or a user
+    let mut taken: HashSet<(Option<Uuid>, Option<Uuid>, String)> = taken
+        .into_iter()
+        .map(|(user_id, team_id, name)| (user_id.filter(|_| team_id.is_none()), team_id, name))
+        .collect();
+
+    let mut maintained: HashMap<Uuid, bool> = HashMap::new();
+    let mut imported = Vec::new();
+    let mut conflicts = Vec::new();
+    let mut rows = Vec::new();
+
+    for exported in &document.repositories {
+        let team_name = exported
+            .team
+            .as_ref()
+            .map(|t| form.team_map.get(t).unwrap_or(t).clone());
+        let owner_name = match &team_name {
+            Some(_) => None,
+            None if user.0.is_admin => exported
+                .owner
+                .as_ref()
+                .map(|o| form.user_map.get(o).unwrap_or(o).clone())
+                .or_else(|| Some(user.0.username.clone())),
+            None => Some(user.0.username.clone()),
+        };
+        let mut conflict = |reason: String| {
+            conflicts.push(ImportConflict {
+                name: exported.name.clone(),
+                owner: owner_name.clone(),
+                team: team_name.clone(),
+                reason,
+            });
+        };
+
+        let (user_id, team_id) = match (&team_name, &owner_name) {
+            (Some(name), _) => {
+                let Some(&team_id) = teams.get(name) else {
+                    conflict(format!("Team {} does not exist", name));
+                    continue;
+                };
+                if !user.0.is_admin {
+                    let is_maintainer = match maintained.get(&team_id) {
+                        Some(&is_maintainer) => is_maintainer,
+                        None => match team_role(connection, team_id, user.0.id) {
+                            Ok(role) => {
+                                let is_maintainer = role == Some(TeamRole::Maintainer);
+                                maintained.insert(team_id, is_maintainer);
+                                is_maintainer
+                            }
+                            Err(_e) => return database_error(),
+                        },
+                    };
+                    if !is_maintainer {
+                        conflict(format!(
+                            "Only maintainers of team {} can import into it",
+                            name
+                        ));
+                        continue;
+                    }
+                }
+                (None, Some(team_id))
+            }
+            (None, Some(name)) => {
+                let Some(&user_id) = users.get(name) else {
+                    conflict(format!("User {} does not exist", name));
+                    continue;
+                };
+                (Some(user_id), None)
+            }
+            (None, None) => unreachable!("personal repositories always have an owner"),
+        };
+
+        let (tags, ref_filters) = match validate_exported(exported, &config.repository) {
+            Ok(normalized) => normalized,
+            Err(reason) => {
+                conflict(reason);
+                continue;
+            }
+        };
+
+        let decrypt = |secret: &Option<String>| match (&key, secret) {
+            (Some(key), Some(secret)) => decrypt_secret(key, secret).map(Some),
+            _ => Some(None),
+        };
+        let (Some(source_key), Some(target_key)) = (
+            decrypt(&exported.git_source_secret_key),
+            decrypt(&exported.git_target_secret_key),
+        ) else {
+            return bad_request("Wrong passphrase, or the export was altered");
+        };
+
+        if !taken.insert((user_id, team_id, exported.name.clone())) {
+            conflict("A repository with this name already exists".to_string());
+            continue;
+        }
+
+        let needs_credentials = target_key.is_none();
+        imported.push(ImportedRepository {
+            name: exported.name.clone(),
+            owner: owner_name.clone(),
+            team: team_name.clone(),
+            repository_id: None,
+            needs_credentials,
+        });
+        rows.push((
+            exported,
+            user_id,
+            team_id,
+            tags,
+            ref_filters,
+            source_key.map(|k| keys.seal(&k)),
+            target_key.map(|k| keys.seal(&k)),
+        ));
+    }
+
+    if dry_run {
+        return Custom(
+            Status::Ok,
+            Json(ApiResponse::success(
+                "Import planned",
+                ImportResponse {
+                    dry_run,
+                    settings_imported: settings.is_some(),
+                    imported,
+                    conflicts,
+                },
+            )),
+        );
+    }
+
+    let settings_before = match settings
+        .map(|_| load_admin_settings(connection))
+        .transpose()
+    {
+        Ok(before) => before,
+        Err(_e) => return database_error(),
+    };
+
+    let result = connection.transaction::<_, diesel::result::Error, _>(|connection| {
+        let mut settings_after = None;
+        if let Some(settings) = settings {
+            set_int_setting(
+                connection,
+                SETTING_LOG_RETENTION_DAYS,
+                settings.log_retention_days,
+            )?;
+            set_int_setting(connection, SETTING_LOG_MAX_ROWS, settings.log_max_rows)?;
+            set_int_setting(
+                connection,
+                SETTING_STALE_AFTER_SECONDS,
+                settings.stale_after_seconds,
+            )?;
+            settings_after = Some(load_admin_settings(connection)?);
+        }
+
+        let mut inserted = Vec::with_capacity(rows.len());
+        for (exported, user_id, team_id, tags, ref_filters, source_key, target_key) in &rows {
+            let new_repo = InsertableRepositoryModel {
+                user_id: *user_id,
+                team_id: *team_id,
+                name: &exported.name,
+                url: exported.url.as_deref().filter(|u| !u.is_empty()),
+                is_enabled: exported.is_enabled && target_key.is_some(),
+                git_source: &exported.git_source,
+                git_source_secret_key: source_key.as_deref(),
+                git_target: &exported.git_target,
+                git_target_secret_key: target_key.as_deref(),
+                git_clone_period_seconds: exported.git_clone_period_seconds,
+                tags: tags.clone(),
+                log_retention_days: exported.log_retention_days,
+                log_max_rows: exported.log_max_rows,
+                ref_filters: ref_filters.clone(),
+            };
+            inserted.push(
+                diesel::insert_into(repository::table)
+                    .values(&new_repo)
+                    .get_result::<RepositoryModel>(connection)?,
+            );
+        }
+        Ok((settings_after, inserted))
+    });
+
+    let (settings_after, inserted) = match result {
+        Ok(result) => result,
+        Err(_e) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Failed to import repositories")),
+            );
+        }
+    };
+
+    if let Some(after) = &settings_after {
+        record_audit_event(
+            connection,
+            Some(&user.0),
+            &client,
+            AUDIT_SETTINGS_UPDATE,
+            Some(AuditTarget {
+                target_type: AUDIT_TARGET_SETTINGS,
+                target_id: None,
+            }),
+            AuditChanges::diff(settings_before.as_ref(), Some(after)),
+        );
+    }
+    for (entry, repo) in imported.iter_mut().zip(&inserted) {
+        entry.repository_id = Some(repo.id);
+        record_audit_event(
+            connection,
+            Some(&user.0),
+            &client,
+            AUDIT_REPOSITORY_IMPORT,
+            Some(AuditTarget::new(AUDIT_TARGET_REPOSITORY, repo.id)),
+            AuditChanges::diff(None, Some(repo)),
+        );
+    }
+
+    Custom(
+        Status::Ok,
+        Json(ApiResponse::success(
+            "Repositories imported",
+            ImportResponse {
+                dry_run,
+                settings_imported: settings.is_some(),
+                imported,
+                conflicts,
+            },
+        )),
+    )
+}
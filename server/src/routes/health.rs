@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+
+use crate::config::{Config, HealthConfig};
+use crate::db::DbConnection;
+use crate::health::{HealthReport, WorkerHeartbeat, check_liveness, check_readiness};
+use crate::utils::response::ApiResponse;
+
+fn report_response(message: &str, report: HealthReport) -> Custom<Json<ApiResponse<HealthReport>>> {
+    // failing reports keep their components, so probes and humans can see which one failed
+    report.log_failures();
+    let status = match report.is_healthy() {
+        true => Status::Ok,
+        false => Status::ServiceUnavailable,
+    };
+
+    Custom(
+        status,
+        Json(ApiResponse {
+            success: report.is_healthy(),
+            message: message.to_string(),
+            data: Some(report),
+        }),
+    )
+}
+
+/// Kept for existing uptime checks, only tells that the server answers.
+#[get("/health")]
+pub fn health() -> &'static str {
+    "OK"
+}
+
+#[get("/health/live")]
+pub fn health_live(
+    config: &State<HealthConfig>,
+    heartbeat: &State<Arc<WorkerHeartbeat>>,
+) -> Custom<Json<ApiResponse<HealthReport>>> {
+    let report = check_liveness(config, heartbeat);
+    report_response("Liveness checked", report)
+}
+
+#[get("/health/ready")]
+pub async fn health_ready(
+    db: &State<DbConnection>,
+    app_config: &State<Config>,
+    config: &State<HealthConfig>,
+    heartbeat: &State<Arc<WorkerHeartbeat>>,
+) -> Custom<Json<ApiResponse<HealthReport>>> {
+    let report =
+        check_readiness(config, heartbeat, db, &app_config.storage.repositories_path).await;
+    report_response("Readiness checked", report)
+}
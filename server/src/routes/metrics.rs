@@ -0,0 +1,30 @@
+use std::sync::Arc;
+
+use rocket::State;
+use rocket::http::{ContentType, Status};
+
+use crate::config::Config;
+use crate::db::DbConnection;
+use crate::metrics::Metrics;
+use crate::middlewares::metrics::MetricsAuth;
+
+/// Prometheus scrape endpoint, mounted at `/metrics` outside of the API.
+#[get("/metrics")]
+pub async fn get_metrics(
+    db: &State<DbConnection>,
+    metrics: &State<Arc<Metrics>>,
+    config: &State<Config>,
+    _auth: MetricsAuth,
+) -> Result<(ContentType, String), Status> {
+    match metrics.render(db, &config.storage.repositories_path).await {
+        Ok(body) => Ok((
+            ContentType::new("application", "openmetrics-text")
+                .with_params([("version", "1.0.0"), ("charset", "utf-8")]),
+            body,
+        )),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to render metrics");
+            Err(Status::InternalServerError)
+        }
+    }
+}
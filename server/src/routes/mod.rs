@@ -1,18 +1,83 @@
+pub mod admin;
 pub mod aggregate;
+pub mod apply;
+pub mod export;
+pub mod health;
+pub mod metrics;
+pub mod notification;
+pub mod oidc;
+pub mod provider;
 pub mod repository;
+pub mod setup;
+pub mod team;
+pub mod token;
+pub mod totp;
+pub mod transfer;
 pub mod user;
+pub mod webhook;
 
 use rocket::Route;
 pub fn routes() -> Vec<Route> {
     routes![
+        health::health,
+        health::health_live,
+        health::health_ready,
+        setup::get_setup_status,
+        setup::setup_required,
+        setup::complete_setup,
         user::login,
         user::me,
         user::change_password,
+        user::logout,
+        user::get_sessions,
+        user::revoke_session,
+        totp::login_totp,
+        oidc::oidc_login,
+        oidc::oidc_callback,
+        totp::enroll_totp,
+        totp::confirm_totp,
+        totp::regenerate_totp_recovery_codes,
+        totp::disable_totp,
+        token::create_api_token,
+        token::get_api_tokens,
+        token::revoke_api_token,
         repository::get_all_repositories,
         repository::add_repository,
         repository::get_repository_by_id,
         repository::delete_repository_by_id,
         repository::get_repository_logs_by_id,
-        aggregate::get_dashboard_data
+        repository::get_repository_log_feed,
+        repository::update_repository,
+        apply::apply_repositories,
+        export::export_repositories,
+        export::import_repositories,
+        provider::import_provider_repositories,
+        repository::update_repository_retention,
+        repository::sync_repository_by_id,
+        transfer::request_repository_transfer,
+        transfer::get_repository_transfers,
+        transfer::accept_repository_transfer,
+        transfer::decline_repository_transfer,
+        transfer::cancel_repository_transfer,
+        webhook::receive_webhook,
+        webhook::rotate_repository_webhook,
+        webhook::delete_repository_webhook,
+        webhook::get_webhook_deliveries,
+        notification::create_notification_channel,
+        notification::get_notification_channels,
+        notification::delete_notification_channel,
+        notification::test_notification_channel,
+        notification::get_notification_deliveries,
+        aggregate::get_dashboard_data,
+        team::create_team,
+        team::get_teams,
+        team::get_team_by_id,
+        team::delete_team_by_id,
+        team::set_team_member,
+        team::remove_team_member,
+        admin::get_admin_settings,
+        admin::update_admin_settings,
+        admin::get_login_attempts,
+        admin::get_audit_events
     ]
 }
@@ -0,0 +1,429 @@
+use std::sync::Arc;
+
+use diesel::prelude::*;
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::{Validate, ValidateEmail};
+
+use crate::db::DbConnection;
+use crate::middlewares::auth::{AuthGuard, scope};
+use crate::middlewares::client::ClientInfo;
+use crate::models::{
+    InsertableNotificationChannelModel, NotificationChannelModel, NotificationDeliveryModel,
+};
+use crate::notify::{
+    CHANNEL_EMAIL, CHANNEL_SLACK, CHANNEL_WEBHOOK, NotificationEvent, Notifier, record_delivery,
+};
+use crate::schema::{notification_channel, notification_delivery, team_member};
+use crate::utils::access::{TeamRole, team_role};
+use crate::utils::audit::{
+    AUDIT_NOTIFICATION_CHANNEL_CREATE, AUDIT_NOTIFICATION_CHANNEL_DELETE,
+    AUDIT_TARGET_NOTIFICATION_CHANNEL, AuditChanges, AuditTarget, MASKED_VALUE, record_audit_event,
+};
+use crate::utils::pagination::page_limit;
+use crate::utils::response::ApiResponse;
+
+#[derive(Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateNotificationChannelForm<'r> {
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Channel name should be between 1 and 100 characters long"
+    ))]
+    name: &'r str,
+    kind: &'r str,
+    #[validate(length(min = 1, max = 1024))]
+    target: &'r str,
+    #[validate(length(
+        min = 16,
+        max = 128,
+        message = "Secret should be between 16 and 128 characters long"
+    ))]
+    secret: Option<&'r str>,
+    /// Creates a team channel, notified for the team's repositories.
+    team_id: Option<Uuid>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetNotificationChannelsResponse {
+    pub notification_channels: Vec<NotificationChannelModel>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetNotificationDeliveriesResponse {
+    pub notification_deliveries: Vec<NotificationDeliveryModel>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteNotificationChannelResponse {
+    pub id: Uuid,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestNotificationChannelResponse {
+    pub delivery: NotificationDeliveryModel,
+}
+
+/// Slack and Mattermost webhook URLs let anyone post to the channel, so like secrets they are
+/// never returned or written to the audit log.
+fn masked_channel(mut channel: NotificationChannelModel) -> NotificationChannelModel {
+    if channel.kind == CHANNEL_SLACK {
+        channel.target = MASKED_VALUE.to_string();
+    }
+    channel
+}
+
+/// Loads a channel the user may manage: their own, or one of a team they maintain.
+/// With `TeamRole::Viewer`, any member of the team may read it.
+fn find_channel_with_role<T>(
+    connection: &mut PgConnection,
+    user_id: Uuid,
+    channel_id: &str,
+    role: TeamRole,
+) -> Result<NotificationChannelModel, Custom<Json<ApiResponse<T>>>> {
+    let parsed_id = Uuid::parse_str(channel_id).map_err(|_| {
+        Custom(
+            Status::BadRequest,
+            Json(ApiResponse::error("Invalid notification channel ID")),
+        )
+    })?;
+
+    let not_found = || {
+        Custom(
+            Status::NotFound,
+            Json(ApiResponse::error("Notification channel not found")),
+        )
+    };
+    let internal_error = || {
+        Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to fetch notification channel")),
+        )
+    };
+
+    let channel = notification_channel::table
+        .filter(notification_channel::id.eq(parsed_id))
+        .select(NotificationChannelModel::as_select())
+        .first::<NotificationChannelModel>(connection)
+        .optional()
+        .map_err(|_| internal_error())?
+        .ok_or_else(not_found)?;
+
+    if channel.user_id == Some(user_id) {
+        return Ok(channel);
+    }
+
+    let member_role = match channel.team_id {
+        Some(team_id) => team_role(connection, team_id, user_id).map_err(|_| internal_error())?,
+        None => None,
+    };
+    match member_role {
+        Some(member_role) if member_role >= role => Ok(channel),
+        Some(_) => Err(Custom(
+            Status::Forbidden,
+            Json(ApiResponse::error(
+                "Only team maintainers can manage team notification channels",
+            )),
+        )),
+        // hide channels of other users and teams
+        None => Err(not_found()),
+    }
+}
+
+#[post("/notification-channel", format = "application/json", data = "<form>")]
+pub fn create_notification_channel(
+    db: &State<DbConnection>,
+    notifier: &State<Arc<Notifier>>,
+    user: AuthGuard,
+    client: ClientInfo,
+    form: Json<CreateNotificationChannelForm<'_>>,
+) -> Custom<Json<ApiResponse<NotificationChannelModel>>> {
+    if let Err(_e) = form.validate() {
+        return Custom(Status::BadRequest, Json(ApiResponse::error("Bad request")));
+    }
+
+    let target_valid = match form.kind {
+        CHANNEL_WEBHOOK | CHANNEL_SLACK => notifier.accepts_url(form.target),
+        CHANNEL_EMAIL => {
+            if !notifier.email_enabled() {
+                return Custom(
+                    Status::BadRequest,
+                    Json(ApiResponse::error("Email notifications are not configured")),
+                );
+            }
+            form.target.validate_email()
+        }
+        _ => {
+            return Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error(
+                    "Channel kind must be webhook, slack or email",
+                )),
+            );
+        }
+    };
+    if !target_valid {
+        return Custom(
+            Status::BadRequest,
+            Json(ApiResponse::error(match form.kind {
+                CHANNEL_EMAIL => "Target must be an email address",
+                _ => "Target must be an http or https URL of a public host",
+            })),
+        );
+    }
+    if form.secret.is_some() && form.kind != CHANNEL_WEBHOOK {
+        return Custom(
+            Status::BadRequest,
+            Json(ApiResponse::error(
+                "Only webhook channels can have a secret",
+            )),
+        );
+    }
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    if let Some(team_id) = form.team_id {
+        match team_role(connection, team_id, user.0.id) {
+            Ok(Some(TeamRole::Maintainer)) => {}
+            Ok(Some(_)) => {
+                return Custom(
+                    Status::Forbidden,
+                    Json(ApiResponse::error(
+                        "Only team maintainers can add team notification channels",
+                    )),
+                );
+            }
+            Ok(None) => {
+                return Custom(Status::NotFound, Json(ApiResponse::error("Team not found")));
+            }
+            Err(_e) => {
+                return Custom(
+                    Status::InternalServerError,
+                    Json(ApiResponse::error("Failed to fetch team")),
+                );
+            }
+        }
+    }
+
+    match diesel::insert_into(notification_channel::table)
+        .values(&InsertableNotificationChannelModel {
+            user_id: match form.team_id {
+                Some(_) => None,
+                None => Some(user.0.id),
+            },
+            team_id: form.team_id,
+            name: form.name,
+            kind: form.kind,
+            target: form.target,
+            secret: form.secret,
+        })
+        .returning(NotificationChannelModel::as_returning())
+        .get_result::<NotificationChannelModel>(connection)
+    {
+        Ok(created) => {
+            let created = masked_channel(created);
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_NOTIFICATION_CHANNEL_CREATE,
+                Some(AuditTarget::new(
+                    AUDIT_TARGET_NOTIFICATION_CHANNEL,
+                    created.id,
+                )),
+                AuditChanges::diff(None, Some(&created)),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "Notification channel created successfully",
+                    created,
+                )),
+            )
+        }
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to create notification channel")),
+        ),
+    }
+}
+
+/// The user's own channels and those of every team they belong to.
+#[get("/notification-channel")]
+pub fn get_notification_channels(
+    db: &State<DbConnection>,
+    user: AuthGuard<scope::Read>,
+) -> Custom<Json<ApiResponse<GetNotificationChannelsResponse>>> {
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let team_ids = team_member::table
+        .filter(team_member::user_id.eq(user.0.id))
+        .select(team_member::team_id.nullable());
+
+    match notification_channel::table
+        .filter(
+            notification_channel::user_id
+                .eq(user.0.id)
+                .or(notification_channel::team_id.eq_any(team_ids)),
+        )
+        .order(notification_channel::created_at.asc())
+        .select(NotificationChannelModel::as_select())
+        .load::<NotificationChannelModel>(connection)
+    {
+        Ok(notification_channels) => Custom(
+            Status::Ok,
+            Json(ApiResponse::success(
+                "Notification channels fetched successfully",
+                GetNotificationChannelsResponse {
+                    notification_channels: notification_channels
+                        .into_iter()
+                        .map(masked_channel)
+                        .collect(),
+                },
+            )),
+        ),
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to fetch notification channels")),
+        ),
+    }
+}
+
+#[delete("/notification-channel/<channel_id>")]
+pub fn delete_notification_channel(
+    db: &State<DbConnection>,
+    user: AuthGuard,
+    client: ClientInfo,
+    channel_id: &str,
+) -> Custom<Json<ApiResponse<DeleteNotificationChannelResponse>>> {
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let channel =
+        match find_channel_with_role(connection, user.0.id, channel_id, TeamRole::Maintainer) {
+            Ok(channel) => channel,
+            Err(response) => return response,
+        };
+
+    match diesel::delete(
+        notification_channel::table.filter(notification_channel::id.eq(channel.id)),
+    )
+    .execute(connection)
+    {
+        Ok(_) => {
+            let channel = masked_channel(channel);
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_NOTIFICATION_CHANNEL_DELETE,
+                Some(AuditTarget::new(
+                    AUDIT_TARGET_NOTIFICATION_CHANNEL,
+                    channel.id,
+                )),
+                AuditChanges::diff(Some(&channel), None),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "Notification channel deleted successfully",
+                    DeleteNotificationChannelResponse { id: channel.id },
+                )),
+            )
+        }
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to delete notification channel")),
+        ),
+    }
+}
+
+/// Sends a test notification right away and returns the recorded delivery.
+#[post("/notification-channel/<channel_id>/test")]
+pub async fn test_notification_channel(
+    db: &State<DbConnection>,
+    notifier: &State<Arc<Notifier>>,
+    user: AuthGuard,
+    channel_id: &str,
+) -> Custom<Json<ApiResponse<TestNotificationChannelResponse>>> {
+    let channel = {
+        let connection = &mut db.get().expect("Failed to get DB Connection");
+        match find_channel_with_role(connection, user.0.id, channel_id, TeamRole::Maintainer) {
+            Ok(channel) => channel,
+            Err(response) => return response,
+        }
+    };
+
+    let result = notifier.send(&channel, NotificationEvent::Test, None).await;
+    match record_delivery(db, channel.id, None, NotificationEvent::Test, &result).await {
+        Ok(delivery) if delivery.succeeded => Custom(
+            Status::Ok,
+            Json(ApiResponse::success(
+                "Test notification sent successfully",
+                TestNotificationChannelResponse { delivery },
+            )),
+        ),
+        Ok(delivery) => Custom(
+            Status::BadGateway,
+            Json(ApiResponse::error(&format!(
+                "Test notification failed: {}",
+                delivery.error.unwrap_or_default()
+            ))),
+        ),
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to record notification delivery")),
+        ),
+    }
+}
+
+/// Recent deliveries of a channel, newest first.
+#[get("/notification-channel/<channel_id>/deliveries?<limit>")]
+pub fn get_notification_deliveries(
+    db: &State<DbConnection>,
+    user: AuthGuard<scope::Read>,
+    channel_id: &str,
+    limit: Option<i64>,
+) -> Custom<Json<ApiResponse<GetNotificationDeliveriesResponse>>> {
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let channel = match find_channel_with_role(connection, user.0.id, channel_id, TeamRole::Viewer)
+    {
+        Ok(channel) => channel,
+        Err(response) => return response,
+    };
+
+    match notification_delivery::table
+        .filter(notification_delivery::channel_id.eq(channel.id))
+        .order(notification_delivery::created_at.desc())
+        .limit(page_limit(limit))
+        .select(NotificationDeliveryModel::as_select())
+        .load::<NotificationDeliveryModel>(connection)
+    {
+        Ok(notification_deliveries) => Custom(
+            Status::Ok,
+            Json(ApiResponse::success(
+                "Notification deliveries fetched successfully",
+                GetNotificationDeliveriesResponse {
+                    notification_deliveries,
+                },
+            )),
+        ),
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error(
+                "Failed to fetch notification deliveries",
+            )),
+        ),
+    }
+}
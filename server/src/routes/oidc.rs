@@ -0,0 +1,305 @@
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use rocket::State;
+use rocket::http::{Cookie, CookieJar, RawStr, SameSite, Status};
+use rocket::response::Redirect;
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+
+use crate::auth::oidc::{OidcIdentity, OidcProvider};
+use crate::config::Config;
+use crate::db::DbConnection;
+use crate::middlewares::client::ClientInfo;
+use crate::models::{InsertableOidcLoginStateModel, OidcLoginStateModel, UserModel};
+use crate::routes::totp::create_login_challenge;
+use crate::schema::{oidc_login_state, user};
+use crate::utils::audit::{
+    AUDIT_TARGET_USER, AUDIT_USER_LOGIN, AuditChanges, AuditTarget, record_audit_event,
+};
+use crate::utils::crypto::generate_random_string;
+use crate::utils::login_throttle::{FAILURE_DISABLED, record_login_attempt};
+use crate::utils::response::ApiResponse;
+use crate::utils::session::{create_session, set_session_cookie};
+
+const OIDC_STATE_COOKIE_NAME: &str = "gitmirrors_oidc_state";
+const OIDC_STATE_TTL_SECONDS: i64 = 10 * 60;
+
+fn oidc_provider(
+    provider: &State<Option<OidcProvider>>,
+) -> Result<&OidcProvider, Custom<Json<ApiResponse<()>>>> {
+    provider.inner().as_ref().ok_or_else(|| {
+        Custom(
+            Status::NotFound,
+            Json(ApiResponse::error("OIDC login is not configured")),
+        )
+    })
+}
+
+/// Hands the challenge to the web client in the fragment, which is never sent to a server.
+fn redirect_with_challenge(provider: &OidcProvider, challenge_token: &str) -> Redirect {
+    Redirect::to(format!(
+        "{}#oidcChallenge={}",
+        provider.config.post_login_redirect_url, challenge_token
+    ))
+}
+
+fn redirect_with_error(provider: &OidcProvider, message: &str) -> Redirect {
+    let target = &provider.config.post_login_redirect_url;
+    let separator = if target.contains('?') { '&' } else { '?' };
+
+    Redirect::to(format!(
+        "{}{}oidcError={}",
+        target,
+        separator,
+        RawStr::new(message).percent_encode()
+    ))
+}
+
+/// Users created from an identity provider, whose admin flag follows the groups claim.
+fn is_oidc_account(user: &UserModel) -> bool {
+    user.password_hash.is_none() && user.ldap_dn.is_none()
+}
+
+/// Finds the user linked to the identity by subject, then by the username claim, provisioning a
+/// new user if allowed. Existing accounts are only linked when the provider verified the
+/// claim, so an identity cannot take over a local or directory account that happens to share
+/// the name.
+fn find_or_provision_user(
+    connection: &mut PgConnection,
+    provider: &OidcProvider,
+    identity: &OidcIdentity,
+) -> Result<UserModel, &'static str> {
+    if identity.username.len() < 3 || identity.username.len() > 32 {
+        return Err("Username claim must be between 3 and 32 characters long");
+    }
+
+    connection
+        .transaction::<_, diesel::result::Error, _>(|connection| {
+            let by_subject = user::table
+                .filter(user::oidc_subject.eq(&identity.subject))
+                .select(UserModel::as_select())
+                .first::<UserModel>(connection)
+                .optional()?;
+
+            let found = match by_subject {
+                Some(found) => Some(found),
+                None => {
+                    let by_username = user::table
+                        .filter(user::username.eq(&identity.username))
+                        .select(UserModel::as_select())
+                        .first::<UserModel>(connection)
+                        .optional()?;
+
+                    match by_username {
+                        Some(found) if found.oidc_subject.is_some() => {
+                            return Ok(Err("Account is linked to another identity"));
+                        }
+                        Some(found) if identity.username_verified => Some(
+                            diesel::update(user::table.filter(user::id.eq(found.id)))
+                                .set(user::oidc_subject.eq(&identity.subject))
+                                .returning(UserModel::as_returning())
+                                .get_result::<UserModel>(connection)?,
+                        ),
+                        Some(_) => {
+                            return Ok(Err("An account with this username already exists"));
+                        }
+                        None => None,
+                    }
+                }
+            };
+
+            let dbusr = match found {
+                Some(found) if is_oidc_account(&found) => {
+                    diesel::update(user::table.filter(user::id.eq(found.id)))
+                        .set(user::is_admin.eq(identity.is_admin.unwrap_or(found.is_admin)))
+                        .returning(UserModel::as_returning())
+                        .get_result::<UserModel>(connection)?
+                }
+                Some(found) => found,
+                None => {
+                    if !provider.config.auto_provision {
+                        return Ok(Err("No account exists for this identity"));
+                    }
+
+                    diesel::insert_into(user::table)
+                        .values((
+                            user::username.eq(&identity.username),
+                            user::oidc_subject.eq(&identity.subject),
+                            user::is_admin.eq(identity.is_admin.unwrap_or(false)),
+                        ))
+                        .returning(UserModel::as_returning())
+                        .get_result::<UserModel>(connection)?
+                }
+            };
+
+            Ok(Ok(dbusr))
+        })
+        .unwrap_or(Err("Database error"))
+}
+
+#[get("/auth/oidc/login")]
+pub async fn oidc_login(
+    db: &State<DbConnection>,
+    provider: &State<Option<OidcProvider>>,
+    cookie_jar: &CookieJar<'_>,
+) -> Result<Redirect, Custom<Json<ApiResponse<()>>>> {
+    let provider = oidc_provider(provider)?;
+
+    let state = generate_random_string(48);
+    let nonce = generate_random_string(48);
+    let code_verifier = generate_random_string(64);
+
+    {
+        let connection = &mut db.get().expect("Failed to get DB Connection");
+
+        let insert_result = diesel::delete(
+            oidc_login_state::table.filter(oidc_login_state::expires_at.le(Utc::now())),
+        )
+        .execute(connection)
+        .and_then(|_| {
+            diesel::insert_into(oidc_login_state::table)
+                .values(&InsertableOidcLoginStateModel {
+                    state: &state,
+                    nonce: &nonce,
+                    code_verifier: &code_verifier,
+                    expires_at: Utc::now() + Duration::seconds(OIDC_STATE_TTL_SECONDS),
+                })
+                .execute(connection)
+        });
+
+        if insert_result.is_err() {
+            return Err(Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Failed to start OIDC login")),
+            ));
+        }
+    }
+
+    let authorization_url = match provider
+        .authorization_url(&state, &nonce, &code_verifier)
+        .await
+    {
+        Ok(url) => url,
+        Err(e) => {
+            tracing::warn!(error = %e, "OIDC discovery failed");
+            return Err(Custom(
+                Status::BadGateway,
+                Json(ApiResponse::error("Identity provider is unavailable")),
+            ));
+        }
+    };
+
+    // binds the callback to this browser, Lax so it survives the redirect back from the provider
+    cookie_jar.add(
+        Cookie::build((OIDC_STATE_COOKIE_NAME, state))
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .path("/")
+            .max_age(rocket::time::Duration::seconds(OIDC_STATE_TTL_SECONDS))
+            .build(),
+    );
+
+    Ok(Redirect::to(authorization_url))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[get("/auth/oidc/callback?<code>&<state>&<error>")]
+pub async fn oidc_callback(
+    db: &State<DbConnection>,
+    provider: &State<Option<OidcProvider>>,
+    config: &State<Config>,
+    cookie_jar: &CookieJar<'_>,
+    client: ClientInfo,
+    code: Option<&str>,
+    state: Option<&str>,
+    error: Option<&str>,
+) -> Result<Redirect, Custom<Json<ApiResponse<()>>>> {
+    let provider = oidc_provider(provider)?;
+
+    let cookie_state = cookie_jar
+        .get(OIDC_STATE_COOKIE_NAME)
+        .map(|c| c.value().to_string());
+    cookie_jar.remove(Cookie::build(OIDC_STATE_COOKIE_NAME).path("/").build());
+
+    if let Some(error) = error {
+        return Ok(redirect_with_error(provider, error));
+    }
+
+    let (code, state) = match (code, state) {
+        (Some(code), Some(state)) if cookie_state.as_deref() == Some(state) => (code, state),
+        _ => return Ok(redirect_with_error(provider, "Invalid OIDC login state")),
+    };
+
+    let login_state = {
+        let connection = &mut db.get().expect("Failed to get DB Connection");
+
+        diesel::delete(
+            oidc_login_state::table
+                .filter(oidc_login_state::state.eq(state))
+                .filter(oidc_login_state::expires_at.gt(Utc::now())),
+        )
+        .returning(OidcLoginStateModel::as_returning())
+        .get_result::<OidcLoginStateModel>(connection)
+        .optional()
+    };
+
+    let login_state = match login_state {
+        Ok(Some(s)) => s,
+        Ok(None) => return Ok(redirect_with_error(provider, "OIDC login expired")),
+        Err(_e) => return Ok(redirect_with_error(provider, "Database error")),
+    };
+
+    let identity = match provider
+        .exchange_code(code, &login_state.code_verifier, &login_state.nonce)
+        .await
+    {
+        Ok(identity) => identity,
+        Err(e) => {
+            tracing::warn!(error = %e, "OIDC code exchange failed");
+            return Ok(redirect_with_error(provider, "OIDC login failed"));
+        }
+    };
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let dbusr = match find_or_provision_user(connection, provider, &identity) {
+        Ok(dbusr) => dbusr,
+        Err(message) => return Ok(redirect_with_error(provider, message)),
+    };
+
+    if dbusr.disabled_at.is_some() {
+        let _ = record_login_attempt(connection, &dbusr.username, &client, Some(FAILURE_DISABLED));
+        return Ok(redirect_with_error(provider, "This account is disabled"));
+    }
+
+    // like a password login, the session waits for the second factor
+    if dbusr.totp_enabled {
+        return match create_login_challenge(connection, dbusr.id) {
+            Ok(challenge_token) => Ok(redirect_with_challenge(provider, &challenge_token)),
+            Err(_e) => Ok(redirect_with_error(
+                provider,
+                "Failed to create login challenge",
+            )),
+        };
+    }
+
+    let _ = record_login_attempt(connection, &dbusr.username, &client, None);
+
+    match create_session(connection, dbusr.id, &client, &config.session) {
+        Ok(token) => set_session_cookie(cookie_jar, token, &config.session),
+        Err(_e) => return Ok(redirect_with_error(provider, "Failed to create session")),
+    }
+
+    record_audit_event(
+        connection,
+        Some(&dbusr),
+        &client,
+        AUDIT_USER_LOGIN,
+        Some(AuditTarget::new(AUDIT_TARGET_USER, dbusr.id)),
+        AuditChanges::none(),
+    );
+
+    Ok(Redirect::to(
+        provider.config.post_login_redirect_url.clone(),
+    ))
+}
@@ -0,0 +1,373 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use diesel::prelude::*;
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::config::Config;
+use crate::db::DbConnection;
+use crate::middlewares::auth::{AuthGuard, scope};
+use crate::middlewares::client::ClientInfo;
+use crate::models::{InsertableRepositoryModel, RepositoryModel};
+use crate::provider::{
+    Provider, ProviderClient, VISIBILITY_INTERNAL, VISIBILITY_PRIVATE, VISIBILITY_PUBLIC,
+    matches_pattern,
+};
+use crate::routes::repository::normalize_tags;
+use crate::schema::repository;
+use crate::utils::access::{TeamRole, team_role};
+use crate::utils::audit::{
+    AUDIT_REPOSITORY_CREATE, AUDIT_TARGET_REPOSITORY, AuditChanges, AuditTarget, record_audit_event,
+};
+use crate::utils::master_key::MasterKeys;
+use crate::utils::response::ApiResponse;
+
+#[derive(Deserialize, Validate)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ProviderImportForm {
+    /// `github`, `gitlab` or `gitea`.
+    pub provider: String,
+
+    /// API of a self-hosted instance, required for Gitea.
+    #[validate(length(max = 512))]
+    pub api_url: Option<String>,
+
+    /// Organization, user or GitLab group path.
+    #[validate(length(min = 1, max = 255))]
+    pub owner: String,
+
+    /// Provider token, needed to list private repositories. Used once and never stored.
+    #[validate(length(max = 512))]
+    pub token: Option<String>,
+
+    /// Shell-style pattern repository names must match, such as `service-*`.
+    #[validate(length(min = 1, max = 200))]
+    pub name_pattern: Option<String>,
+
+    #[serde(default)]
+    pub include_archived: bool,
+
+    /// `public`, `private` or `internal`, any visibility when unset.
+    pub visibility: Option<String>,
+
+    /// Clone over SSH with `gitSourceSecretKey` instead of HTTPS.
+    #[serde(default)]
+    pub use_ssh: bool,
+
+    /// Target of each mirror with `{owner}` and `{name}` replaced, such as
+    /// `git@backup:{owner}/{name}.git`.
+    #[validate(length(min = 3, max = 512))]
+    pub target_template: String,
+
+    #[validate(length(max = 512))]
+    pub git_source_secret_key: Option<String>,
+
+    #[validate(length(min = 3))]
+    pub git_target_secret_key: String,
+
+    pub git_clone_period_seconds: u32,
+
+    /// Team that owns the mirrors instead of the current user.
+    pub team_id: Option<Uuid>,
+
+    #[serde(default)]
+    #[validate(length(max = 20))]
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderImportedRepository {
+    pub name: String,
+    pub git_source: String,
+    pub git_target: String,
+    /// Unset on dry runs.
+    pub repository_id: Option<Uuid>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedProviderRepository {
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderImportResponse {
+    pub dry_run: bool,
+    /// Repositories the provider listed.
+    pub listed: usize,
+    /// Listed repositories left out by the name, archived and visibility filters.
+    pub filtered: usize,
+    pub created: Vec<ProviderImportedRepository>,
+    /// Matching repositories that were not imported.
+    pub skipped: Vec<SkippedProviderRepository>,
+}
+
+fn bad_request<T>(message: &str) -> Custom<Json<ApiResponse<T>>> {
+    Custom(Status::BadRequest, Json(ApiResponse::error(message)))
+}
+
+fn render_target(template: &str, owner: &str, name: &str) -> String {
+    template.replace("{owner}", owner).replace("{name}", name)
+}
+
+/// Creates a mirror for every repository of a GitHub, GitLab or Gitea organization that
+/// passes the filters. Mirrors are named by their path below the imported owner, and those
+/// whose name is already taken for the new owner are skipped, so running an import again
+/// only adds what is new. With `dry_run` nothing is created.
+#[post(
+    "/repository/provider-import?<dry_run>",
+    format = "application/json",
+    data = "<form>"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn import_provider_repositories(
+    db: &State<DbConnection>,
+    config: &State<Config>,
+    providers: &State<Arc<ProviderClient>>,
+    user: AuthGuard<scope::RepositoriesWrite>,
+    client: ClientInfo,
+    dry_run: Option<bool>,
+    form: Json<ProviderImportForm>,
+) -> Custom<Json<ApiResponse<ProviderImportResponse>>> {
+    let dry_run = dry_run.unwrap_or(false);
+
+    if let Err(_e) = form.validate() {
+        return bad_request("Bad request");
+    }
+    let Some(provider) = Provider::parse(&form.provider) else {
+        return bad_request("Provider must be github, gitlab or gitea");
+    };
+    let Some(api_url) = form
+        .api_url
+        .as_deref()
+        .or(provider.default_api_url())
+        .map(String::from)
+    else {
+        return bad_request("Gitea imports require the apiUrl of the instance");
+    };
+    if !providers.accepts_url(&api_url) {
+        return bad_request("The API URL must be an http or https URL of a public host");
+    }
+    if let Some(visibility) = form.visibility.as_deref()
+        && ![VISIBILITY_PUBLIC, VISIBILITY_PRIVATE, VISIBILITY_INTERNAL].contains(&visibility)
+    {
+        return bad_request("Visibility must be public, private or internal");
+    }
+    if !form.target_template.contains("{name}") {
+        return bad_request("The target template must contain {name}");
+    }
+    let periods = &config.repository;
+    if !(periods.min_period_seconds..=periods.max_period_seconds)
+        .contains(&form.git_clone_period_seconds)
+    {
+        return bad_request(&format!(
+            "Cloning period must be between {} and {} seconds",
+            periods.min_period_seconds, periods.max_period_seconds
+        ));
+    }
+    let Some(tags) = normalize_tags(&form.tags) else {
+        return bad_request("Tags must be between 1 and 50 characters long");
+    };
+
+    // names already taken for the new owner, checked before calling the provider
+    let taken = {
+        let connection = &mut db.get().expect("Failed to get DB Connection");
+
+        if let Some(team_id) = form.team_id {
+            match team_role(connection, team_id, user.0.id) {
+                Ok(Some(TeamRole::Maintainer)) => {}
+                Ok(_) => {
+                    return Custom(
+                        Status::Forbidden,
+                        Json(ApiResponse::error(
+                            "Only team maintainers can add repositories to a team",
+                        )),
+                    );
+                }
+                Err(_e) => {
+                    return Custom(
+                        Status::InternalServerError,
+                        Json(ApiResponse::error("Database error")),
+                    );
+                }
+            }
+        }
+
+        let names = match form.team_id {
+            Some(team_id) => repository::table
+                .filter(repository::team_id.eq(team_id))
+                .select(repository::name)
+                .load::<String>(connection),
+            None => repository::table
+                .filter(repository::user_id.eq(user.0.id))
+                .filter(repository::team_id.is_null())
+                .select(repository::name)
+                .load::<String>(connection),
+        };
+        match names {
+            Ok(names) => names,
+            Err(_e) => {
+                return Custom(
+                    Status::InternalServerError,
+                    Json(ApiResponse::error("Failed to fetch repositories")),
+                );
+            }
+        }
+    };
+    let mut taken: HashSet<String> = taken.into_iter().collect();
+
+    let listed = match providers
+        .list_repositories(provider, &api_url, &form.owner, form.token.as_deref())
+        .await
+    {
+        Ok(listed) => listed,
+        // the error can describe hosts on the server's network, so it is only logged
+        Err(e) => {
+            tracing::warn!(provider = provider.as_str(), api_url = %api_url, owner = %form.owner, error = %e, "listing provider repositories failed");
+            return Custom(
+                Status::BadGateway,
+                Json(ApiResponse::error(
+                    "Failed to list repositories, check the provider, owner and token",
+                )),
+            );
+        }
+    };
+
+    let mut response = ProviderImportResponse {
+        dry_run,
+        listed: listed.len(),
+        filtered: 0,
+        created: Vec::new(),
+        skipped: Vec::new(),
+    };
+    let mut new_repos = Vec::new();
+
+    for repo in &listed {
+        let wanted = (form.include_archived || !repo.archived)
+            && form
+                .visibility
+                .as_deref()
+                .is_none_or(|v| v == repo.visibility)
+            && form
+                .name_pattern
+                .as_deref()
+                .is_none_or(|p| matches_pattern(p, &repo.name));
+        if !wanted {
+            response.filtered += 1;
+            continue;
+        }
+
+        let name = repo.path_below(&form.owner);
+        let git_source = if form.use_ssh {
+            &repo.ssh_url
+        } else {
+            &repo.https_url
+        };
+        let git_target = render_target(&form.target_template, &repo.owner, &repo.name);
+        let skip_reason = if !(3..=200).contains(&name.chars().count()) {
+            Some("Name must be between 3 and 200 characters long")
+        } else if git_source.chars().count() > 512 || git_target.chars().count() > 512 {
+            Some("git Source and Target must be less than 512 characters long")
+        } else if taken.contains(&name) {
+            Some("A repository with this name already exists")
+        } else {
+            None
+        };
+        if let Some(reason) = skip_reason {
+            response.skipped.push(SkippedProviderRepository {
+                name,
+                reason: reason.to_string(),
+            });
+            continue;
+        }
+
+        taken.insert(name.clone());
+        response.created.push(ProviderImportedRepository {
+            name: name.clone(),
+            git_source: git_source.clone(),
+            git_target: git_target.clone(),
+            repository_id: None,
+        });
+        new_repos.push((repo, name, git_source, git_target));
+    }
+
+    if dry_run {
+        return Custom(
+            Status::Ok,
+            Json(ApiResponse::success("Import planned", response)),
+        );
+    }
+
+    let keys = MasterKeys::from_config(&config.keys);
+    let source_key = form
+        .git_source_secret_key
+        .as_deref()
+        .filter(|k| !k.is_empty())
+        .map(|k| keys.seal(k));
+    let target_key = keys.seal(&form.git_target_secret_key);
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+    let result = connection.transaction::<_, diesel::result::Error, _>(|connection| {
+        let mut inserted = Vec::with_capacity(new_repos.len());
+        for (repo, name, git_source, git_target) in &new_repos {
+            let new_repo = InsertableRepositoryModel {
+                user_id: Some(user.0.id).filter(|_| form.team_id.is_none()),
+                team_id: form.team_id,
+                name,
+                url: Some(repo.web_url.as_str()).filter(|u| u.chars().count() <= 512),
+                is_enabled: true,
+                git_source,
+                git_source_secret_key: source_key.as_deref(),
+                git_target,
+                git_target_secret_key: Some(&target_key),
+                git_clone_period_seconds: form.git_clone_period_seconds as i32,
+                tags: tags.clone(),
+                log_retention_days: None,
+                log_max_rows: None,
+                ref_filters: Vec::new(),
+            };
+            inserted.push(
+                diesel::insert_into(repository::table)
+                    .values(&new_repo)
+                    .get_result::<RepositoryModel>(connection)?,
+            );
+        }
+        Ok(inserted)
+    });
+
+    let inserted = match result {
+        Ok(inserted) => inserted,
+        Err(_e) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Failed to import repositories")),
+            );
+        }
+    };
+
+    for (entry, repo) in response.created.iter_mut().zip(&inserted) {
+        entry.repository_id = Some(repo.id);
+        record_audit_event(
+            connection,
+            Some(&user.0),
+            &client,
+            AUDIT_REPOSITORY_CREATE,
+            Some(AuditTarget::new(AUDIT_TARGET_REPOSITORY, repo.id)),
+            AuditChanges::diff(None, Some(repo)),
+        );
+    }
+
+    Custom(
+        Status::Ok,
+        Json(ApiResponse::success("Repositories imported", response)),
+    )
+}
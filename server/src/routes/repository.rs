@@ -1,4 +1,10 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use diesel::dsl::sql;
+use diesel::pg::Pg;
 use diesel::prelude::*;
+use diesel::sql_types::{Bool, Integer, Text};
 use rocket::State;
 use rocket::http::Status;
 use rocket::response::status::Custom;
@@ -6,34 +12,72 @@ use rocket::serde::json::Json;
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
+use crate::clone::worker::SYNC_STATUS_FAILED;
+use crate::config::Config;
 use crate::db::DbConnection;
-use crate::middlewares::auth::AuthGuard;
-use crate::models::{InsertableRepositoryModel, RepositoryLogModel, RepositoryModel};
-use crate::schema::repository;
+use crate::middlewares::auth::{AuthGuard, scope};
+use crate::middlewares::client::ClientInfo;
+use crate::models::{
+    InsertableRepositoryModel, PublicRepository, RepositoryChangeset, RepositoryLogModel,
+    RepositoryModel,
+};
+use crate::schema::{repository, repository_logs};
+use crate::utils::access::{TeamRole, accessible_repositories, repository_role, team_role};
+use crate::utils::audit::{
+    AUDIT_REPOSITORY_CREATE, AUDIT_REPOSITORY_DELETE, AUDIT_REPOSITORY_RETENTION_UPDATE,
+    AUDIT_REPOSITORY_SYNC, AUDIT_REPOSITORY_UPDATE, AUDIT_TARGET_REPOSITORY, AuditChanges,
+    AuditTarget, record_audit_event,
+};
+use crate::utils::master_key::MasterKeys;
+use crate::utils::pagination::{Cursor, page_limit, parse_time_filter};
 use crate::utils::response::ApiResponse;
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetRepositoryResponse {
-    pub repository: RepositoryModel,
+    pub repository: PublicRepository,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetRepositoryLogsResponse {
     pub repository_logs: Vec<RepositoryLogModel>,
+    /// Pass as `cursor` to fetch the next page, absent on the last page.
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RepositoryLogFeedEntry {
+    #[serde(flatten)]
+    pub log: RepositoryLogModel,
+    pub repository_name: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetRepositoryLogFeedResponse {
+    pub repository_logs: Vec<RepositoryLogFeedEntry>,
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DeleteRepositoryResponse {
-    pub repository: RepositoryModel,
+    pub repository: PublicRepository,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncRepositoryResponse {
+    pub repository: PublicRepository,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetRepositoriesResponse {
-    pub repositories: Vec<RepositoryModel>,
+    pub repositories: Vec<PublicRepository>,
+    pub total: i64,
 }
 
 #[derive(Deserialize, Validate)]
@@ -75,28 +119,350 @@ pub struct AddRepositoryForm {
     ))]
     pub git_target_secret_key: String,
 
-    #[validate(range(
-        min = 60,
-        max = 31_556_952,
-        message = "Cloning period must be between 60 seconds and 1 year"
-    ))]
+    /// Checked against the configured `[repository]` period range.
     pub git_clone_period_seconds: u32,
+
+    /// Team that owns the repository instead of the current user.
+    pub team_id: Option<uuid::Uuid>,
+
+    #[validate(length(max = 20, message = "A repository can have at most 20 tags"))]
+    pub tags: Option<Vec<String>>,
+
+    /// Refs pushed to the target, see `normalize_ref_filters`. All refs when empty.
+    #[validate(length(max = 20, message = "A repository can have at most 20 ref filters"))]
+    pub ref_filters: Option<Vec<String>>,
+
+    /// Overrides the global log retention, see `UpdateRetentionForm`.
+    #[validate(range(min = 0, message = "Log retention days cannot be negative"))]
+    pub log_retention_days: Option<i32>,
+
+    #[validate(range(min = 0, message = "Log max rows cannot be negative"))]
+    pub log_max_rows: Option<i32>,
+}
+
+/// Omitted fields keep their value. An empty `url` or `gitSourceSecretKey` clears it.
+#[derive(Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateRepositoryForm {
+    #[validate(length(
+        min = 3,
+        max = 200,
+        message = "Name length should be more than 3 characters and less than 200 characters long"
+    ))]
+    pub name: Option<String>,
+
+    #[validate(length(max = 512, message = "Url should be less than 512 characters long"))]
+    pub url: Option<String>,
+
+    #[validate(length(
+        min = 3,
+        max = 512,
+        message = "git Source should be more than 3 characters and less than 512 characters long"
+    ))]
+    pub git_source: Option<String>,
+
+    #[validate(length(
+        max = 512,
+        message = "git Source Private Key should be less than 512 characters long"
+    ))]
+    pub git_source_secret_key: Option<String>,
+
+    #[validate(length(
+        min = 3,
+        max = 512,
+        message = "git Target should be more than 3 characters and less than 512 characters long"
+    ))]
+    pub git_target: Option<String>,
+
+    #[validate(length(
+        min = 3,
+        message = "git Target Secret Key should be more than 3 characters long"
+    ))]
+    pub git_target_secret_key: Option<String>,
+
+    pub git_clone_period_seconds: Option<u32>,
+
+    pub is_enabled: Option<bool>,
+
+    #[validate(length(max = 20, message = "A repository can have at most 20 tags"))]
+    pub tags: Option<Vec<String>>,
+
+    #[validate(length(max = 20, message = "A repository can have at most 20 ref filters"))]
+    pub ref_filters: Option<Vec<String>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateRepositoryResponse {
+    pub repository: PublicRepository,
+}
+
+/// Per-repository log retention, `null` falls back to the global settings and 0 keeps logs forever.
+#[derive(Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateRetentionForm {
+    #[validate(range(min = 0, message = "Log retention days cannot be negative"))]
+    pub log_retention_days: Option<i32>,
+
+    #[validate(range(min = 0, message = "Log max rows cannot be negative"))]
+    pub log_max_rows: Option<i32>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateRetentionResponse {
+    pub repository: PublicRepository,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AddRepositoryResponse {
-    pub created_repository: RepositoryModel,
+    pub created_repository: PublicRepository,
+}
+
+const MAX_TAG_LENGTH: usize = 50;
+const MAX_REF_FILTER_LENGTH: usize = 255;
+
+pub const INVALID_REF_FILTERS_MESSAGE: &str =
+    "Ref filters must be full ref names such as refs/heads/main or refs/tags/*, with at most one *";
+
+/// Failing first, then never synced, healthy and disabled.
+const STATUS_RANK_SQL: &str = "CASE
+      WHEN NOT is_enabled THEN 3
+      WHEN last_sync_status = 'failed' THEN 0
+      WHEN last_sync_status IS NULL THEN 1
+      ELSE 2
+    END";
+
+/// Enabled repositories that missed at least one whole cloning period.
+pub const OVERDUE_SQL: &str = "(is_enabled AND coalesce(last_clone_at, created_at)
+      + 2 * (git_clone_period_seconds || ' seconds')::interval < now())";
+
+#[derive(Clone, Copy)]
+enum RepositorySort {
+    Name,
+    LastCloneAt,
+    CreatedAt,
+    Status,
+}
+
+impl RepositorySort {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "name" => Some(RepositorySort::Name),
+            "last_clone_at" => Some(RepositorySort::LastCloneAt),
+            "created_at" => Some(RepositorySort::CreatedAt),
+            "status" => Some(RepositorySort::Status),
+            _ => None,
+        }
+    }
+}
+
+/// Escapes `LIKE` wildcards so user input only matches literally.
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Trims and lowercases tags, dropping duplicates. Returns `None` for empty or overlong tags.
+pub fn normalize_tags(tags: &[String]) -> Option<Vec<String>> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if tag.is_empty() || tag.chars().count() > MAX_TAG_LENGTH {
+            return None;
+        }
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    Some(normalized)
+}
+
+/// Trims ref filters and drops duplicates. Returns `None` unless every filter is a ref
+/// pattern git accepts in a refspec, such as `refs/heads/main` or `refs/tags/v*`.
+pub fn normalize_ref_filters(filters: &[String]) -> Option<Vec<String>> {
+    let mut normalized: Vec<String> = Vec::with_capacity(filters.len());
+    for filter in filters {
+        let filter = filter.trim();
+        let valid = filter.starts_with("refs/")
+            && filter.len() <= MAX_REF_FILTER_LENGTH
+            && filter.matches('*').count() <= 1
+            && !filter.ends_with('/')
+            && !filter.ends_with(".lock")
+            && !filter.contains("..")
+            && !filter.contains("//")
+            && !filter
+                .chars()
+                .any(|c| c.is_whitespace() || c.is_control() || ":^~?[\\+".contains(c));
+        if !valid {
+            return None;
+        }
+        if !normalized.iter().any(|f| f == filter) {
+            normalized.push(filter.to_string());
+        }
+    }
+    Some(normalized)
+}
+
+/// Query parameters shared by the per-repository logs and the log feed.
+struct LogFilter {
+    types: Vec<String>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    cursor: Option<Cursor>,
+}
+
+impl LogFilter {
+    /// `types` is a comma separated list such as `error_clone_job,panic_clone_job`.
+    fn parse<T>(
+        types: Option<&str>,
+        since: Option<&str>,
+        until: Option<&str>,
+        cursor: Option<&str>,
+    ) -> Result<Self, Custom<Json<ApiResponse<T>>>> {
+        let (since, until) = match (parse_time_filter(since), parse_time_filter(until)) {
+            (Ok(since), Ok(until)) => (since, until),
+            _ => {
+                return Err(Custom(
+                    Status::BadRequest,
+                    Json(ApiResponse::error("Timestamps must be RFC 3339")),
+                ));
+            }
+        };
+
+        let cursor = match cursor.map(Cursor::decode) {
+            Some(Some(cursor)) => Some(cursor),
+            Some(None) => {
+                return Err(Custom(
+                    Status::BadRequest,
+                    Json(ApiResponse::error("Invalid cursor")),
+                ));
+            }
+            None => None,
+        };
+
+        let types = types
+            .map(|t| {
+                t.split(',')
+                    .map(str::trim)
+                    .filter(|t| !t.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(LogFilter {
+            types,
+            since,
+            until,
+            cursor,
+        })
+    }
+}
+
+/// Loads one page of logs newest first, keyed on `(created_at, id)` so pages stay
+/// consistent while the clone worker keeps appending.
+fn load_log_page<'a>(
+    connection: &mut PgConnection,
+    mut query: repository_logs::BoxedQuery<'a, Pg>,
+    filter: &LogFilter,
+    limit: Option<i64>,
+) -> QueryResult<(Vec<RepositoryLogModel>, Option<String>)> {
+    if !filter.types.is_empty() {
+        query = query.filter(repository_logs::type_.eq_any(filter.types.clone()));
+    }
+    if let Some(since) = filter.since {
+        query = query.filter(repository_logs::created_at.ge(since));
+    }
+    if let Some(until) = filter.until {
+        query = query.filter(repository_logs::created_at.lt(until));
+    }
+    if let Some(cursor) = &filter.cursor {
+        query = query.filter(
+            repository_logs::created_at
+                .lt(cursor.created_at)
+                .or(repository_logs::created_at
+                    .eq(cursor.created_at)
+                    .and(repository_logs::id.lt(cursor.id))),
+        );
+    }
+
+    let limit = page_limit(limit);
+    // one extra row tells whether there is a next page
+    let mut logs = query
+        .order((
+            repository_logs::created_at.desc(),
+            repository_logs::id.desc(),
+        ))
+        .limit(limit + 1)
+        .load::<RepositoryLogModel>(connection)?;
+
+    let next_cursor = if logs.len() as i64 > limit {
+        logs.truncate(limit as usize);
+        logs.last().map(|log| {
+            Cursor {
+                created_at: log.created_at,
+                id: log.id,
+            }
+            .encode()
+        })
+    } else {
+        None
+    };
+
+    Ok((logs, next_cursor))
+}
+
+/// Loads a repository visible to the user and checks that their role allows `required`.
+/// Repositories the user cannot see at all are reported as missing.
+pub fn find_repository_with_role<T>(
+    connection: &mut PgConnection,
+    user_id: uuid::Uuid,
+    repo_id: uuid::Uuid,
+    required: TeamRole,
+) -> Result<RepositoryModel, Custom<Json<ApiResponse<T>>>> {
+    let repo = match accessible_repositories(user_id, TeamRole::Viewer)
+        .filter(repository::id.eq(repo_id))
+        .first::<RepositoryModel>(connection)
+        .optional()
+    {
+        Ok(Some(repo)) => repo,
+        Ok(None) => {
+            return Err(Custom(
+                Status::NotFound,
+                Json(ApiResponse::error("Repository not found")),
+            ));
+        }
+        Err(_) => {
+            return Err(Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Failed to fetch repository")),
+            ));
+        }
+    };
+
+    match repository_role(connection, &repo, user_id) {
+        Ok(Some(role)) if role >= required => Ok(repo),
+        Ok(_) => Err(Custom(
+            Status::Forbidden,
+            Json(ApiResponse::error("Insufficient team role")),
+        )),
+        Err(_) => Err(Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to fetch repository")),
+        )),
+    }
 }
 
 #[get("/repository/<repo_id>")]
 pub fn get_repository_by_id(
     db: &State<DbConnection>,
-    user: AuthGuard,
+    user: AuthGuard<scope::Read>,
     repo_id: String,
 ) -> Custom<Json<ApiResponse<GetRepositoryResponse>>> {
-    use crate::schema::repository::dsl::*;
-
     let connection = &mut db.get().unwrap();
 
     let parsed_id = match uuid::Uuid::parse_str(&repo_id) {
@@ -109,37 +475,32 @@ pub fn get_repository_by_id(
         }
     };
 
-    match repository
-        .filter(id.eq(parsed_id).and(user_id.eq(user.0.id)))
-        .first::<RepositoryModel>(connection)
-        .optional()
-    {
-        Ok(Some(repo)) => Custom(
+    match find_repository_with_role(connection, user.0.id, parsed_id, TeamRole::Viewer) {
+        Ok(repo) => Custom(
             Status::Ok,
             Json(ApiResponse::success(
                 "Repository fetched successfully",
-                GetRepositoryResponse { repository: repo },
+                GetRepositoryResponse {
+                    repository: repo.into(),
+                },
             )),
         ),
-        Ok(None) => Custom(
-            Status::NotFound,
-            Json(ApiResponse::error("Repository not found")),
-        ),
-        Err(_) => Custom(
-            Status::InternalServerError,
-            Json(ApiResponse::error("Failed to fetch repository")),
-        ),
+        Err(response) => response,
     }
 }
 
-#[get("/repository/<repo_id>/logs")]
+#[allow(clippy::too_many_arguments)]
+#[get("/repository/<repo_id>/logs?<type>&<since>&<until>&<cursor>&<limit>")]
 pub fn get_repository_logs_by_id(
     db: &State<DbConnection>,
-    user: AuthGuard,
+    user: AuthGuard<scope::Read>,
     repo_id: String,
+    r#type: Option<&str>,
+    since: Option<&str>,
+    until: Option<&str>,
+    cursor: Option<&str>,
+    limit: Option<i64>,
 ) -> Custom<Json<ApiResponse<GetRepositoryLogsResponse>>> {
-    use crate::schema::repository::dsl::*;
-
     let connection = &mut db.get().unwrap();
 
     let parsed_id = match uuid::Uuid::parse_str(&repo_id) {
@@ -152,38 +513,28 @@ pub fn get_repository_logs_by_id(
         }
     };
 
-    let repo = match repository
-        .filter(id.eq(parsed_id).and(user_id.eq(user.0.id)))
-        .first::<RepositoryModel>(connection)
-        .optional()
-    {
-        Ok(Some(r)) => r,
-        Ok(None) => {
-            return Custom(
-                Status::NotFound,
-                Json(ApiResponse::error("Repository not found")),
-            );
-        }
-        Err(_) => {
-            return Custom(
-                Status::InternalServerError,
-                Json(ApiResponse::error("Failed to fetch repository")),
-            );
-        }
+    let filter = match LogFilter::parse(r#type, since, until, cursor) {
+        Ok(filter) => filter,
+        Err(response) => return response,
+    };
+
+    let repo = match find_repository_with_role(connection, user.0.id, parsed_id, TeamRole::Viewer) {
+        Ok(r) => r,
+        Err(response) => return response,
     };
 
-    let logs_result = crate::schema::repository_logs::table
-        .filter(crate::schema::repository_logs::repository_id.eq(repo.id))
-        .order(crate::schema::repository_logs::created_at.desc())
-        .load::<RepositoryLogModel>(connection);
+    let query = repository_logs::table
+        .filter(repository_logs::repository_id.eq(repo.id))
+        .into_boxed();
 
-    match logs_result {
-        Ok(logs) => Custom(
+    match load_log_page(connection, query, &filter, limit) {
+        Ok((logs, next_cursor)) => Custom(
             Status::Ok,
             Json(ApiResponse::success(
                 "Logs fetched successfully",
                 GetRepositoryLogsResponse {
                     repository_logs: logs,
+                    next_cursor,
                 },
             )),
         ),
@@ -194,10 +545,79 @@ pub fn get_repository_logs_by_id(
     }
 }
 
+/// Logs of every repository the user can see, newest first.
+#[get("/repository/logs?<type>&<since>&<until>&<cursor>&<limit>")]
+pub fn get_repository_log_feed(
+    db: &State<DbConnection>,
+    user: AuthGuard<scope::Read>,
+    r#type: Option<&str>,
+    since: Option<&str>,
+    until: Option<&str>,
+    cursor: Option<&str>,
+    limit: Option<i64>,
+) -> Custom<Json<ApiResponse<GetRepositoryLogFeedResponse>>> {
+    let filter = match LogFilter::parse(r#type, since, until, cursor) {
+        Ok(filter) => filter,
+        Err(response) => return response,
+    };
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let visible_ids = accessible_repositories(user.0.id, TeamRole::Viewer).select(repository::id);
+    let query = repository_logs::table
+        .filter(repository_logs::repository_id.eq_any(visible_ids))
+        .into_boxed();
+
+    let (logs, next_cursor) = match load_log_page(connection, query, &filter, limit) {
+        Ok(page) => page,
+        Err(_) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Failed to fetch logs")),
+            );
+        }
+    };
+
+    let repo_ids: Vec<uuid::Uuid> = logs.iter().map(|l| l.repository_id).collect();
+    let names: HashMap<uuid::Uuid, String> = match repository::table
+        .filter(repository::id.eq_any(&repo_ids))
+        .select((repository::id, repository::name))
+        .load::<(uuid::Uuid, String)>(connection)
+    {
+        Ok(rows) => rows.into_iter().collect(),
+        Err(_) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Failed to fetch logs")),
+            );
+        }
+    };
+
+    let repository_logs = logs
+        .into_iter()
+        .map(|log| RepositoryLogFeedEntry {
+            repository_name: names.get(&log.repository_id).cloned().unwrap_or_default(),
+            log,
+        })
+        .collect();
+
+    Custom(
+        Status::Ok,
+        Json(ApiResponse::success(
+            "Logs fetched successfully",
+            GetRepositoryLogFeedResponse {
+                repository_logs,
+                next_cursor,
+            },
+        )),
+    )
+}
+
 #[delete("/repository/<repo_id>")]
 pub fn delete_repository_by_id(
     db: &State<DbConnection>,
-    user: AuthGuard,
+    user: AuthGuard<scope::RepositoriesWrite>,
+    client: ClientInfo,
     repo_id: String,
 ) -> Custom<Json<ApiResponse<DeleteRepositoryResponse>>> {
     use crate::schema::repository::dsl::*;
@@ -214,12 +634,8 @@ pub fn delete_repository_by_id(
         }
     };
 
-    match repository
-        .filter(id.eq(parsed_id).and(user_id.eq(user.0.id)))
-        .first::<RepositoryModel>(connection)
-        .optional()
-    {
-        Ok(Some(repo)) => {
+    match find_repository_with_role(connection, user.0.id, parsed_id, TeamRole::Maintainer) {
+        Ok(repo) => {
             if diesel::delete(repository.filter(id.eq(parsed_id)))
                 .execute(connection)
                 .is_err()
@@ -230,85 +646,544 @@ pub fn delete_repository_by_id(
                 );
             }
 
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_REPOSITORY_DELETE,
+                Some(AuditTarget::new(AUDIT_TARGET_REPOSITORY, repo.id)),
+                AuditChanges::diff(Some(&repo), None),
+            );
+
             Custom(
                 Status::Ok,
                 Json(ApiResponse::success(
                     "Repository deleted successfully",
-                    DeleteRepositoryResponse { repository: repo },
+                    DeleteRepositoryResponse {
+                        repository: repo.into(),
+                    },
+                )),
+            )
+        }
+        Err(response) => response,
+    }
+}
+
+#[post("/repository/<repo_id>/sync")]
+pub fn sync_repository_by_id(
+    db: &State<DbConnection>,
+    user: AuthGuard<scope::SyncTrigger>,
+    client: ClientInfo,
+    repo_id: String,
+) -> Custom<Json<ApiResponse<SyncRepositoryResponse>>> {
+    use crate::schema::repository::dsl::*;
+
+    let connection = &mut db.get().unwrap();
+
+    let parsed_id = match uuid::Uuid::parse_str(&repo_id) {
+        Ok(uuid) => uuid,
+        Err(_) => {
+            return Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error("Invalid repository ID")),
+            );
+        }
+    };
+
+    if let Err(response) =
+        find_repository_with_role(connection, user.0.id, parsed_id, TeamRole::Operator)
+    {
+        return response;
+    }
+
+    // the clone worker picks up repositories with a pending sync request before due ones
+    match diesel::update(repository.filter(id.eq(parsed_id).and(is_enabled.eq(true))))
+        .set(sync_requested_at.eq(Utc::now()))
+        .get_result::<RepositoryModel>(connection)
+        .optional()
+    {
+        Ok(Some(repo)) => {
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_REPOSITORY_SYNC,
+                Some(AuditTarget::new(AUDIT_TARGET_REPOSITORY, repo.id)),
+                AuditChanges::none(),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "Repository sync requested",
+                    SyncRepositoryResponse {
+                        repository: repo.into(),
+                    },
                 )),
             )
         }
         Ok(None) => Custom(
             Status::NotFound,
-            Json(ApiResponse::error("Repository not found")),
+            Json(ApiResponse::error("Enabled repository not found")),
         ),
         Err(_) => Custom(
             Status::InternalServerError,
-            Json(ApiResponse::error("Failed to fetch repository")),
+            Json(ApiResponse::error("Failed to request repository sync")),
+        ),
+    }
+}
+
+#[patch("/repository/<repo_id>", format = "application/json", data = "<form>")]
+pub fn update_repository(
+    db: &State<DbConnection>,
+    config: &State<Config>,
+    user: AuthGuard<scope::RepositoriesWrite>,
+    client: ClientInfo,
+    repo_id: String,
+    form: Json<UpdateRepositoryForm>,
+) -> Custom<Json<ApiResponse<UpdateRepositoryResponse>>> {
+    if let Err(_e) = form.validate() {
+        return Custom(Status::BadRequest, Json(ApiResponse::error("Bad request")));
+    }
+
+    let periods = &config.repository;
+    if let Some(period) = form.git_clone_period_seconds
+        && !(periods.min_period_seconds..=periods.max_period_seconds).contains(&period)
+    {
+        return Custom(
+            Status::BadRequest,
+            Json(ApiResponse::error(&format!(
+                "Cloning period must be between {} and {} seconds",
+                periods.min_period_seconds, periods.max_period_seconds
+            ))),
+        );
+    }
+
+    let tags = match form.tags.as_deref().map(normalize_tags) {
+        Some(Some(tags)) => Some(tags),
+        Some(None) => {
+            return Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error(
+                    "Tags must be between 1 and 50 characters long",
+                )),
+            );
+        }
+        None => None,
+    };
+
+    let ref_filters = match form.ref_filters.as_deref().map(normalize_ref_filters) {
+        Some(Some(filters)) => Some(filters),
+        Some(None) => {
+            return Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error(INVALID_REF_FILTERS_MESSAGE)),
+            );
+        }
+        None => None,
+    };
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let parsed_id = match uuid::Uuid::parse_str(&repo_id) {
+        Ok(uuid) => uuid,
+        Err(_) => {
+            return Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error("Invalid repository ID")),
+            );
+        }
+    };
+
+    let before =
+        match find_repository_with_role(connection, user.0.id, parsed_id, TeamRole::Maintainer) {
+            Ok(repo) => repo,
+            Err(response) => return response,
+        };
+
+    let keys = MasterKeys::from_config(&config.keys);
+    let source_key = form
+        .git_source_secret_key
+        .as_deref()
+        .map(|k| Some(k).filter(|k| !k.is_empty()).map(|k| keys.seal(k)));
+    let target_key = form.git_target_secret_key.as_deref().map(|k| keys.seal(k));
+
+    let changeset = RepositoryChangeset {
+        name: form.name.as_deref(),
+        url: form
+            .url
+            .as_deref()
+            .map(|s| Some(s).filter(|s| !s.is_empty())),
+        is_enabled: form.is_enabled,
+        git_source: form.git_source.as_deref(),
+        git_source_secret_key: source_key.as_ref().map(Option::as_deref),
+        git_target: form.git_target.as_deref(),
+        git_target_secret_key: target_key.as_deref(),
+        git_clone_period_seconds: form.git_clone_period_seconds.map(|p| p as i32),
+        tags,
+        ref_filters,
+        updated_at: Some(Utc::now()),
+    };
+
+    match diesel::update(repository::table.filter(repository::id.eq(before.id)))
+        .set(&changeset)
+        .get_result::<RepositoryModel>(connection)
+    {
+        Ok(repo) => {
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_REPOSITORY_UPDATE,
+                Some(AuditTarget::new(AUDIT_TARGET_REPOSITORY, repo.id)),
+                AuditChanges::diff(Some(&before), Some(&repo)),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "Repository updated successfully",
+                    UpdateRepositoryResponse {
+                        repository: repo.into(),
+                    },
+                )),
+            )
+        }
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to update repository")),
+        ),
+    }
+}
+
+#[put(
+    "/repository/<repo_id>/retention",
+    format = "application/json",
+    data = "<form>"
+)]
+pub fn update_repository_retention(
+    db: &State<DbConnection>,
+    user: AuthGuard<scope::RepositoriesWrite>,
+    client: ClientInfo,
+    repo_id: String,
+    form: Json<UpdateRetentionForm>,
+) -> Custom<Json<ApiResponse<UpdateRetentionResponse>>> {
+    if let Err(_e) = form.validate() {
+        return Custom(Status::BadRequest, Json(ApiResponse::error("Bad request")));
+    }
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let parsed_id = match uuid::Uuid::parse_str(&repo_id) {
+        Ok(uuid) => uuid,
+        Err(_) => {
+            return Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error("Invalid repository ID")),
+            );
+        }
+    };
+
+    let before =
+        match find_repository_with_role(connection, user.0.id, parsed_id, TeamRole::Maintainer) {
+            Ok(repo) => repo,
+            Err(response) => return response,
+        };
+
+    match diesel::update(repository::table.filter(repository::id.eq(before.id)))
+        .set((
+            repository::log_retention_days.eq(form.log_retention_days),
+            repository::log_max_rows.eq(form.log_max_rows),
+            repository::updated_at.eq(Utc::now()),
+        ))
+        .get_result::<RepositoryModel>(connection)
+    {
+        Ok(repo) => {
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_REPOSITORY_RETENTION_UPDATE,
+                Some(AuditTarget::new(AUDIT_TARGET_REPOSITORY, repo.id)),
+                AuditChanges::diff(Some(&before), Some(&repo)),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "Log retention updated successfully",
+                    UpdateRetentionResponse {
+                        repository: repo.into(),
+                    },
+                )),
+            )
+        }
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to update log retention")),
         ),
     }
 }
 
-#[get("/repository")]
+#[allow(clippy::too_many_arguments)]
+#[get(
+    "/repository?<search>&<enabled>&<failing>&<overdue>&<tag>&<host>&<sort>&<order>&<limit>&<offset>"
+)]
 pub fn get_all_repositories(
     db: &State<DbConnection>,
-    user: AuthGuard,
+    user: AuthGuard<scope::Read>,
+    search: Option<&str>,
+    enabled: Option<bool>,
+    failing: Option<bool>,
+    overdue: Option<bool>,
+    tag: Option<&str>,
+    host: Option<&str>,
+    sort: Option<&str>,
+    order: Option<&str>,
+    limit: Option<i64>,
+    offset: Option<i64>,
 ) -> Custom<Json<ApiResponse<GetRepositoriesResponse>>> {
-    let connection = &mut db.get().unwrap();
+    let sort = match RepositorySort::parse(sort.unwrap_or("name")) {
+        Some(sort) => sort,
+        None => {
+            return Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error(
+                    "Sort must be one of name, last_clone_at, created_at, status",
+                )),
+            );
+        }
+    };
+    let descending = match order.unwrap_or("asc") {
+        "asc" => false,
+        "desc" => true,
+        _ => {
+            return Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error("Order must be asc or desc")),
+            );
+        }
+    };
 
-    let results = repository::table
-        .filter(repository::user_id.eq(user.0.id))
-        .load::<RepositoryModel>(connection)
-        .unwrap();
+    let connection = &mut db.get().expect("Failed to get DB Connection");
 
-    Custom(
-        Status::Ok,
-        Json(ApiResponse::success(
-            "Repositories fetched successfully",
-            GetRepositoriesResponse {
-                repositories: results,
-            },
-        )),
-    )
+    let search_pattern = search
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| format!("%{}%", escape_like(s)));
+
+    let filtered = || {
+        let mut query = accessible_repositories(user.0.id, TeamRole::Viewer);
+        if let Some(pattern) = search_pattern.as_deref() {
+            // same expression as idx_repository_search so the trigram index is used
+            query = query.filter(
+                sql::<Bool>(
+                    "(name || ' ' || coalesce(url, '') || ' ' || git_source || ' ' || git_target) ILIKE ",
+                )
+                .bind::<Text, _>(pattern.to_string()),
+            );
+        }
+        if let Some(enabled) = enabled {
+            query = query.filter(repository::is_enabled.eq(enabled));
+        }
+        if let Some(failing) = failing {
+            query = match failing {
+                true => query.filter(repository::last_sync_status.eq(SYNC_STATUS_FAILED)),
+                false => query.filter(
+                    repository::last_sync_status
+                        .is_null()
+                        .or(repository::last_sync_status.ne(SYNC_STATUS_FAILED)),
+                ),
+            };
+        }
+        if let Some(overdue) = overdue {
+            let overdue_sql = sql::<Bool>(OVERDUE_SQL);
+            query = match overdue {
+                true => query.filter(overdue_sql),
+                false => query.filter(overdue_sql.eq(false)),
+            };
+        }
+        if let Some(tag) = tag {
+            query = query.filter(repository::tags.contains(vec![tag.trim().to_lowercase()]));
+        }
+        if let Some(host) = host {
+            query = query.filter(repository::source_host.eq(host.to_lowercase()));
+        }
+        query
+    };
+
+    let total = match filtered().count().get_result::<i64>(connection) {
+        Ok(total) => total,
+        Err(_e) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Failed to fetch repositories")),
+            );
+        }
+    };
+
+    let query = filtered();
+    let query = match (sort, descending) {
+        (RepositorySort::Name, false) => query.order(repository::name.asc()),
+        (RepositorySort::Name, true) => query.order(repository::name.desc()),
+        (RepositorySort::LastCloneAt, false) => {
+            query.order(repository::last_clone_at.asc().nulls_first())
+        }
+        (RepositorySort::LastCloneAt, true) => {
+            query.order(repository::last_clone_at.desc().nulls_last())
+        }
+        (RepositorySort::CreatedAt, false) => query.order(repository::created_at.asc()),
+        (RepositorySort::CreatedAt, true) => query.order(repository::created_at.desc()),
+        (RepositorySort::Status, false) => query.order(sql::<Integer>(STATUS_RANK_SQL).asc()),
+        (RepositorySort::Status, true) => query.order(sql::<Integer>(STATUS_RANK_SQL).desc()),
+    };
+
+    // the id tie-breaker keeps pages stable when sort values repeat
+    match query
+        .then_order_by(repository::id.asc())
+        .limit(page_limit(limit))
+        .offset(offset.unwrap_or(0).max(0))
+        .load::<RepositoryModel>(connection)
+    {
+        Ok(repositories) => Custom(
+            Status::Ok,
+            Json(ApiResponse::success(
+                "Repositories fetched successfully",
+                GetRepositoriesResponse {
+                    repositories: repositories
+                        .into_iter()
+                        .map(PublicRepository::from)
+                        .collect(),
+                    total,
+                },
+            )),
+        ),
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to fetch repositories")),
+        ),
+    }
 }
 
 #[post("/repository", format = "application/json", data = "<form>")]
 pub fn add_repository(
     db: &State<DbConnection>,
-    user: AuthGuard,
+    config: &State<Config>,
+    user: AuthGuard<scope::RepositoriesWrite>,
+    client: ClientInfo,
     form: Json<AddRepositoryForm>,
 ) -> Custom<Json<ApiResponse<AddRepositoryResponse>>> {
     if let Err(_e) = form.validate() {
         return Custom(Status::BadRequest, Json(ApiResponse::error("Bad request")));
     }
 
+    let periods = &config.repository;
+    if !(periods.min_period_seconds..=periods.max_period_seconds)
+        .contains(&form.git_clone_period_seconds)
+    {
+        return Custom(
+            Status::BadRequest,
+            Json(ApiResponse::error(&format!(
+                "Cloning period must be between {} and {} seconds",
+                periods.min_period_seconds, periods.max_period_seconds
+            ))),
+        );
+    }
+
     let connection = &mut db.get().unwrap();
 
+    if let Some(team_id) = form.team_id {
+        match team_role(connection, team_id, user.0.id) {
+            Ok(Some(TeamRole::Maintainer)) => {}
+            Ok(_) => {
+                return Custom(
+                    Status::Forbidden,
+                    Json(ApiResponse::error(
+                        "Only team maintainers can add repositories to a team",
+                    )),
+                );
+            }
+            Err(_e) => {
+                return Custom(
+                    Status::InternalServerError,
+                    Json(ApiResponse::error("Database error")),
+                );
+            }
+        }
+    }
+
+    let tags = match normalize_tags(form.tags.as_deref().unwrap_or_default()) {
+        Some(tags) => tags,
+        None => {
+            return Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error(
+                    "Tags must be between 1 and 50 characters long",
+                )),
+            );
+        }
+    };
+
+    let ref_filters = match normalize_ref_filters(form.ref_filters.as_deref().unwrap_or_default()) {
+        Some(filters) => filters,
+        None => {
+            return Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error(INVALID_REF_FILTERS_MESSAGE)),
+            );
+        }
+    };
+
+    let keys = MasterKeys::from_config(&config.keys);
+    let source_key = form
+        .git_source_secret_key
+        .as_deref()
+        .filter(|k| !k.is_empty())
+        .map(|k| keys.seal(k));
+    let target_key = keys.seal(&form.git_target_secret_key);
+
+    // team repositories have no personal owner
     let new_repo = InsertableRepositoryModel {
-        user_id: user.0.id,
+        user_id: Some(user.0.id).filter(|_| form.team_id.is_none()),
+        team_id: form.team_id,
         name: form.name.as_str(),
         url: Some(form.url.as_str()).filter(|s| !s.is_empty()),
         is_enabled: true,
         git_source: form.git_source.as_str(),
-        git_source_secret_key: form.git_source_secret_key.as_deref(),
+        git_source_secret_key: source_key.as_deref(),
         git_target: form.git_target.as_str(),
-        git_target_secret_key: Some(form.git_target_secret_key.as_str()),
+        git_target_secret_key: Some(target_key.as_str()),
         git_clone_period_seconds: form.git_clone_period_seconds as i32,
+        tags,
+        log_retention_days: form.log_retention_days,
+        log_max_rows: form.log_max_rows,
+        ref_filters,
     };
 
     match diesel::insert_into(crate::schema::repository::table)
         .values(&new_repo)
         .get_result::<RepositoryModel>(connection)
     {
-        Ok(inserted) => Custom(
-            Status::Ok,
-            Json(ApiResponse::success(
-                "Repository added successfully",
-                AddRepositoryResponse {
-                    created_repository: inserted,
-                },
-            )),
-        ),
+        Ok(inserted) => {
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_REPOSITORY_CREATE,
+                Some(AuditTarget::new(AUDIT_TARGET_REPOSITORY, inserted.id)),
+                AuditChanges::diff(None, Some(&inserted)),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "Repository added successfully",
+                    AddRepositoryResponse {
+                        created_repository: inserted.into(),
+                    },
+                )),
+            )
+        }
         Err(_e) => Custom(
             Status::InternalServerError,
             Json(ApiResponse::error(
@@ -0,0 +1,147 @@
+use diesel::prelude::*;
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::db::DbConnection;
+use crate::middlewares::client::ClientInfo;
+use crate::middlewares::setup::{SETUP_ADMIN_USERNAME, SetupState};
+use crate::models::{PublicUser, UserModel};
+use crate::routes::user::{MAX_PASSWORD_LENGTH, MIN_PASSWORD_LENGTH};
+use crate::schema::user;
+use crate::utils::audit::{
+    AUDIT_SETUP_COMPLETE, AUDIT_TARGET_USER, AuditChanges, AuditTarget, record_audit_event,
+};
+use crate::utils::crypto::{constant_time_eq, hash_password};
+use crate::utils::response::ApiResponse;
+
+#[derive(Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct SetupForm<'r> {
+    #[validate(length(min = 1, max = 256))]
+    setup_token: &'r str,
+    #[validate(length(min = MIN_PASSWORD_LENGTH, max = MAX_PASSWORD_LENGTH))]
+    password: &'r str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetupStatusResponse {
+    pub setup_required: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetupResponse {
+    pub user: PublicUser,
+}
+
+#[get("/setup")]
+pub fn get_setup_status(setup: &State<SetupState>) -> Json<ApiResponse<SetupStatusResponse>> {
+    Json(ApiResponse::success(
+        "Success",
+        SetupStatusResponse {
+            setup_required: setup.is_required(),
+        },
+    ))
+}
+
+#[get("/setup/required")]
+pub fn setup_required() -> Custom<Json<ApiResponse<SetupStatusResponse>>> {
+    Custom(
+        Status::ServiceUnavailable,
+        Json(ApiResponse {
+            success: false,
+            message: "Setup required".to_string(),
+            data: Some(SetupStatusResponse {
+                setup_required: true,
+            }),
+        }),
+    )
+}
+
+#[post("/setup", format = "application/json", data = "<form>")]
+pub fn complete_setup(
+    db: &State<DbConnection>,
+    setup: &State<SetupState>,
+    client: ClientInfo,
+    form: Json<SetupForm<'_>>,
+) -> Custom<Json<ApiResponse<SetupResponse>>> {
+    if !setup.is_required() {
+        return Custom(
+            Status::Conflict,
+            Json(ApiResponse::error("Setup already completed")),
+        );
+    }
+
+    if let Err(_e) = form.validate() {
+        return Custom(Status::BadRequest, Json(ApiResponse::error("Bad request")));
+    }
+
+    if !constant_time_eq(form.setup_token, &setup.bootstrap_token) {
+        return Custom(
+            Status::Forbidden,
+            Json(ApiResponse::error("Invalid setup token")),
+        );
+    }
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let new_password_hash = match hash_password(form.password) {
+        Ok(h) => h,
+        Err(_) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Failed to hash password")),
+            );
+        }
+    };
+
+    // only the first request that sets the password wins
+    let update_result = diesel::update(
+        user::table
+            .filter(user::username.eq(SETUP_ADMIN_USERNAME))
+            .filter(user::password_hash.is_null()),
+    )
+    .set(user::password_hash.eq(new_password_hash))
+    .returning(UserModel::as_returning())
+    .get_result::<UserModel>(connection)
+    .optional();
+
+    match update_result {
+        Ok(Some(admin)) => {
+            setup.mark_completed();
+            record_audit_event(
+                connection,
+                Some(&admin),
+                &client,
+                AUDIT_SETUP_COMPLETE,
+                Some(AuditTarget::new(AUDIT_TARGET_USER, admin.id)),
+                AuditChanges::none(),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "Setup completed",
+                    SetupResponse { user: admin.into() },
+                )),
+            )
+        }
+        Ok(None) => {
+            setup.mark_completed();
+
+            Custom(
+                Status::Conflict,
+                Json(ApiResponse::error("Setup already completed")),
+            )
+        }
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Database error")),
+        ),
+    }
+}
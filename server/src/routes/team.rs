@@ -0,0 +1,544 @@
+use diesel::prelude::*;
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::db::DbConnection;
+use crate::middlewares::auth::{AuthGuard, scope};
+use crate::middlewares::client::ClientInfo;
+use crate::models::{
+    InsertableTeamMemberModel, InsertableTeamModel, PublicTeamMember, TeamMemberModel, TeamModel,
+};
+use crate::routes::user::{MAX_USERNAME_LENGTH, MIN_USERNAME_LENGTH};
+use crate::schema::{repository, team, team_member, user};
+use crate::utils::access::{TeamRole, team_role};
+use crate::utils::audit::{
+    AUDIT_TARGET_TEAM, AUDIT_TEAM_CREATE, AUDIT_TEAM_DELETE, AUDIT_TEAM_MEMBER_REMOVE,
+    AUDIT_TEAM_MEMBER_SET, AuditChanges, AuditTarget, record_audit_event,
+};
+use crate::utils::response::ApiResponse;
+
+#[derive(Deserialize, Validate)]
+pub struct CreateTeamForm<'r> {
+    #[validate(length(
+        min = 3,
+        max = 100,
+        message = "Team name should be between 3 and 100 characters long"
+    ))]
+    name: &'r str,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct SetTeamMemberForm<'r> {
+    #[validate(length(min = MIN_USERNAME_LENGTH, max = MAX_USERNAME_LENGTH))]
+    username: &'r str,
+    role: &'r str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamWithRole {
+    pub team: TeamModel,
+    pub role: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTeamsResponse {
+    pub teams: Vec<TeamWithRole>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTeamResponse {
+    pub team: TeamModel,
+    pub role: String,
+    pub members: Vec<PublicTeamMember>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamMemberResponse {
+    pub member: PublicTeamMember,
+}
+
+fn parse_id<T>(value: &str, message: &str) -> Result<Uuid, Custom<Json<ApiResponse<T>>>> {
+    Uuid::parse_str(value)
+        .map_err(|_| Custom(Status::BadRequest, Json(ApiResponse::error(message))))
+}
+
+/// Checks the user's role in the team. Non-members get a 404 so team ids are not disclosed.
+fn require_team_role<T>(
+    connection: &mut PgConnection,
+    team_id: Uuid,
+    user_id: Uuid,
+    required: TeamRole,
+) -> Result<TeamRole, Custom<Json<ApiResponse<T>>>> {
+    match team_role(connection, team_id, user_id) {
+        Ok(Some(role)) if role >= required => Ok(role),
+        Ok(Some(_)) => Err(Custom(
+            Status::Forbidden,
+            Json(ApiResponse::error("Insufficient team role")),
+        )),
+        Ok(None) => Err(Custom(
+            Status::NotFound,
+            Json(ApiResponse::error("Team not found")),
+        )),
+        Err(_e) => Err(Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Database error")),
+        )),
+    }
+}
+
+fn load_members(
+    connection: &mut PgConnection,
+    team_id: Uuid,
+) -> QueryResult<Vec<PublicTeamMember>> {
+    let rows = team_member::table
+        .inner_join(user::table)
+        .filter(team_member::team_id.eq(team_id))
+        .order(user::username.asc())
+        .select((TeamMemberModel::as_select(), user::username))
+        .load::<(TeamMemberModel, String)>(connection)?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(member, username)| PublicTeamMember {
+            user_id: member.user_id,
+            username,
+            role: member.role,
+            created_at: member.created_at,
+        })
+        .collect())
+}
+
+#[post("/team", format = "application/json", data = "<form>")]
+pub fn create_team(
+    db: &State<DbConnection>,
+    user: AuthGuard,
+    client: ClientInfo,
+    form: Json<CreateTeamForm<'_>>,
+) -> Custom<Json<ApiResponse<TeamWithRole>>> {
+    if let Err(_e) = form.validate() {
+        return Custom(Status::BadRequest, Json(ApiResponse::error("Bad request")));
+    }
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    // the creator becomes the first maintainer
+    let result = connection.transaction(|connection| {
+        let created = diesel::insert_into(team::table)
+            .values(&InsertableTeamModel { name: form.name })
+            .returning(TeamModel::as_returning())
+            .get_result::<TeamModel>(connection)?;
+
+        diesel::insert_into(team_member::table)
+            .values(&InsertableTeamMemberModel {
+                team_id: created.id,
+                user_id: user.0.id,
+                role: TeamRole::Maintainer.as_str(),
+            })
+            .execute(connection)?;
+
+        Ok::<_, diesel::result::Error>(created)
+    });
+
+    match result {
+        Ok(created) => {
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_TEAM_CREATE,
+                Some(AuditTarget::new(AUDIT_TARGET_TEAM, created.id)),
+                AuditChanges::diff(None, Some(&created)),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "Team created successfully",
+                    TeamWithRole {
+                        team: created,
+                        role: TeamRole::Maintainer.as_str().to_string(),
+                    },
+                )),
+            )
+        }
+        Err(diesel::result::Error::DatabaseError(
+            diesel::result::DatabaseErrorKind::UniqueViolation,
+            _,
+        )) => Custom(
+            Status::Conflict,
+            Json(ApiResponse::error("A team with this name already exists")),
+        ),
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to create team")),
+        ),
+    }
+}
+
+#[get("/team")]
+pub fn get_teams(
+    db: &State<DbConnection>,
+    user: AuthGuard<scope::Read>,
+) -> Custom<Json<ApiResponse<GetTeamsResponse>>> {
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    match team::table
+        .inner_join(team_member::table)
+        .filter(team_member::user_id.eq(user.0.id))
+        .order(team::name.asc())
+        .select((TeamModel::as_select(), team_member::role))
+        .load::<(TeamModel, String)>(connection)
+    {
+        Ok(rows) => Custom(
+            Status::Ok,
+            Json(ApiResponse::success(
+                "Teams fetched successfully",
+                GetTeamsResponse {
+                    teams: rows
+                        .into_iter()
+                        .map(|(team, role)| TeamWithRole { team, role })
+                        .collect(),
+                },
+            )),
+        ),
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to fetch teams")),
+        ),
+    }
+}
+
+#[get("/team/<team_id>")]
+pub fn get_team_by_id(
+    db: &State<DbConnection>,
+    user: AuthGuard<scope::Read>,
+    team_id: String,
+) -> Custom<Json<ApiResponse<GetTeamResponse>>> {
+    let team_id = match parse_id(&team_id, "Invalid team ID") {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let role = match require_team_role(connection, team_id, user.0.id, TeamRole::Viewer) {
+        Ok(role) => role,
+        Err(response) => return response,
+    };
+
+    let result = team::table
+        .find(team_id)
+        .select(TeamModel::as_select())
+        .first::<TeamModel>(connection)
+        .and_then(|team| Ok((team, load_members(connection, team_id)?)));
+
+    match result {
+        Ok((team, members)) => Custom(
+            Status::Ok,
+            Json(ApiResponse::success(
+                "Team fetched successfully",
+                GetTeamResponse {
+                    team,
+                    role: role.as_str().to_string(),
+                    members,
+                },
+            )),
+        ),
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to fetch team")),
+        ),
+    }
+}
+
+#[delete("/team/<team_id>")]
+pub fn delete_team_by_id(
+    db: &State<DbConnection>,
+    user: AuthGuard,
+    client: ClientInfo,
+    team_id: String,
+) -> Custom<Json<ApiResponse<TeamModel>>> {
+    let team_id = match parse_id(&team_id, "Invalid team ID") {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    if let Err(response) = require_team_role(connection, team_id, user.0.id, TeamRole::Maintainer) {
+        return response;
+    }
+
+    match repository::table
+        .filter(repository::team_id.eq(team_id))
+        .count()
+        .get_result::<i64>(connection)
+    {
+        Ok(0) => {}
+        Ok(_) => {
+            return Custom(
+                Status::Conflict,
+                Json(ApiResponse::error(
+                    "Team still owns repositories, transfer or delete them first",
+                )),
+            );
+        }
+        Err(_e) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Database error")),
+            );
+        }
+    }
+
+    match diesel::delete(team::table.find(team_id))
+        .returning(TeamModel::as_returning())
+        .get_result::<TeamModel>(connection)
+    {
+        Ok(deleted) => {
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_TEAM_DELETE,
+                Some(AuditTarget::new(AUDIT_TARGET_TEAM, deleted.id)),
+                AuditChanges::diff(Some(&deleted), None),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success("Team deleted successfully", deleted)),
+            )
+        }
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to delete team")),
+        ),
+    }
+}
+
+#[put(
+    "/team/<team_id>/members",
+    format = "application/json",
+    data = "<form>"
+)]
+pub fn set_team_member(
+    db: &State<DbConnection>,
+    user: AuthGuard,
+    client: ClientInfo,
+    team_id: String,
+    form: Json<SetTeamMemberForm<'_>>,
+) -> Custom<Json<ApiResponse<TeamMemberResponse>>> {
+    if let Err(_e) = form.validate() {
+        return Custom(Status::BadRequest, Json(ApiResponse::error("Bad request")));
+    }
+
+    let role = match TeamRole::parse(form.role) {
+        Some(role) => role,
+        None => {
+            return Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error(&format!("Unknown role: {}", form.role))),
+            );
+        }
+    };
+
+    let team_id = match parse_id(&team_id, "Invalid team ID") {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    if let Err(response) = require_team_role(connection, team_id, user.0.id, TeamRole::Maintainer) {
+        return response;
+    }
+
+    let member_user_id = match user::table
+        .filter(user::username.eq(form.username))
+        .select(user::id)
+        .first::<Uuid>(connection)
+        .optional()
+    {
+        Ok(Some(id)) => id,
+        Ok(None) => {
+            return Custom(Status::NotFound, Json(ApiResponse::error("User not found")));
+        }
+        Err(_e) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Database error")),
+            );
+        }
+    };
+
+    let result = connection.transaction(|connection| {
+        let previous_role = team_role(connection, team_id, member_user_id)?;
+
+        if previous_role == Some(TeamRole::Maintainer)
+            && role != TeamRole::Maintainer
+            && count_maintainers(connection, team_id)? <= 1
+        {
+            return Ok(None);
+        }
+
+        let member = diesel::insert_into(team_member::table)
+            .values(&InsertableTeamMemberModel {
+                team_id,
+                user_id: member_user_id,
+                role: role.as_str(),
+            })
+            .on_conflict((team_member::team_id, team_member::user_id))
+            .do_update()
+            .set((
+                team_member::role.eq(role.as_str()),
+                team_member::updated_at.eq(diesel::dsl::now),
+            ))
+            .returning(TeamMemberModel::as_returning())
+            .get_result::<TeamMemberModel>(connection)?;
+
+        Ok::<_, diesel::result::Error>(Some((previous_role, member)))
+    });
+
+    match result {
+        Ok(Some((previous_role, member))) => {
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_TEAM_MEMBER_SET,
+                Some(AuditTarget::new(AUDIT_TARGET_TEAM, team_id)),
+                AuditChanges::diff(
+                    previous_role
+                        .map(|r| serde_json::json!({ "username": form.username, "role": r.as_str() }))
+                        .as_ref(),
+                    Some(&serde_json::json!({ "username": form.username, "role": role.as_str() })),
+                ),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "Team member saved",
+                    TeamMemberResponse {
+                        member: PublicTeamMember {
+                            user_id: member.user_id,
+                            username: form.username.to_string(),
+                            role: member.role,
+                            created_at: member.created_at,
+                        },
+                    },
+                )),
+            )
+        }
+        Ok(None) => Custom(
+            Status::Conflict,
+            Json(ApiResponse::error("A team needs at least one maintainer")),
+        ),
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to save team member")),
+        ),
+    }
+}
+
+fn count_maintainers(connection: &mut PgConnection, team_id: Uuid) -> QueryResult<i64> {
+    team_member::table
+        .filter(team_member::team_id.eq(team_id))
+        .filter(team_member::role.eq(TeamRole::Maintainer.as_str()))
+        .count()
+        .get_result::<i64>(connection)
+}
+
+/// Maintainers can remove anyone, every member can leave on their own.
+#[delete("/team/<team_id>/members/<member_user_id>")]
+pub fn remove_team_member(
+    db: &State<DbConnection>,
+    user: AuthGuard,
+    client: ClientInfo,
+    team_id: String,
+    member_user_id: String,
+) -> Custom<Json<ApiResponse<()>>> {
+    let team_id = match parse_id(&team_id, "Invalid team ID") {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+    let member_user_id = match parse_id(&member_user_id, "Invalid user ID") {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let required = if member_user_id == user.0.id {
+        TeamRole::Viewer
+    } else {
+        TeamRole::Maintainer
+    };
+    if let Err(response) = require_team_role(connection, team_id, user.0.id, required) {
+        return response;
+    }
+
+    let result = connection.transaction(|connection| {
+        let previous_role = team_role(connection, team_id, member_user_id)?;
+
+        if previous_role == Some(TeamRole::Maintainer)
+            && count_maintainers(connection, team_id)? <= 1
+        {
+            return Ok(Err((
+                Status::Conflict,
+                "A team needs at least one maintainer",
+            )));
+        }
+
+        let removed = diesel::delete(
+            team_member::table
+                .filter(team_member::team_id.eq(team_id))
+                .filter(team_member::user_id.eq(member_user_id)),
+        )
+        .execute(connection)?;
+
+        Ok::<_, diesel::result::Error>(match (removed, previous_role) {
+            (0, _) | (_, None) => Err((Status::NotFound, "Team member not found")),
+            (_, Some(role)) => Ok(role),
+        })
+    });
+
+    match result {
+        Ok(Ok(previous_role)) => {
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_TEAM_MEMBER_REMOVE,
+                Some(AuditTarget::new(AUDIT_TARGET_TEAM, team_id)),
+                AuditChanges::diff(
+                    Some(&serde_json::json!({
+                        "userId": member_user_id,
+                        "role": previous_role.as_str(),
+                    })),
+                    None,
+                ),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success("Team member removed", ())),
+            )
+        }
+        Ok(Err((status, message))) => Custom(status, Json(ApiResponse::error(message))),
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to remove team member")),
+        ),
+    }
+}
@@ -0,0 +1,230 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::db::DbConnection;
+use crate::middlewares::auth::{API_TOKEN_PREFIX, ApiTokenScope, AuthGuard};
+use crate::middlewares::client::ClientInfo;
+use crate::models::{ApiTokenModel, InsertableApiTokenModel, PublicApiToken};
+use crate::schema::api_token;
+use crate::utils::audit::{
+    AUDIT_API_TOKEN_CREATE, AUDIT_API_TOKEN_REVOKE, AUDIT_TARGET_API_TOKEN, AuditChanges,
+    AuditTarget, record_audit_event,
+};
+use crate::utils::crypto::{generate_random_string, hash_token};
+use crate::utils::response::ApiResponse;
+
+#[derive(Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiTokenForm {
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Token name should be between 1 and 100 characters long"
+    ))]
+    pub name: String,
+
+    #[validate(length(min = 1, message = "At least one scope is required"))]
+    pub scopes: Vec<String>,
+
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiTokenResponse {
+    /// Raw token value. It is only returned here and cannot be recovered later.
+    pub token: String,
+    pub api_token: PublicApiToken,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetApiTokensResponse {
+    pub api_tokens: Vec<PublicApiToken>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevokeApiTokenResponse {
+    pub api_token: PublicApiToken,
+}
+
+#[post("/user/tokens", format = "application/json", data = "<form>")]
+pub fn create_api_token(
+    db: &State<DbConnection>,
+    user: AuthGuard,
+    client: ClientInfo,
+    form: Json<CreateApiTokenForm>,
+) -> Custom<Json<ApiResponse<CreateApiTokenResponse>>> {
+    if let Err(_e) = form.validate() {
+        return Custom(Status::BadRequest, Json(ApiResponse::error("Bad request")));
+    }
+
+    let mut scopes = Vec::new();
+    for requested in &form.scopes {
+        match ApiTokenScope::parse(requested) {
+            Some(scope) if !scopes.contains(&scope) => scopes.push(scope),
+            Some(_) => {}
+            None => {
+                return Custom(
+                    Status::BadRequest,
+                    Json(ApiResponse::error(&format!("Unknown scope: {}", requested))),
+                );
+            }
+        }
+    }
+
+    if form.expires_at.is_some_and(|t| t <= Utc::now()) {
+        return Custom(
+            Status::BadRequest,
+            Json(ApiResponse::error("Expiry must be in the future")),
+        );
+    }
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let secret = generate_random_string(40);
+    let token = format!("{}{}", API_TOKEN_PREFIX, secret);
+    let token_prefix = format!("{}{}", API_TOKEN_PREFIX, &secret[..8]);
+    let scopes = scopes
+        .iter()
+        .map(ApiTokenScope::as_str)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let new_token = InsertableApiTokenModel {
+        user_id: user.0.id,
+        name: form.name.as_str(),
+        token_prefix: &token_prefix,
+        token_hash: &hash_token(&token),
+        scopes: &scopes,
+        expires_at: form.expires_at,
+    };
+
+    match diesel::insert_into(api_token::table)
+        .values(&new_token)
+        .returning(ApiTokenModel::as_returning())
+        .get_result::<ApiTokenModel>(connection)
+    {
+        Ok(inserted) => {
+            let created = PublicApiToken::from(inserted);
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_API_TOKEN_CREATE,
+                Some(AuditTarget::new(AUDIT_TARGET_API_TOKEN, created.id)),
+                AuditChanges::diff(None, Some(&created)),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "API token created successfully",
+                    CreateApiTokenResponse {
+                        token,
+                        api_token: created,
+                    },
+                )),
+            )
+        }
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to create API token")),
+        ),
+    }
+}
+
+#[get("/user/tokens")]
+pub fn get_api_tokens(
+    db: &State<DbConnection>,
+    user: AuthGuard,
+) -> Custom<Json<ApiResponse<GetApiTokensResponse>>> {
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    match api_token::table
+        .filter(api_token::user_id.eq(user.0.id))
+        .order(api_token::created_at.desc())
+        .select(ApiTokenModel::as_select())
+        .load::<ApiTokenModel>(connection)
+    {
+        Ok(tokens) => Custom(
+            Status::Ok,
+            Json(ApiResponse::success(
+                "API tokens fetched successfully",
+                GetApiTokensResponse {
+                    api_tokens: tokens.into_iter().map(PublicApiToken::from).collect(),
+                },
+            )),
+        ),
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to fetch API tokens")),
+        ),
+    }
+}
+
+#[delete("/user/tokens/<token_id>")]
+pub fn revoke_api_token(
+    db: &State<DbConnection>,
+    user: AuthGuard,
+    client: ClientInfo,
+    token_id: String,
+) -> Custom<Json<ApiResponse<RevokeApiTokenResponse>>> {
+    let parsed_id = match Uuid::parse_str(&token_id) {
+        Ok(uuid) => uuid,
+        Err(_) => {
+            return Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error("Invalid API token ID")),
+            );
+        }
+    };
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    match diesel::delete(
+        api_token::table
+            .filter(api_token::id.eq(parsed_id))
+            .filter(api_token::user_id.eq(user.0.id)),
+    )
+    .returning(ApiTokenModel::as_returning())
+    .get_result::<ApiTokenModel>(connection)
+    .optional()
+    {
+        Ok(Some(revoked)) => {
+            let revoked = PublicApiToken::from(revoked);
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_API_TOKEN_REVOKE,
+                Some(AuditTarget::new(AUDIT_TARGET_API_TOKEN, revoked.id)),
+                AuditChanges::diff(Some(&revoked), None),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "API token revoked",
+                    RevokeApiTokenResponse { api_token: revoked },
+                )),
+            )
+        }
+        Ok(None) => Custom(
+            Status::NotFound,
+            Json(ApiResponse::error("API token not found")),
+        ),
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to revoke API token")),
+        ),
+    }
+}
@@ -0,0 +1,568 @@
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use rand::Rng;
+use rocket::State;
+use rocket::http::{CookieJar, Status};
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::config::Config;
+use crate::db::DbConnection;
+use crate::middlewares::auth::AuthGuard;
+use crate::middlewares::client::ClientInfo;
+use crate::models::{
+    InsertableLoginChallengeModel, InsertableRecoveryCodeModel, LoginChallengeModel, UserModel,
+};
+use crate::routes::user::{
+    MAX_PASSWORD_LENGTH, MIN_PASSWORD_LENGTH, UserLoginResponse, issue_login_session,
+};
+use crate::schema::{login_challenge, recovery_code, user};
+use crate::utils::audit::{
+    AUDIT_TARGET_USER, AUDIT_USER_RECOVERY_CODES_REGENERATE, AUDIT_USER_TOTP_DISABLE,
+    AUDIT_USER_TOTP_ENABLE, AuditChanges, AuditTarget, record_audit_event,
+};
+use crate::utils::crypto::{generate_random_string, hash_token, verify_password};
+use crate::utils::login_throttle::{
+    FAILURE_BAD_TOTP, begin_login_attempt, discard_login_attempt, record_login_attempt,
+};
+use crate::utils::response::ApiResponse;
+use crate::utils::settings::{SETTING_REQUIRE_TOTP, get_bool_setting};
+use crate::utils::totp::{generate_totp_secret, totp_otpauth_uri, verify_totp};
+
+const LOGIN_CHALLENGE_TTL_SECONDS: i64 = 5 * 60;
+const LOGIN_CHALLENGE_MAX_ATTEMPTS: i32 = 5;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+#[derive(Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpLoginForm<'r> {
+    #[validate(length(min = 1, max = 128))]
+    challenge_token: &'r str,
+    #[validate(length(min = 6, max = 32))]
+    code: &'r str,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct TotpCodeForm<'r> {
+    #[validate(length(min = 6, max = 32))]
+    code: &'r str,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct TotpDisableForm<'r> {
+    /// Required for accounts with a local password, SSO and LDAP accounts only confirm with
+    /// the code.
+    #[validate(length(min = MIN_PASSWORD_LENGTH, max = MAX_PASSWORD_LENGTH))]
+    password: Option<&'r str>,
+    #[validate(length(min = 6, max = 32))]
+    code: &'r str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpEnrollResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpRecoveryCodesResponse {
+    /// Single-use codes, only returned once.
+    pub recovery_codes: Vec<String>,
+}
+
+pub fn create_login_challenge(connection: &mut PgConnection, user_id: Uuid) -> QueryResult<String> {
+    let token = generate_random_string(64);
+
+    diesel::delete(login_challenge::table.filter(login_challenge::expires_at.le(Utc::now())))
+        .execute(connection)?;
+
+    diesel::insert_into(login_challenge::table)
+        .values(&InsertableLoginChallengeModel {
+            user_id,
+            token_hash: &hash_token(&token),
+            expires_at: Utc::now() + Duration::seconds(LOGIN_CHALLENGE_TTL_SECONDS),
+        })
+        .execute(connection)?;
+
+    Ok(token)
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Replaces all recovery codes of the user and returns the new ones in plain text.
+fn regenerate_recovery_codes(
+    connection: &mut PgConnection,
+    user_id: Uuid,
+) -> QueryResult<Vec<String>> {
+    const CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    let mut rng = rand::rng();
+
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let raw: String = (0..10)
+                .map(|_| CHARSET[rng.random_range(0..CHARSET.len())] as char)
+                .collect();
+            format!("{}-{}", &raw[..5], &raw[5..])
+        })
+        .collect();
+
+    connection.transaction(|connection| {
+        diesel::delete(recovery_code::table.filter(recovery_code::user_id.eq(user_id)))
+            .execute(connection)?;
+
+        let hashes: Vec<String> = codes
+            .iter()
+            .map(|c| hash_token(&normalize_recovery_code(c)))
+            .collect();
+        let rows: Vec<InsertableRecoveryCodeModel> = hashes
+            .iter()
+            .map(|code_hash| InsertableRecoveryCodeModel { user_id, code_hash })
+            .collect();
+
+        diesel::insert_into(recovery_code::table)
+            .values(&rows)
+            .execute(connection)
+    })?;
+
+    Ok(codes)
+}
+
+/// Accepts either a current TOTP code that was not used before or an unused recovery code.
+fn verify_second_factor(
+    connection: &mut PgConnection,
+    dbusr: &UserModel,
+    code: &str,
+) -> QueryResult<bool> {
+    if let Some(secret) = dbusr.totp_secret.as_deref()
+        && let Some(step) = verify_totp(secret, code, Utc::now().timestamp())
+    {
+        // claiming the step in a single statement keeps concurrent requests from both using it
+        let claimed = diesel::update(
+            user::table.filter(user::id.eq(dbusr.id)).filter(
+                user::totp_last_used_step
+                    .is_null()
+                    .or(user::totp_last_used_step.lt(step)),
+            ),
+        )
+        .set(user::totp_last_used_step.eq(step))
+        .execute(connection)?;
+
+        return Ok(claimed > 0);
+    }
+
+    let used = diesel::update(
+        recovery_code::table
+            .filter(recovery_code::user_id.eq(dbusr.id))
+            .filter(recovery_code::code_hash.eq(hash_token(&normalize_recovery_code(code))))
+            .filter(recovery_code::used_at.is_null()),
+    )
+    .set(recovery_code::used_at.eq(Utc::now()))
+    .execute(connection)?;
+
+    Ok(used > 0)
+}
+
+#[post("/user/login/totp", format = "application/json", data = "<form>")]
+pub fn login_totp(
+    db: &State<DbConnection>,
+    config: &State<Config>,
+    cookie_jar: &CookieJar<'_>,
+    client: ClientInfo,
+    form: Json<TotpLoginForm<'_>>,
+) -> Custom<Json<ApiResponse<UserLoginResponse>>> {
+    if let Err(_e) = form.validate() {
+        return Custom(Status::BadRequest, Json(ApiResponse::error("Bad request")));
+    }
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    // every guess uses up one of the challenge's attempts before the code is checked, counted
+    // in SQL so parallel guesses cannot get past the limit
+    let challenge_result = diesel::update(
+        login_challenge::table
+            .filter(login_challenge::token_hash.eq(hash_token(form.challenge_token)))
+            .filter(login_challenge::expires_at.gt(Utc::now()))
+            .filter(login_challenge::attempts.lt(LOGIN_CHALLENGE_MAX_ATTEMPTS)),
+    )
+    .set(login_challenge::attempts.eq(login_challenge::attempts + 1))
+    .returning(LoginChallengeModel::as_returning())
+    .get_result::<LoginChallengeModel>(connection)
+    .optional()
+    .and_then(|challenge| match challenge {
+        Some(challenge) => user::table
+            .filter(user::id.eq(challenge.user_id))
+            .select(UserModel::as_select())
+            .first::<UserModel>(connection)
+            .map(|dbusr| Some((challenge, dbusr))),
+        None => Ok(None),
+    });
+
+    let (challenge, dbusr) = match challenge_result {
+        Ok(Some(found)) => found,
+        Ok(None) => {
+            return Custom(
+                Status::Unauthorized,
+                Json(ApiResponse::error("Invalid or expired login challenge")),
+            );
+        }
+        Err(_e) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Database error")),
+            );
+        }
+    };
+
+    let attempt_id = match begin_login_attempt(connection, &config.login, &dbusr.username, &client)
+    {
+        Ok(throttle) => match throttle.attempt_id {
+            Some(attempt_id) => attempt_id,
+            None => {
+                return Custom(
+                    Status::TooManyRequests,
+                    Json(ApiResponse::error(
+                        "Too many failed login attempts. Try again later",
+                    )),
+                );
+            }
+        },
+        Err(_e) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Database error")),
+            );
+        }
+    };
+
+    let response = match verify_second_factor(connection, &dbusr, form.code) {
+        Ok(true) => {
+            let _ =
+                diesel::delete(login_challenge::table.filter(login_challenge::id.eq(challenge.id)))
+                    .execute(connection);
+
+            issue_login_session(connection, cookie_jar, &client, &config.session, dbusr)
+        }
+        Ok(false) => {
+            let _ =
+                record_login_attempt(connection, &dbusr.username, &client, Some(FAILURE_BAD_TOTP));
+
+            // a challenge only allows a handful of guesses, afterwards the password is needed again
+            if challenge.attempts >= LOGIN_CHALLENGE_MAX_ATTEMPTS {
+                let _ = diesel::delete(
+                    login_challenge::table.filter(login_challenge::id.eq(challenge.id)),
+                )
+                .execute(connection);
+            }
+
+            Custom(
+                Status::Unauthorized,
+                Json(ApiResponse::error("Invalid two-factor code")),
+            )
+        }
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Database error")),
+        ),
+    };
+
+    // the outcome is recorded by now
+    let _ = discard_login_attempt(connection, attempt_id);
+    response
+}
+
+#[post("/user/totp/enroll")]
+pub fn enroll_totp(
+    db: &State<DbConnection>,
+    user: AuthGuard,
+) -> Custom<Json<ApiResponse<TotpEnrollResponse>>> {
+    if user.0.totp_enabled {
+        return Custom(
+            Status::Conflict,
+            Json(ApiResponse::error(
+                "Two-factor authentication is already enabled",
+            )),
+        );
+    }
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let secret = generate_totp_secret();
+
+    if diesel::update(user::table.filter(user::id.eq(user.0.id)))
+        .set((
+            user::totp_secret.eq(&secret),
+            user::totp_last_used_step.eq(None::<i64>),
+        ))
+        .execute(connection)
+        .is_err()
+    {
+        return Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to start enrollment")),
+        );
+    }
+
+    Custom(
+        Status::Ok,
+        Json(ApiResponse::success(
+            "Scan the URI with an authenticator app and confirm with a code",
+            TotpEnrollResponse {
+                otpauth_uri: totp_otpauth_uri(&secret, &user.0.username),
+                secret,
+            },
+        )),
+    )
+}
+
+#[post("/user/totp/confirm", format = "application/json", data = "<form>")]
+pub fn confirm_totp(
+    db: &State<DbConnection>,
+    user: AuthGuard,
+    client: ClientInfo,
+    form: Json<TotpCodeForm<'_>>,
+) -> Custom<Json<ApiResponse<TotpRecoveryCodesResponse>>> {
+    if let Err(_e) = form.validate() {
+        return Custom(Status::BadRequest, Json(ApiResponse::error("Bad request")));
+    }
+
+    if user.0.totp_enabled {
+        return Custom(
+            Status::Conflict,
+            Json(ApiResponse::error(
+                "Two-factor authentication is already enabled",
+            )),
+        );
+    }
+
+    let step = match user
+        .0
+        .totp_secret
+        .as_deref()
+        .and_then(|secret| verify_totp(secret, form.code, Utc::now().timestamp()))
+    {
+        Some(step) => step,
+        None => {
+            return Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error("Invalid two-factor code")),
+            );
+        }
+    };
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let result = connection.transaction(|connection| {
+        diesel::update(user::table.filter(user::id.eq(user.0.id)))
+            .set((
+                user::totp_enabled.eq(true),
+                user::totp_last_used_step.eq(step),
+            ))
+            .execute(connection)?;
+
+        regenerate_recovery_codes(connection, user.0.id)
+    });
+
+    match result {
+        Ok(recovery_codes) => {
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_USER_TOTP_ENABLE,
+                Some(AuditTarget::new(AUDIT_TARGET_USER, user.0.id)),
+                AuditChanges::none(),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "Two-factor authentication enabled",
+                    TotpRecoveryCodesResponse { recovery_codes },
+                )),
+            )
+        }
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error(
+                "Failed to enable two-factor authentication",
+            )),
+        ),
+    }
+}
+
+#[post(
+    "/user/totp/recovery-codes",
+    format = "application/json",
+    data = "<form>"
+)]
+pub fn regenerate_totp_recovery_codes(
+    db: &State<DbConnection>,
+    user: AuthGuard,
+    client: ClientInfo,
+    form: Json<TotpCodeForm<'_>>,
+) -> Custom<Json<ApiResponse<TotpRecoveryCodesResponse>>> {
+    if let Err(_e) = form.validate() {
+        return Custom(Status::BadRequest, Json(ApiResponse::error("Bad request")));
+    }
+
+    if !user.0.totp_enabled {
+        return Custom(
+            Status::Conflict,
+            Json(ApiResponse::error(
+                "Two-factor authentication is not enabled",
+            )),
+        );
+    }
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    match verify_second_factor(connection, &user.0, form.code) {
+        Ok(true) => {}
+        Ok(false) => {
+            return Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error("Invalid two-factor code")),
+            );
+        }
+        Err(_e) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Database error")),
+            );
+        }
+    }
+
+    match regenerate_recovery_codes(connection, user.0.id) {
+        Ok(recovery_codes) => {
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_USER_RECOVERY_CODES_REGENERATE,
+                Some(AuditTarget::new(AUDIT_TARGET_USER, user.0.id)),
+                AuditChanges::none(),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "Recovery codes regenerated",
+                    TotpRecoveryCodesResponse { recovery_codes },
+                )),
+            )
+        }
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to regenerate recovery codes")),
+        ),
+    }
+}
+
+#[post("/user/totp/disable", format = "application/json", data = "<form>")]
+pub fn disable_totp(
+    db: &State<DbConnection>,
+    user: AuthGuard,
+    client: ClientInfo,
+    form: Json<TotpDisableForm<'_>>,
+) -> Custom<Json<ApiResponse<()>>> {
+    if let Err(_e) = form.validate() {
+        return Custom(Status::BadRequest, Json(ApiResponse::error("Bad request")));
+    }
+
+    if !user.0.totp_enabled {
+        return Custom(
+            Status::Conflict,
+            Json(ApiResponse::error(
+                "Two-factor authentication is not enabled",
+            )),
+        );
+    }
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    match get_bool_setting(connection, SETTING_REQUIRE_TOTP) {
+        Ok(false) => {}
+        Ok(true) => {
+            return Custom(
+                Status::Conflict,
+                Json(ApiResponse::error(
+                    "Two-factor authentication is enforced by an administrator",
+                )),
+            );
+        }
+        Err(_e) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Database error")),
+            );
+        }
+    }
+
+    // accounts without a local password re-authenticate with the second factor alone
+    let password_matches = match user.0.password_hash.as_deref() {
+        Some(h) => form.password.is_some_and(|p| verify_password(h, p)),
+        None => true,
+    };
+
+    if !password_matches
+        || !matches!(
+            verify_second_factor(connection, &user.0, form.code),
+            Ok(true)
+        )
+    {
+        return Custom(
+            Status::Forbidden,
+            Json(ApiResponse::error("Invalid password or two-factor code")),
+        );
+    }
+
+    let result = connection.transaction(|connection| {
+        diesel::delete(recovery_code::table.filter(recovery_code::user_id.eq(user.0.id)))
+            .execute(connection)?;
+
+        diesel::update(user::table.filter(user::id.eq(user.0.id)))
+            .set((
+                user::totp_enabled.eq(false),
+                user::totp_secret.eq(None::<String>),
+                user::totp_last_used_step.eq(None::<i64>),
+            ))
+            .execute(connection)
+    });
+
+    match result {
+        Ok(_) => {
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_USER_TOTP_DISABLE,
+                Some(AuditTarget::new(AUDIT_TARGET_USER, user.0.id)),
+                AuditChanges::none(),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "Two-factor authentication disabled",
+                    (),
+                )),
+            )
+        }
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error(
+                "Failed to disable two-factor authentication",
+            )),
+        ),
+    }
+}
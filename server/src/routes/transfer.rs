@@ -0,0 +1,539 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::db::DbConnection;
+use crate::middlewares::auth::{AuthGuard, scope};
+use crate::middlewares::client::ClientInfo;
+use crate::models::{InsertableRepositoryTransferModel, RepositoryModel, RepositoryTransferModel};
+use crate::routes::repository::find_repository_with_role;
+use crate::schema::{repository, repository_transfer, team, team_member, user};
+use crate::utils::access::{TeamRole, accessible_repositories, team_role};
+use crate::utils::audit::{
+    AUDIT_REPOSITORY_TRANSFER_ACCEPT, AUDIT_REPOSITORY_TRANSFER_CANCEL,
+    AUDIT_REPOSITORY_TRANSFER_DECLINE, AUDIT_REPOSITORY_TRANSFER_REQUEST, AUDIT_TARGET_REPOSITORY,
+    AuditChanges, AuditTarget, record_audit_event,
+};
+use crate::utils::response::ApiResponse;
+
+const TRANSFER_PENDING: &str = "pending";
+const TRANSFER_ACCEPTED: &str = "accepted";
+const TRANSFER_DECLINED: &str = "declined";
+const TRANSFER_CANCELLED: &str = "cancelled";
+
+#[derive(Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestTransferForm {
+    #[validate(length(min = 3, max = 32))]
+    pub username: Option<String>,
+    pub team_id: Option<Uuid>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferResponse {
+    pub transfer: RepositoryTransferModel,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingTransfer {
+    pub transfer: RepositoryTransferModel,
+    pub repository_name: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTransfersResponse {
+    /// Waiting for the current user, or a team they maintain, to accept or decline.
+    pub incoming: Vec<PendingTransfer>,
+    /// Requested for repositories the current user maintains.
+    pub outgoing: Vec<PendingTransfer>,
+}
+
+/// Owner fields as recorded in the audit log.
+fn owner_snapshot(user_id: Option<Uuid>, team_id: Option<Uuid>) -> serde_json::Value {
+    json!({ "userId": user_id, "teamId": team_id })
+}
+
+/// Users accept transfers addressed to them, team maintainers those addressed to their team.
+fn is_transfer_recipient(
+    connection: &mut PgConnection,
+    transfer: &RepositoryTransferModel,
+    user_id: Uuid,
+) -> QueryResult<bool> {
+    if transfer.to_user_id == Some(user_id) {
+        return Ok(true);
+    }
+
+    match transfer.to_team_id {
+        Some(team_id) => Ok(team_role(connection, team_id, user_id)? == Some(TeamRole::Maintainer)),
+        None => Ok(false),
+    }
+}
+
+fn find_pending_transfer<T>(
+    connection: &mut PgConnection,
+    transfer_id: &str,
+) -> Result<RepositoryTransferModel, Custom<Json<ApiResponse<T>>>> {
+    let parsed_id = match Uuid::parse_str(transfer_id) {
+        Ok(uuid) => uuid,
+        Err(_) => {
+            return Err(Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error("Invalid transfer ID")),
+            ));
+        }
+    };
+
+    match repository_transfer::table
+        .filter(repository_transfer::id.eq(parsed_id))
+        .filter(repository_transfer::status.eq(TRANSFER_PENDING))
+        .select(RepositoryTransferModel::as_select())
+        .first::<RepositoryTransferModel>(connection)
+        .optional()
+    {
+        Ok(Some(transfer)) => Ok(transfer),
+        Ok(None) => Err(Custom(
+            Status::NotFound,
+            Json(ApiResponse::error("Pending transfer not found")),
+        )),
+        Err(_e) => Err(Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to fetch transfer")),
+        )),
+    }
+}
+
+fn resolve_transfer(
+    connection: &mut PgConnection,
+    transfer_id: Uuid,
+    status: &str,
+    resolved_by: Uuid,
+) -> QueryResult<Option<RepositoryTransferModel>> {
+    diesel::update(
+        repository_transfer::table
+            .filter(repository_transfer::id.eq(transfer_id))
+            .filter(repository_transfer::status.eq(TRANSFER_PENDING)),
+    )
+    .set((
+        repository_transfer::status.eq(status),
+        repository_transfer::resolved_by.eq(resolved_by),
+        repository_transfer::resolved_at.eq(Utc::now()),
+    ))
+    .returning(RepositoryTransferModel::as_returning())
+    .get_result::<RepositoryTransferModel>(connection)
+    .optional()
+}
+
+#[post(
+    "/repository/<repo_id>/transfer",
+    format = "application/json",
+    data = "<form>"
+)]
+pub fn request_repository_transfer(
+    db: &State<DbConnection>,
+    user: AuthGuard<scope::RepositoriesWrite>,
+    client: ClientInfo,
+    repo_id: String,
+    form: Json<RequestTransferForm>,
+) -> Custom<Json<ApiResponse<TransferResponse>>> {
+    if let Err(_e) = form.validate() {
+        return Custom(Status::BadRequest, Json(ApiResponse::error("Bad request")));
+    }
+
+    let parsed_id = match Uuid::parse_str(&repo_id) {
+        Ok(uuid) => uuid,
+        Err(_) => {
+            return Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error("Invalid repository ID")),
+            );
+        }
+    };
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let repo =
+        match find_repository_with_role(connection, user.0.id, parsed_id, TeamRole::Maintainer) {
+            Ok(repo) => repo,
+            Err(response) => return response,
+        };
+
+    let recipient = match (form.username.as_deref(), form.team_id) {
+        (Some(username), None) => user::table
+            .filter(user::username.eq(username))
+            .select(user::id)
+            .first::<Uuid>(connection)
+            .optional()
+            .map(|found| found.map(|id| (Some(id), None))),
+        (None, Some(team_id)) => team::table
+            .filter(team::id.eq(team_id))
+            .select(team::id)
+            .first::<Uuid>(connection)
+            .optional()
+            .map(|found| found.map(|id| (None, Some(id)))),
+        _ => {
+            return Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error("Specify either a username or a team ID")),
+            );
+        }
+    };
+
+    let (to_user_id, to_team_id) = match recipient {
+        Ok(Some(recipient)) => recipient,
+        Ok(None) => {
+            return Custom(
+                Status::NotFound,
+                Json(ApiResponse::error("Recipient not found")),
+            );
+        }
+        Err(_e) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Database error")),
+            );
+        }
+    };
+
+    if (to_user_id, to_team_id) == (repo.user_id, repo.team_id) {
+        return Custom(
+            Status::BadRequest,
+            Json(ApiResponse::error(
+                "Repository already belongs to this owner",
+            )),
+        );
+    }
+
+    match diesel::insert_into(repository_transfer::table)
+        .values(&InsertableRepositoryTransferModel {
+            repository_id: repo.id,
+            requested_by: Some(user.0.id),
+            from_user_id: repo.user_id,
+            from_team_id: repo.team_id,
+            to_user_id,
+            to_team_id,
+        })
+        .returning(RepositoryTransferModel::as_returning())
+        .get_result::<RepositoryTransferModel>(connection)
+    {
+        Ok(transfer) => {
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_REPOSITORY_TRANSFER_REQUEST,
+                Some(AuditTarget::new(AUDIT_TARGET_REPOSITORY, repo.id)),
+                AuditChanges::diff(
+                    Some(&owner_snapshot(repo.user_id, repo.team_id)),
+                    Some(&owner_snapshot(to_user_id, to_team_id)),
+                ),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "Transfer requested, waiting for the recipient to accept",
+                    TransferResponse { transfer },
+                )),
+            )
+        }
+        Err(diesel::result::Error::DatabaseError(
+            diesel::result::DatabaseErrorKind::UniqueViolation,
+            _,
+        )) => Custom(
+            Status::Conflict,
+            Json(ApiResponse::error(
+                "A transfer is already pending for this repository",
+            )),
+        ),
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to request transfer")),
+        ),
+    }
+}
+
+#[get("/repository/transfers")]
+pub fn get_repository_transfers(
+    db: &State<DbConnection>,
+    user: AuthGuard<scope::Read>,
+) -> Custom<Json<ApiResponse<GetTransfersResponse>>> {
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let maintained_teams = team_member::table
+        .filter(team_member::user_id.eq(user.0.id))
+        .filter(team_member::role.eq(TeamRole::Maintainer.as_str()))
+        .select(team_member::team_id.nullable());
+
+    let incoming = repository_transfer::table
+        .inner_join(repository::table)
+        .filter(repository_transfer::status.eq(TRANSFER_PENDING))
+        .filter(
+            repository_transfer::to_user_id
+                .eq(user.0.id)
+                .or(repository_transfer::to_team_id.eq_any(maintained_teams)),
+        )
+        .order(repository_transfer::created_at.desc())
+        .select((RepositoryTransferModel::as_select(), repository::name))
+        .load::<(RepositoryTransferModel, String)>(connection);
+
+    let outgoing = repository_transfer::table
+        .inner_join(repository::table)
+        .filter(repository_transfer::status.eq(TRANSFER_PENDING))
+        .filter(repository_transfer::repository_id.eq_any(
+            accessible_repositories(user.0.id, TeamRole::Maintainer).select(repository::id),
+        ))
+        .order(repository_transfer::created_at.desc())
+        .select((RepositoryTransferModel::as_select(), repository::name))
+        .load::<(RepositoryTransferModel, String)>(connection);
+
+    let to_pending = |rows: Vec<(RepositoryTransferModel, String)>| {
+        rows.into_iter()
+            .map(|(transfer, repository_name)| PendingTransfer {
+                transfer,
+                repository_name,
+            })
+            .collect()
+    };
+
+    match (incoming, outgoing) {
+        (Ok(incoming), Ok(outgoing)) => Custom(
+            Status::Ok,
+            Json(ApiResponse::success(
+                "Transfers fetched successfully",
+                GetTransfersResponse {
+                    incoming: to_pending(incoming),
+                    outgoing: to_pending(outgoing),
+                },
+            )),
+        ),
+        _ => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to fetch transfers")),
+        ),
+    }
+}
+
+#[post("/repository/transfers/<transfer_id>/accept")]
+pub fn accept_repository_transfer(
+    db: &State<DbConnection>,
+    user: AuthGuard<scope::RepositoriesWrite>,
+    client: ClientInfo,
+    transfer_id: String,
+) -> Custom<Json<ApiResponse<TransferResponse>>> {
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let transfer = match find_pending_transfer(connection, &transfer_id) {
+        Ok(transfer) => transfer,
+        Err(response) => return response,
+    };
+
+    match is_transfer_recipient(connection, &transfer, user.0.id) {
+        Ok(true) => {}
+        Ok(false) => {
+            return Custom(
+                Status::NotFound,
+                Json(ApiResponse::error("Pending transfer not found")),
+            );
+        }
+        Err(_e) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Database error")),
+            );
+        }
+    }
+
+    // logs and credentials stay attached to the repository row, only the owner changes
+    let result = connection.transaction::<_, diesel::result::Error, _>(|connection| {
+        let repo = repository::table
+            .filter(repository::id.eq(transfer.repository_id))
+            .for_update()
+            .first::<RepositoryModel>(connection)?;
+
+        if (repo.user_id, repo.team_id) != (transfer.from_user_id, transfer.from_team_id) {
+            resolve_transfer(connection, transfer.id, TRANSFER_CANCELLED, user.0.id)?;
+            return Ok(None);
+        }
+
+        diesel::update(repository::table.filter(repository::id.eq(repo.id)))
+            .set((
+                repository::user_id.eq(transfer.to_user_id),
+                repository::team_id.eq(transfer.to_team_id),
+                repository::updated_at.eq(Utc::now()),
+            ))
+            .execute(connection)?;
+
+        resolve_transfer(connection, transfer.id, TRANSFER_ACCEPTED, user.0.id)
+    });
+
+    match result {
+        Ok(Some(accepted)) => {
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_REPOSITORY_TRANSFER_ACCEPT,
+                Some(AuditTarget::new(
+                    AUDIT_TARGET_REPOSITORY,
+                    accepted.repository_id,
+                )),
+                AuditChanges::diff(
+                    Some(&owner_snapshot(
+                        accepted.from_user_id,
+                        accepted.from_team_id,
+                    )),
+                    Some(&owner_snapshot(accepted.to_user_id, accepted.to_team_id)),
+                ),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "Repository transferred",
+                    TransferResponse { transfer: accepted },
+                )),
+            )
+        }
+        Ok(None) => Custom(
+            Status::Conflict,
+            Json(ApiResponse::error(
+                "Repository owner changed since the transfer was requested",
+            )),
+        ),
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to accept transfer")),
+        ),
+    }
+}
+
+#[post("/repository/transfers/<transfer_id>/decline")]
+pub fn decline_repository_transfer(
+    db: &State<DbConnection>,
+    user: AuthGuard<scope::RepositoriesWrite>,
+    client: ClientInfo,
+    transfer_id: String,
+) -> Custom<Json<ApiResponse<TransferResponse>>> {
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let transfer = match find_pending_transfer(connection, &transfer_id) {
+        Ok(transfer) => transfer,
+        Err(response) => return response,
+    };
+
+    match is_transfer_recipient(connection, &transfer, user.0.id) {
+        Ok(true) => {}
+        Ok(false) => {
+            return Custom(
+                Status::NotFound,
+                Json(ApiResponse::error("Pending transfer not found")),
+            );
+        }
+        Err(_e) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Database error")),
+            );
+        }
+    }
+
+    match resolve_transfer(connection, transfer.id, TRANSFER_DECLINED, user.0.id) {
+        Ok(Some(declined)) => {
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_REPOSITORY_TRANSFER_DECLINE,
+                Some(AuditTarget::new(
+                    AUDIT_TARGET_REPOSITORY,
+                    declined.repository_id,
+                )),
+                AuditChanges::none(),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "Transfer declined",
+                    TransferResponse { transfer: declined },
+                )),
+            )
+        }
+        Ok(None) => Custom(
+            Status::NotFound,
+            Json(ApiResponse::error("Pending transfer not found")),
+        ),
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to decline transfer")),
+        ),
+    }
+}
+
+#[delete("/repository/transfers/<transfer_id>")]
+pub fn cancel_repository_transfer(
+    db: &State<DbConnection>,
+    user: AuthGuard<scope::RepositoriesWrite>,
+    client: ClientInfo,
+    transfer_id: String,
+) -> Custom<Json<ApiResponse<TransferResponse>>> {
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let transfer = match find_pending_transfer(connection, &transfer_id) {
+        Ok(transfer) => transfer,
+        Err(response) => return response,
+    };
+
+    if let Err(response) = find_repository_with_role(
+        connection,
+        user.0.id,
+        transfer.repository_id,
+        TeamRole::Maintainer,
+    ) {
+        return response;
+    }
+
+    match resolve_transfer(connection, transfer.id, TRANSFER_CANCELLED, user.0.id) {
+        Ok(Some(cancelled)) => {
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_REPOSITORY_TRANSFER_CANCEL,
+                Some(AuditTarget::new(
+                    AUDIT_TARGET_REPOSITORY,
+                    cancelled.repository_id,
+                )),
+                AuditChanges::none(),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "Transfer cancelled",
+                    TransferResponse {
+                        transfer: cancelled,
+                    },
+                )),
+            )
+        }
+        Ok(None) => Custom(
+            Status::NotFound,
+            Json(ApiResponse::error("Pending transfer not found")),
+        ),
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to cancel transfer")),
+        ),
+    }
+}
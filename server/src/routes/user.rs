@@ -1,6 +1,6 @@
+use chrono::Utc;
 use diesel::prelude::*;
-use diesel::r2d2::{ConnectionManager, PooledConnection};
-use rocket::http::{Cookie, Status};
+use rocket::http::Status;
 use rocket::response::status::Custom;
 use rocket::serde::json::Json;
 use rocket::{State, http::CookieJar};
@@ -8,30 +8,55 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use validator::Validate;
 
+use crate::auth::ldap::{LdapAuthenticator, LdapIdentity};
+use crate::config::{Config, SessionConfig};
 use crate::db::DbConnection;
-use crate::middlewares::auth::AuthGuard;
-use crate::models::{PublicUser, UserModel};
-use crate::schema::user;
-use crate::utils::crypto::{generate_random_string, hash_password, verify_password};
+use crate::middlewares::auth::{AuthGuard, scope};
+use crate::middlewares::client::ClientInfo;
+use crate::models::{PublicSession, PublicUser, SessionModel, UserModel};
+use crate::routes::totp::create_login_challenge;
+use crate::schema::{session, user};
+use crate::utils::audit::{
+    AUDIT_TARGET_SESSION, AUDIT_TARGET_USER, AUDIT_USER_LOGIN, AUDIT_USER_PASSWORD_CHANGE,
+    AUDIT_USER_SESSION_REVOKE, AuditChanges, AuditTarget, record_audit_event,
+};
+use crate::utils::crypto::{hash_password, verify_dummy_password, verify_password};
+use crate::utils::login_throttle::{
+    FAILURE_BAD_PASSWORD, FAILURE_DISABLED, FAILURE_LDAP_ERROR, FAILURE_LDAP_REJECTED,
+    FAILURE_NO_PASSWORD, FAILURE_UNKNOWN_USER, LoginThrottleStatus, begin_login_attempt,
+    discard_login_attempt, record_login_attempt,
+};
 use crate::utils::response::ApiResponse;
+use crate::utils::session::{create_session, remove_session_cookie, set_session_cookie};
+
+/// Limits of local accounts, shared with the `user` admin commands.
+pub const MIN_USERNAME_LENGTH: u64 = 3;
+pub const MAX_USERNAME_LENGTH: u64 = 32;
+pub const MIN_PASSWORD_LENGTH: u64 = 8;
+pub const MAX_PASSWORD_LENGTH: u64 = 50;
 
 #[derive(Deserialize, Validate)]
 pub struct UserLoginForm<'r> {
-    #[validate(length(min = 3, max = 32))]
-    username: &'r str,
-    #[validate(length(min = 8, max = 50))]
-    password: &'r str,
+    #[validate(length(min = MIN_USERNAME_LENGTH, max = MAX_USERNAME_LENGTH))]
+    pub username: &'r str,
+    #[validate(length(min = MIN_PASSWORD_LENGTH, max = MAX_PASSWORD_LENGTH))]
+    pub password: &'r str,
 }
 
 #[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct UserLoginResponse {
-    pub user: PublicUser,
+    /// Unset until the second factor is verified.
+    pub user: Option<PublicUser>,
+    pub totp_required: bool,
+    /// Passed to `/user/login/totp` together with the code when `totp_required` is set.
+    pub challenge_token: Option<String>,
 }
 
 #[derive(Deserialize, Validate)]
 pub struct UserChangePasswordForm<'r> {
-    #[validate(length(min = 8, max = 50))]
-    password: &'r str,
+    #[validate(length(min = MIN_PASSWORD_LENGTH, max = MAX_PASSWORD_LENGTH))]
+    pub password: &'r str,
 }
 
 #[derive(Serialize)]
@@ -44,106 +69,360 @@ pub struct UserMeResponse {
     pub user: PublicUser,
 }
 
+#[derive(Serialize)]
+pub struct UserSessionsResponse {
+    pub sessions: Vec<PublicSession>,
+}
+
+/// Local accounts with a password or an SSO identity are never taken over by a directory entry.
+fn is_local_account(user: &UserModel) -> bool {
+    user.ldap_dn.is_none() && (user.password_hash.is_some() || user.oidc_subject.is_some())
+}
+
+type LoginResponse = Custom<Json<ApiResponse<UserLoginResponse>>>;
+
+/// Login step that failed, with the status and message sent to the client.
+struct LoginError(Status, &'static str);
+
+impl LoginError {
+    fn database() -> Self {
+        LoginError(Status::InternalServerError, "Database error")
+    }
+
+    fn respond(self) -> LoginResponse {
+        Custom(self.0, Json(ApiResponse::error(self.1)))
+    }
+}
+
+/// Outcome of a login whose credentials checked out.
+enum LoginStep {
+    TotpRequired { challenge_token: String },
+    Session { user: UserModel, token: String },
+}
+
+/// Runs Diesel queries and password hashing on the blocking pool instead of the executor.
+async fn run_blocking<T, F>(db: &DbConnection, f: F) -> Result<T, LoginError>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut PgConnection) -> Result<T, LoginError> + Send + 'static,
+{
+    let pool = db.clone();
+
+    rocket::tokio::task::spawn_blocking(move || {
+        let connection = &mut pool.get().expect("Failed to get DB Connection");
+        f(connection)
+    })
+    .await
+    .unwrap_or_else(|_e| Err(LoginError(Status::InternalServerError, "Internal error")))
+}
+
+/// Links the directory entry to a user by DN, then by username, provisioning a new user if allowed.
+fn find_or_provision_ldap_user(
+    connection: &mut PgConnection,
+    auto_provision: bool,
+    username: &str,
+    identity: &LdapIdentity,
+) -> Result<UserModel, &'static str> {
+    connection
+        .transaction::<_, diesel::result::Error, _>(|connection| {
+            let by_dn = user::table
+                .filter(user::ldap_dn.eq(&identity.dn))
+                .select(UserModel::as_select())
+                .first::<UserModel>(connection)
+                .optional()?;
+
+            let existing = match by_dn {
+                Some(found) => Some(found),
+                None => {
+                    let by_username = user::table
+                        .filter(user::username.eq(username))
+                        .select(UserModel::as_select())
+                        .first::<UserModel>(connection)
+                        .optional()?;
+
+                    match by_username {
+                        Some(found) if is_local_account(&found) => {
+                            return Ok(Err(FAILURE_LDAP_REJECTED));
+                        }
+                        other => other,
+                    }
+                }
+            };
+
+            let dbusr = match existing {
+                Some(found) => diesel::update(user::table.filter(user::id.eq(found.id)))
+                    .set((
+                        user::ldap_dn.eq(&identity.dn),
+                        user::is_admin.eq(identity.is_admin.unwrap_or(found.is_admin)),
+                    ))
+                    .returning(UserModel::as_returning())
+                    .get_result::<UserModel>(connection)?,
+                None if auto_provision => diesel::insert_into(user::table)
+                    .values((
+                        user::username.eq(username),
+                        user::ldap_dn.eq(&identity.dn),
+                        user::is_admin.eq(identity.is_admin.unwrap_or(false)),
+                    ))
+                    .returning(UserModel::as_returning())
+                    .get_result::<UserModel>(connection)?,
+                None => return Ok(Err(FAILURE_LDAP_REJECTED)),
+            };
+
+            Ok(Ok(dbusr))
+        })
+        .unwrap_or(Err(FAILURE_LDAP_ERROR))
+}
+
 #[post("/user/login", format = "application/json", data = "<form>")]
-pub fn login(
+pub async fn login(
     db: &State<DbConnection>,
-    cookie_jar: &CookieJar,
+    ldap: &State<Option<LdapAuthenticator>>,
+    config: &State<Config>,
+    cookie_jar: &CookieJar<'_>,
+    client: ClientInfo,
     form: Json<UserLoginForm<'_>>,
-) -> Custom<Json<ApiResponse<UserLoginResponse>>> {
+) -> LoginResponse {
     if let Err(_e) = form.validate() {
         return Custom(Status::BadRequest, Json(ApiResponse::error("Bad request")));
     }
 
-    let connection = &mut db.get().expect("Failed to get DB Connection");
+    let username = form.username.to_string();
+    let password = form.password.to_string();
 
-    let get_user_result = user::table
-        .filter(user::username.eq(form.username))
-        .select(UserModel::as_select())
-        .first::<UserModel>(connection);
-
-    match get_user_result {
-        Ok(user) => {
-            let user_id = user.id;
-            let user_username = user.username.clone();
-            let user_password_hash = user.password_hash.clone();
-
-            if user_username == "admin" && user_password_hash.is_none() {
-                let new_admin_session_token = set_new_session_token(user_id, connection);
-
-                cookie_jar.add(
-                    Cookie::build(("gitmirrors_session_token", new_admin_session_token))
-                        .http_only(true)
-                        .build(),
-                );
-
-                return Custom(
-                    Status::Ok,
-                    Json(ApiResponse::success(
-                        "Login successful",
-                        UserLoginResponse { user: user.into() },
-                    )),
-                );
-            }
+    let throttle = run_blocking(db, {
+        let (username, client, login_config) =
+            (username.clone(), client.clone(), config.login.clone());
+        move |connection| match begin_login_attempt(connection, &login_config, &username, &client) {
+            Ok(LoginThrottleStatus {
+                attempt_id: Some(attempt_id),
+                delay,
+                ..
+            }) => Ok((attempt_id, delay)),
+            Ok(_) => Err(LoginError(
+                Status::TooManyRequests,
+                "Too many failed login attempts. Try again later",
+            )),
+            Err(_e) => Err(LoginError::database()),
+        }
+    })
+    .await;
+    let (attempt_id, delay) = match throttle {
+        Ok(throttle) => throttle,
+        Err(e) => return e.respond(),
+    };
 
-            let database_user_password_hash = match user_password_hash {
-                Some(h) => h,
+    if !delay.is_zero() {
+        rocket::tokio::time::sleep(delay).await;
+    }
+
+    let checked = run_blocking(db, {
+        let (username, password) = (username.clone(), password.clone());
+        move |connection| {
+            let found = user::table
+                .filter(user::username.eq(&username))
+                .select(UserModel::as_select())
+                .first::<UserModel>(connection)
+                .optional()
+                .map_err(|_e| LoginError::database())?;
+
+            // unknown users, missing and wrong passwords all look the same to the client
+            let failure_reason = match &found {
+                Some(user) => match &user.password_hash {
+                    Some(h) if verify_password(h, &password) => None,
+                    Some(_) => Some(FAILURE_BAD_PASSWORD),
+                    None => {
+                        verify_dummy_password(&password);
+                        Some(FAILURE_NO_PASSWORD)
+                    }
+                },
                 None => {
-                    return Custom(
-                        Status::InternalServerError,
-                        Json(ApiResponse::error(
-                            "No password set for non-admin account. Access forbidden",
-                        )),
-                    );
+                    verify_dummy_password(&password);
+                    Some(FAILURE_UNKNOWN_USER)
                 }
             };
 
-            let is_password_match = verify_password(&database_user_password_hash, form.password);
+            Ok((found, failure_reason))
+        }
+    })
+    .await;
+    let (found, failure_reason) = match checked {
+        Ok(checked) => checked,
+        Err(e) => return e.respond(),
+    };
 
-            if is_password_match {
-                let new_admin_session_token = set_new_session_token(user.id, connection);
+    // local passwords are checked first so the break-glass admin works while the directory is down
+    let ldap_result = match (ldap.inner(), &found, failure_reason) {
+        (_, Some(_), None) => None,
+        (Some(_), Some(user), _) if is_local_account(user) => None,
+        (Some(ldap), _, _) => Some(ldap.authenticate(&username, &password).await),
+        (None, _, _) => None,
+    };
+    let (identity, failure_reason) = match ldap_result {
+        Some(Ok(Some(identity))) => (Some(identity), None),
+        Some(Ok(None)) => (None, Some(FAILURE_LDAP_REJECTED)),
+        Some(Err(e)) => {
+            tracing::warn!(error = %e, "LDAP authentication failed");
+            (None, Some(FAILURE_LDAP_ERROR))
+        }
+        None => (None, failure_reason),
+    };
+    let auto_provision = ldap
+        .inner()
+        .as_ref()
+        .is_some_and(|ldap| ldap.config.auto_provision);
+    let session_config = config.session.clone();
 
-                cookie_jar.add(
-                    Cookie::build(("gitmirrors_session_token", new_admin_session_token))
-                        .http_only(true)
-                        .build(),
-                );
+    let step = run_blocking(db, move |connection| {
+        let step = (|| {
+            let (found, failure_reason) = match identity {
+                Some(identity) => {
+                    match find_or_provision_ldap_user(
+                        connection,
+                        auto_provision,
+                        &username,
+                        &identity,
+                    ) {
+                        Ok(user) => (Some(user), None),
+                        Err(reason) => (found, Some(reason)),
+                    }
+                }
+                None => (found, failure_reason),
+            };
+
+            let user = match (found, failure_reason) {
+                (Some(user), None) => user,
+                _ => {
+                    let _ = record_login_attempt(connection, &username, &client, failure_reason);
+
+                    return Err(LoginError(
+                        Status::Unauthorized,
+                        "Invalid username or password",
+                    ));
+                }
+            };
 
-                return Custom(
-                    Status::Ok,
-                    Json(ApiResponse::success(
-                        "Login successful",
-                        UserLoginResponse { user: user.into() },
+            reject_disabled_user(connection, &client, &user)?;
+
+            // the attempt only counts as successful once the second factor is verified as well
+            if user.totp_enabled {
+                return match create_login_challenge(connection, user.id) {
+                    Ok(challenge_token) => Ok(LoginStep::TotpRequired { challenge_token }),
+                    Err(_e) => Err(LoginError(
+                        Status::InternalServerError,
+                        "Failed to create login challenge",
                     )),
-                );
+                };
             }
 
-            Custom(
-                Status::Forbidden,
-                Json(ApiResponse::error("Access forbidden")),
-            )
-        }
-        Err(diesel::result::Error::NotFound) => {
-            Custom(Status::NotFound, Json(ApiResponse::error("User not found")))
-        }
-        Err(_e) => Custom(
-            Status::InternalServerError,
-            Json(ApiResponse::error("Database error")),
+            let (token, user) = open_login_session(connection, &client, &session_config, user)?;
+            Ok(LoginStep::Session { user, token })
+        })();
+        // the outcome is recorded by now, or left to the second factor
+        let _ = discard_login_attempt(connection, attempt_id);
+        step
+    })
+    .await;
+
+    match step {
+        Ok(LoginStep::TotpRequired { challenge_token }) => Custom(
+            Status::Ok,
+            Json(ApiResponse::success(
+                "Two-factor authentication required",
+                UserLoginResponse {
+                    user: None,
+                    totp_required: true,
+                    challenge_token: Some(challenge_token),
+                },
+            )),
         ),
+        Ok(LoginStep::Session { user, token }) => {
+            login_succeeded(cookie_jar, &config.session, token, user)
+        }
+        Err(e) => e.respond(),
     }
 }
 
-#[get("/user/me")]
-pub fn me(user: AuthGuard) -> Custom<Json<ApiResponse<UserMeResponse>>> {
-    match &user.0.session_token {
-        Some(s) => s.clone(),
-        None => {
-            return Custom(
-                Status::Unauthorized,
-                Json(ApiResponse::error("Unauthorized")),
-            );
-        }
-    };
+/// Disabled users are only told so once their credentials checked out.
+fn reject_disabled_user(
+    connection: &mut PgConnection,
+    client: &ClientInfo,
+    user: &UserModel,
+) -> Result<(), LoginError> {
+    if user.disabled_at.is_none() {
+        return Ok(());
+    }
+
+    let _ = record_login_attempt(connection, &user.username, client, Some(FAILURE_DISABLED));
+
+    Err(LoginError(Status::Forbidden, "This account is disabled"))
+}
 
+/// Records the successful attempt and creates a session, returning its token.
+fn open_login_session(
+    connection: &mut PgConnection,
+    client: &ClientInfo,
+    session_config: &SessionConfig,
+    user: UserModel,
+) -> Result<(String, UserModel), LoginError> {
+    reject_disabled_user(connection, client, &user)?;
+
+    if record_login_attempt(connection, &user.username, client, None).is_err() {
+        return Err(LoginError::database());
+    }
+
+    let new_session_token = create_session(connection, user.id, client, session_config)
+        .map_err(|_e| LoginError(Status::InternalServerError, "Failed to create session"))?;
+
+    record_audit_event(
+        connection,
+        Some(&user),
+        client,
+        AUDIT_USER_LOGIN,
+        Some(AuditTarget::new(AUDIT_TARGET_USER, user.id)),
+        AuditChanges::none(),
+    );
+
+    Ok((new_session_token, user))
+}
+
+/// Sets the cookie of a session opened by [`open_login_session`].
+fn login_succeeded(
+    cookie_jar: &CookieJar<'_>,
+    session_config: &SessionConfig,
+    token: String,
+    user: UserModel,
+) -> LoginResponse {
+    set_session_cookie(cookie_jar, token, session_config);
+
+    Custom(
+        Status::Ok,
+        Json(ApiResponse::success(
+            "Login successful",
+            UserLoginResponse {
+                user: Some(user.into()),
+                totp_required: false,
+                challenge_token: None,
+            },
+        )),
+    )
+}
+
+/// Records the successful attempt, creates a session and sets its cookie.
+pub fn issue_login_session(
+    connection: &mut PgConnection,
+    cookie_jar: &CookieJar<'_>,
+    client: &ClientInfo,
+    session_config: &SessionConfig,
+    user: UserModel,
+) -> LoginResponse {
+    match open_login_session(connection, client, session_config, user) {
+        Ok((token, user)) => login_succeeded(cookie_jar, session_config, token, user),
+        Err(e) => e.respond(),
+    }
+}
+
+#[get("/user/me")]
+pub fn me(user: AuthGuard<scope::Read>) -> Custom<Json<ApiResponse<UserMeResponse>>> {
     Custom(
         Status::Ok,
         Json(ApiResponse::success(
@@ -159,6 +438,7 @@ pub fn me(user: AuthGuard) -> Custom<Json<ApiResponse<UserMeResponse>>> {
 pub fn change_password(
     db: &State<DbConnection>,
     user: AuthGuard,
+    client: ClientInfo,
     form: Json<UserChangePasswordForm<'_>>,
 ) -> Custom<Json<ApiResponse<UserChangePasswordResponse>>> {
     if let Err(_e) = form.validate() {
@@ -174,6 +454,24 @@ pub fn change_password(
         .execute(connection)
         .unwrap();
 
+    // sign out everywhere else, the current session stays valid
+    diesel::delete(
+        session::table
+            .filter(session::user_id.eq(user.0.id))
+            .filter(session::id.ne(user.session_id().unwrap_or_default())),
+    )
+    .execute(connection)
+    .unwrap();
+
+    record_audit_event(
+        connection,
+        Some(&user.0),
+        &client,
+        AUDIT_USER_PASSWORD_CHANGE,
+        Some(AuditTarget::new(AUDIT_TARGET_USER, user.0.id)),
+        AuditChanges::none(),
+    );
+
     Custom(
         Status::Ok,
         Json(ApiResponse::success(
@@ -185,16 +483,116 @@ pub fn change_password(
     )
 }
 
-pub fn set_new_session_token(
-    user_id: Uuid,
-    connection: &mut PooledConnection<ConnectionManager<PgConnection>>,
-) -> String {
-    let new_token = generate_random_string(256);
+#[post("/user/logout")]
+pub fn logout(
+    db: &State<DbConnection>,
+    cookie_jar: &CookieJar,
+    user: AuthGuard,
+) -> Custom<Json<ApiResponse<()>>> {
+    let connection = &mut db.get().expect("Failed to get DB Connection");
 
-    diesel::update(user::table.filter(user::id.eq(user_id)))
-        .set(user::session_token.eq(&new_token))
+    if diesel::delete(session::table.filter(session::id.nullable().eq(user.session_id())))
         .execute(connection)
-        .unwrap();
+        .is_err()
+    {
+        return Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to revoke session")),
+        );
+    }
+
+    remove_session_cookie(cookie_jar);
+
+    Custom(Status::Ok, Json(ApiResponse::success("Logged out", ())))
+}
+
+#[get("/user/sessions")]
+pub fn get_sessions(
+    db: &State<DbConnection>,
+    user: AuthGuard,
+) -> Custom<Json<ApiResponse<UserSessionsResponse>>> {
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    let sessions_result = session::table
+        .filter(session::user_id.eq(user.0.id))
+        .filter(session::expires_at.gt(Utc::now()))
+        .order(session::last_seen_at.desc())
+        .select(SessionModel::as_select())
+        .load::<SessionModel>(connection);
+
+    match sessions_result {
+        Ok(sessions) => Custom(
+            Status::Ok,
+            Json(ApiResponse::success(
+                "Sessions fetched successfully",
+                UserSessionsResponse {
+                    sessions: sessions
+                        .into_iter()
+                        .map(|s| PublicSession::from_model(s, user.session_id()))
+                        .collect(),
+                },
+            )),
+        ),
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to fetch sessions")),
+        ),
+    }
+}
+
+#[delete("/user/sessions/<session_id>")]
+pub fn revoke_session(
+    db: &State<DbConnection>,
+    cookie_jar: &CookieJar,
+    user: AuthGuard,
+    client: ClientInfo,
+    session_id: String,
+) -> Custom<Json<ApiResponse<()>>> {
+    let parsed_id = match Uuid::parse_str(&session_id) {
+        Ok(uuid) => uuid,
+        Err(_) => {
+            return Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error("Invalid session ID")),
+            );
+        }
+    };
 
-    new_token
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    match diesel::delete(
+        session::table
+            .filter(session::id.eq(parsed_id))
+            .filter(session::user_id.eq(user.0.id)),
+    )
+    .execute(connection)
+    {
+        Ok(0) => Custom(
+            Status::NotFound,
+            Json(ApiResponse::error("Session not found")),
+        ),
+        Ok(_) => {
+            if Some(parsed_id) == user.session_id() {
+                remove_session_cookie(cookie_jar);
+            }
+
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_USER_SESSION_REVOKE,
+                Some(AuditTarget::new(AUDIT_TARGET_SESSION, parsed_id)),
+                AuditChanges::none(),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success("Session revoked", ())),
+            )
+        }
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to revoke session")),
+        ),
+    }
 }
@@ -0,0 +1,419 @@
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use rocket::State;
+use rocket::data::{Data, ToByteUnit};
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::db::DbConnection;
+use crate::middlewares::auth::{AuthGuard, scope};
+use crate::middlewares::client::ClientInfo;
+use crate::middlewares::webhook::WebhookHeaders;
+use crate::models::{InsertableWebhookDeliveryModel, RepositoryWebhookModel, WebhookDeliveryModel};
+use crate::routes::repository::find_repository_with_role;
+use crate::schema::{repository, repository_webhook, webhook_delivery};
+use crate::utils::access::TeamRole;
+use crate::utils::audit::{
+    AUDIT_REPOSITORY_WEBHOOK_DELETE, AUDIT_REPOSITORY_WEBHOOK_ROTATE, AUDIT_TARGET_REPOSITORY,
+    AuditChanges, AuditTarget, record_audit_event,
+};
+use crate::utils::crypto::generate_random_string;
+use crate::utils::pagination::page_limit;
+use crate::utils::response::ApiResponse;
+use crate::utils::webhook::{
+    DELIVERY_ACCEPTED, DELIVERY_IGNORED, DELIVERY_REJECTED, is_ref_change_event,
+    verify_webhook_signature,
+};
+
+/// Push payloads of large pushes can reach a few megabytes.
+const WEBHOOK_BODY_LIMIT_MIB: u64 = 10;
+/// Rejected deliveries come from anyone who knows the URL, so only this many are recorded
+/// per repository and hour; the rest are only logged.
+const MAX_RECORDED_REJECTIONS_PER_HOUR: i64 = 20;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookDeliveryResponse {
+    pub outcome: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotateWebhookResponse {
+    /// Path to configure on the git host, relative to the public server URL.
+    pub webhook_path: String,
+    /// Only returned here, store it in the git host's webhook settings.
+    pub secret: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetWebhookDeliveriesResponse {
+    pub configured: bool,
+    pub webhook_deliveries: Vec<WebhookDeliveryModel>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteWebhookResponse {
+    pub repository_id: Uuid,
+}
+
+fn parse_repository_id<T>(repo_id: &str) -> Result<Uuid, Custom<Json<ApiResponse<T>>>> {
+    Uuid::parse_str(repo_id).map_err(|_| {
+        Custom(
+            Status::BadRequest,
+            Json(ApiResponse::error("Invalid repository ID")),
+        )
+    })
+}
+
+/// Whether the repository already has the most rejected deliveries recorded this hour.
+fn rejections_exhausted(connection: &mut PgConnection, repository_id: Uuid) -> QueryResult<bool> {
+    let recorded = webhook_delivery::table
+        .filter(webhook_delivery::repository_id.eq(repository_id))
+        .filter(webhook_delivery::outcome.eq(DELIVERY_REJECTED))
+        .filter(webhook_delivery::created_at.gt(Utc::now() - Duration::hours(1)))
+        .count()
+        .get_result::<i64>(connection)?;
+
+    Ok(recorded >= MAX_RECORDED_REJECTIONS_PER_HOUR)
+}
+
+/// Records the delivery and builds the response. Recording failures are only logged,
+/// the git host cannot do anything about them.
+fn finish_delivery(
+    connection: &mut PgConnection,
+    delivery: InsertableWebhookDeliveryModel,
+    status: Status,
+) -> Custom<Json<ApiResponse<WebhookDeliveryResponse>>> {
+    let record = delivery.outcome != DELIVERY_REJECTED
+        || !rejections_exhausted(connection, delivery.repository_id).unwrap_or(true);
+
+    if !record {
+        tracing::debug!(
+            repository_id = %delivery.repository_id,
+            message = delivery.message,
+            "webhook delivery rejected, not recorded"
+        );
+    } else if let Err(e) = diesel::insert_into(webhook_delivery::table)
+        .values(&delivery)
+        .execute(connection)
+    {
+        tracing::error!(
+            repository_id = %delivery.repository_id,
+            error = %e,
+            "failed to record webhook delivery"
+        );
+    }
+
+    let response = WebhookDeliveryResponse {
+        outcome: delivery.outcome.to_string(),
+    };
+    if delivery.outcome == DELIVERY_REJECTED {
+        return Custom(status, Json(ApiResponse::error(delivery.message)));
+    }
+    Custom(
+        status,
+        Json(ApiResponse::success(delivery.message, response)),
+    )
+}
+
+/// Receives push webhooks from GitHub, GitLab and Gitea and requests an immediate sync.
+#[post("/webhook/<repo_id>", data = "<body>")]
+pub async fn receive_webhook(
+    db: &State<DbConnection>,
+    client: ClientInfo,
+    headers: WebhookHeaders,
+    repo_id: &str,
+    body: Data<'_>,
+) -> Custom<Json<ApiResponse<WebhookDeliveryResponse>>> {
+    let parsed_id = match parse_repository_id(repo_id) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let body = match body
+        .open(WEBHOOK_BODY_LIMIT_MIB.mebibytes())
+        .into_bytes()
+        .await
+    {
+        Ok(body) if body.is_complete() => body.into_inner(),
+        Ok(_) => {
+            return Custom(
+                Status::PayloadTooLarge,
+                Json(ApiResponse::error("Payload too large")),
+            );
+        }
+        Err(_e) => {
+            return Custom(
+                Status::BadRequest,
+                Json(ApiResponse::error("Failed to read payload")),
+            );
+        }
+    };
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    // unknown repositories and ones without a webhook are not recorded
+    let webhook = match repository_webhook::table
+        .filter(repository_webhook::repository_id.eq(parsed_id))
+        .select(RepositoryWebhookModel::as_select())
+        .first::<RepositoryWebhookModel>(connection)
+        .optional()
+    {
+        Ok(Some(webhook)) => webhook,
+        Ok(None) => {
+            return Custom(
+                Status::NotFound,
+                Json(ApiResponse::error("Webhook not found")),
+            );
+        }
+        Err(_e) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Failed to fetch webhook")),
+            );
+        }
+    };
+
+    let mut delivery = InsertableWebhookDeliveryModel {
+        repository_id: parsed_id,
+        provider: headers.provider.map(|p| p.as_str()),
+        event: headers.event.as_deref(),
+        delivery_id: headers.delivery_id.as_deref(),
+        outcome: DELIVERY_REJECTED,
+        message: "",
+        ip_address: client.ip_address.as_deref(),
+    };
+
+    let (provider, event) = match (headers.provider, headers.event.as_deref()) {
+        (Some(provider), Some(event)) => (provider, event),
+        _ => {
+            delivery.message = "Unknown webhook provider";
+            return finish_delivery(connection, delivery, Status::BadRequest);
+        }
+    };
+
+    let verified = headers.signature.as_deref().is_some_and(|signature| {
+        verify_webhook_signature(provider, signature, &webhook.secret, &body)
+    });
+    if !verified {
+        delivery.message = "Invalid webhook signature";
+        return finish_delivery(connection, delivery, Status::Unauthorized);
+    }
+
+    if !is_ref_change_event(provider, event) {
+        delivery.outcome = DELIVERY_IGNORED;
+        delivery.message = "Event does not trigger a sync";
+        return finish_delivery(connection, delivery, Status::Ok);
+    }
+
+    // the clone worker picks up repositories with a pending sync request before due ones
+    match diesel::update(
+        repository::table
+            .filter(repository::id.eq(parsed_id))
+            .filter(repository::is_enabled.eq(true)),
+    )
+    .set(repository::sync_requested_at.eq(Utc::now()))
+    .execute(connection)
+    {
+        Ok(0) => {
+            delivery.outcome = DELIVERY_IGNORED;
+            delivery.message = "Repository is disabled";
+            finish_delivery(connection, delivery, Status::Ok)
+        }
+        Ok(_) => {
+            delivery.outcome = DELIVERY_ACCEPTED;
+            delivery.message = "Repository sync requested";
+            finish_delivery(connection, delivery, Status::Accepted)
+        }
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to request repository sync")),
+        ),
+    }
+}
+
+/// Creates the repository's webhook, or replaces its secret.
+#[post("/repository/<repo_id>/webhook")]
+pub fn rotate_repository_webhook(
+    db: &State<DbConnection>,
+    user: AuthGuard<scope::RepositoriesWrite>,
+    client: ClientInfo,
+    repo_id: &str,
+) -> Custom<Json<ApiResponse<RotateWebhookResponse>>> {
+    let parsed_id = match parse_repository_id(repo_id) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    if let Err(response) =
+        find_repository_with_role(connection, user.0.id, parsed_id, TeamRole::Maintainer)
+    {
+        return response;
+    }
+
+    let secret = generate_random_string(40);
+
+    match diesel::insert_into(repository_webhook::table)
+        .values((
+            repository_webhook::repository_id.eq(parsed_id),
+            repository_webhook::secret.eq(&secret),
+        ))
+        .on_conflict(repository_webhook::repository_id)
+        .do_update()
+        .set((
+            repository_webhook::secret.eq(&secret),
+            repository_webhook::updated_at.eq(Utc::now()),
+        ))
+        .execute(connection)
+    {
+        Ok(_) => {
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_REPOSITORY_WEBHOOK_ROTATE,
+                Some(AuditTarget::new(AUDIT_TARGET_REPOSITORY, parsed_id)),
+                AuditChanges::none(),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "Webhook secret generated successfully",
+                    RotateWebhookResponse {
+                        webhook_path: format!("/api/webhook/{}", parsed_id),
+                        secret,
+                    },
+                )),
+            )
+        }
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to save webhook")),
+        ),
+    }
+}
+
+// ranked after `DELETE /repository/transfers/<id>`, which has the same shape
+#[delete("/repository/<repo_id>/webhook", rank = 2)]
+pub fn delete_repository_webhook(
+    db: &State<DbConnection>,
+    user: AuthGuard<scope::RepositoriesWrite>,
+    client: ClientInfo,
+    repo_id: &str,
+) -> Custom<Json<ApiResponse<DeleteWebhookResponse>>> {
+    let parsed_id = match parse_repository_id(repo_id) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    if let Err(response) =
+        find_repository_with_role(connection, user.0.id, parsed_id, TeamRole::Maintainer)
+    {
+        return response;
+    }
+
+    match diesel::delete(
+        repository_webhook::table.filter(repository_webhook::repository_id.eq(parsed_id)),
+    )
+    .execute(connection)
+    {
+        Ok(0) => Custom(
+            Status::NotFound,
+            Json(ApiResponse::error("Webhook not found")),
+        ),
+        Ok(_) => {
+            record_audit_event(
+                connection,
+                Some(&user.0),
+                &client,
+                AUDIT_REPOSITORY_WEBHOOK_DELETE,
+                Some(AuditTarget::new(AUDIT_TARGET_REPOSITORY, parsed_id)),
+                AuditChanges::none(),
+            );
+
+            Custom(
+                Status::Ok,
+                Json(ApiResponse::success(
+                    "Webhook deleted successfully",
+                    DeleteWebhookResponse {
+                        repository_id: parsed_id,
+                    },
+                )),
+            )
+        }
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to delete webhook")),
+        ),
+    }
+}
+
+/// Recent deliveries, newest first, to debug webhook setup on the git host.
+#[get("/repository/<repo_id>/webhook/deliveries?<limit>")]
+pub fn get_webhook_deliveries(
+    db: &State<DbConnection>,
+    user: AuthGuard<scope::Read>,
+    repo_id: &str,
+    limit: Option<i64>,
+) -> Custom<Json<ApiResponse<GetWebhookDeliveriesResponse>>> {
+    let parsed_id = match parse_repository_id(repo_id) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let connection = &mut db.get().expect("Failed to get DB Connection");
+
+    if let Err(response) =
+        find_repository_with_role(connection, user.0.id, parsed_id, TeamRole::Viewer)
+    {
+        return response;
+    }
+
+    let configured = match repository_webhook::table
+        .filter(repository_webhook::repository_id.eq(parsed_id))
+        .count()
+        .get_result::<i64>(connection)
+    {
+        Ok(count) => count > 0,
+        Err(_e) => {
+            return Custom(
+                Status::InternalServerError,
+                Json(ApiResponse::error("Failed to fetch webhook")),
+            );
+        }
+    };
+
+    match webhook_delivery::table
+        .filter(webhook_delivery::repository_id.eq(parsed_id))
+        .order(webhook_delivery::created_at.desc())
+        .limit(page_limit(limit))
+        .select(WebhookDeliveryModel::as_select())
+        .load::<WebhookDeliveryModel>(connection)
+    {
+        Ok(webhook_deliveries) => Custom(
+            Status::Ok,
+            Json(ApiResponse::success(
+                "Webhook deliveries fetched successfully",
+                GetWebhookDeliveriesResponse {
+                    configured,
+                    webhook_deliveries,
+                },
+            )),
+        ),
+        Err(_e) => Custom(
+            Status::InternalServerError,
+            Json(ApiResponse::error("Failed to fetch webhook deliveries")),
+        ),
+    }
+}
@@ -1,9 +1,143 @@
 // @generated automatically by Diesel CLI.
 
 diesel::table! {
-    repository (id) {
+    app_setting (key) {
+        #[max_length = 64]
+        key -> Varchar,
+        value -> Text,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    api_token (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 100]
+        name -> Varchar,
+        #[max_length = 16]
+        token_prefix -> Varchar,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        #[max_length = 256]
+        scopes -> Varchar,
+        expires_at -> Nullable<Timestamptz>,
+        last_used_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    audit_event (id) {
+        id -> Uuid,
+        actor_user_id -> Nullable<Uuid>,
+        #[max_length = 32]
+        actor_username -> Nullable<Varchar>,
+        #[max_length = 64]
+        action -> Varchar,
+        #[max_length = 32]
+        target_type -> Nullable<Varchar>,
+        target_id -> Nullable<Uuid>,
+        before -> Nullable<Jsonb>,
+        after -> Nullable<Jsonb>,
+        #[max_length = 64]
+        ip_address -> Nullable<Varchar>,
+        #[max_length = 512]
+        user_agent -> Nullable<Varchar>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    login_attempt (id) {
+        id -> Uuid,
+        #[max_length = 64]
+        username -> Varchar,
+        #[max_length = 64]
+        ip_address -> Nullable<Varchar>,
+        #[max_length = 512]
+        user_agent -> Nullable<Varchar>,
+        succeeded -> Bool,
+        #[max_length = 30]
+        failure_reason -> Nullable<Varchar>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    login_challenge (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        attempts -> Int4,
+        expires_at -> Timestamptz,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    notification_channel (id) {
+        id -> Uuid,
+        user_id -> Nullable<Uuid>,
+        team_id -> Nullable<Uuid>,
+        #[max_length = 100]
+        name -> Varchar,
+        #[max_length = 16]
+        kind -> Varchar,
+        #[max_length = 1024]
+        target -> Varchar,
+        #[max_length = 128]
+        secret -> Nullable<Varchar>,
+        is_enabled -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    notification_delivery (id) {
+        id -> Uuid,
+        channel_id -> Uuid,
+        repository_id -> Nullable<Uuid>,
+        #[max_length = 32]
+        event -> Varchar,
+        succeeded -> Bool,
+        error -> Nullable<Text>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    oidc_login_state (id) {
+        id -> Uuid,
+        #[max_length = 64]
+        state -> Varchar,
+        #[max_length = 64]
+        nonce -> Varchar,
+        #[max_length = 128]
+        code_verifier -> Varchar,
+        expires_at -> Timestamptz,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    recovery_code (id) {
         id -> Uuid,
         user_id -> Uuid,
+        #[max_length = 64]
+        code_hash -> Varchar,
+        used_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    repository (id) {
+        id -> Uuid,
+        user_id -> Nullable<Uuid>,
         #[max_length = 200]
         name -> Varchar,
         #[max_length = 512]
@@ -19,6 +153,29 @@ diesel::table! {
         last_clone_at -> Nullable<Timestamptz>,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        sync_requested_at -> Nullable<Timestamptz>,
+        team_id -> Nullable<Uuid>,
+        tags -> Array<Text>,
+        #[max_length = 16]
+        last_sync_status -> Nullable<Varchar>,
+        #[max_length = 255]
+        source_host -> Nullable<Varchar>,
+        log_retention_days -> Nullable<Int4>,
+        log_max_rows -> Nullable<Int4>,
+        #[max_length = 16]
+        health_state -> Varchar,
+        ref_filters -> Array<Text>,
+    }
+}
+
+diesel::table! {
+    repository_log_daily_count (repository_id, day, type_) {
+        repository_id -> Uuid,
+        day -> Date,
+        #[sql_name = "type"]
+        #[max_length = 30]
+        type_ -> Varchar,
+        count -> Int8,
     }
 }
 
@@ -32,6 +189,73 @@ diesel::table! {
         message -> Text,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        job_id -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    repository_transfer (id) {
+        id -> Uuid,
+        repository_id -> Uuid,
+        requested_by -> Nullable<Uuid>,
+        from_user_id -> Nullable<Uuid>,
+        from_team_id -> Nullable<Uuid>,
+        to_user_id -> Nullable<Uuid>,
+        to_team_id -> Nullable<Uuid>,
+        #[max_length = 16]
+        status -> Varchar,
+        resolved_by -> Nullable<Uuid>,
+        resolved_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    repository_webhook (repository_id) {
+        repository_id -> Uuid,
+        #[max_length = 64]
+        secret -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    session (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        #[max_length = 512]
+        user_agent -> Nullable<Varchar>,
+        #[max_length = 64]
+        ip_address -> Nullable<Varchar>,
+        last_seen_at -> Timestamptz,
+        expires_at -> Timestamptz,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    team (id) {
+        id -> Uuid,
+        #[max_length = 100]
+        name -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    team_member (id) {
+        id -> Uuid,
+        team_id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 16]
+        role -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
@@ -42,18 +266,77 @@ diesel::table! {
         username -> Varchar,
         #[max_length = 256]
         password_hash -> Nullable<Varchar>,
-        #[max_length = 256]
-        session_token -> Nullable<Varchar>,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        is_admin -> Bool,
+        #[max_length = 64]
+        totp_secret -> Nullable<Varchar>,
+        totp_enabled -> Bool,
+        totp_last_used_step -> Nullable<Int8>,
+        #[max_length = 255]
+        oidc_subject -> Nullable<Varchar>,
+        #[max_length = 1024]
+        ldap_dn -> Nullable<Varchar>,
+        disabled_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    webhook_delivery (id) {
+        id -> Uuid,
+        repository_id -> Uuid,
+        #[max_length = 16]
+        provider -> Nullable<Varchar>,
+        #[max_length = 64]
+        event -> Nullable<Varchar>,
+        #[max_length = 128]
+        delivery_id -> Nullable<Varchar>,
+        #[max_length = 16]
+        outcome -> Varchar,
+        message -> Text,
+        #[max_length = 64]
+        ip_address -> Nullable<Varchar>,
+        created_at -> Timestamptz,
     }
 }
 
+diesel::joinable!(api_token -> user (user_id));
+diesel::joinable!(login_challenge -> user (user_id));
+diesel::joinable!(recovery_code -> user (user_id));
+diesel::joinable!(repository -> team (team_id));
 diesel::joinable!(repository -> user (user_id));
+diesel::joinable!(notification_channel -> team (team_id));
+diesel::joinable!(notification_channel -> user (user_id));
+diesel::joinable!(notification_delivery -> notification_channel (channel_id));
+diesel::joinable!(notification_delivery -> repository (repository_id));
+diesel::joinable!(repository_log_daily_count -> repository (repository_id));
 diesel::joinable!(repository_logs -> repository (repository_id));
+diesel::joinable!(repository_transfer -> repository (repository_id));
+diesel::joinable!(repository_transfer -> team (to_team_id));
+diesel::joinable!(repository_webhook -> repository (repository_id));
+diesel::joinable!(session -> user (user_id));
+diesel::joinable!(team_member -> team (team_id));
+diesel::joinable!(team_member -> user (user_id));
+diesel::joinable!(webhook_delivery -> repository (repository_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    api_token,
+    app_setting,
+    audit_event,
+    login_attempt,
+    login_challenge,
+    notification_channel,
+    notification_delivery,
+    oidc_login_state,
+    recovery_code,
     repository,
+    repository_log_daily_count,
     repository_logs,
+    repository_transfer,
+    repository_webhook,
+    session,
+    team,
+    team_member,
     user,
+    webhook_delivery,
 );
@@ -0,0 +1,87 @@
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+use crate::config::TelemetryConfig;
+
+/// Used when `RUST_LOG` is unset. Rocket's own request logging is replaced by the
+/// request id fairing, so only its warnings are kept.
+const DEFAULT_LOG_FILTER: &str = "info,rocket=warn,_=warn";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One JSON object per line, with the fields of the enclosing spans.
+    Json,
+    /// Human readable lines for local development.
+    Text,
+}
+
+/// Installs the global tracing subscriber. Returns the tracer provider when OTLP export
+/// is enabled, which has to be shut down to flush pending spans. Commands log to stderr to
+/// keep their JSON output on stdout parseable.
+pub fn init(config: &TelemetryConfig, to_stderr: bool) -> Option<SdkTracerProvider> {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+
+    let provider = otlp_tracer_provider(config);
+    let otel_layer = provider
+        .as_ref()
+        .map(|p| tracing_opentelemetry::layer().with_tracer(p.tracer(config.service_name.clone())));
+
+    let writer = move || -> Box<dyn std::io::Write> {
+        if to_stderr {
+            Box::new(std::io::stderr())
+        } else {
+            Box::new(std::io::stdout())
+        }
+    };
+
+    let registry = tracing_subscriber::registry().with(filter).with(otel_layer);
+    match config.log_format {
+        LogFormat::Json => registry
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .flatten_event(true)
+                    .with_current_span(false)
+                    .with_span_list(true)
+                    .with_writer(writer),
+            )
+            .init(),
+        LogFormat::Text => registry
+            .with(tracing_subscriber::fmt::layer().with_writer(writer))
+            .init(),
+    }
+
+    provider
+}
+
+/// Exports spans over OTLP/HTTP when an endpoint is configured. The exporter reads the
+/// remaining standard `OTEL_*` variables itself, such as the protocol, headers and timeout.
+fn otlp_tracer_provider(config: &TelemetryConfig) -> Option<SdkTracerProvider> {
+    let endpoint = config.traces_endpoint()?;
+
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()
+        .expect("Failed to create OTLP span exporter");
+
+    // the resource still honours OTEL_RESOURCE_ATTRIBUTES
+    let resource = Resource::builder()
+        .with_service_name(config.service_name.clone())
+        .build();
+
+    Some(
+        SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(resource)
+            .build(),
+    )
+}
@@ -0,0 +1,92 @@
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::models::RepositoryModel;
+use crate::schema::{repository, team_member};
+
+/// Team membership roles, each one includes the permissions of the ones before it.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum TeamRole {
+    /// Read repositories, logs and the dashboard.
+    Viewer,
+    /// Also trigger syncs.
+    Operator,
+    /// Also add and delete repositories and manage the team.
+    Maintainer,
+}
+
+impl TeamRole {
+    pub const ALL: [TeamRole; 3] = [TeamRole::Viewer, TeamRole::Operator, TeamRole::Maintainer];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        TeamRole::ALL.into_iter().find(|r| r.as_str() == value)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TeamRole::Viewer => "viewer",
+            TeamRole::Operator => "operator",
+            TeamRole::Maintainer => "maintainer",
+        }
+    }
+
+    /// Role names granting at least this role.
+    fn at_least(self) -> Vec<&'static str> {
+        TeamRole::ALL
+            .into_iter()
+            .filter(|r| *r >= self)
+            .map(|r| r.as_str())
+            .collect()
+    }
+}
+
+/// Repositories owned by the user or by a team in which the user has at least `role`.
+pub fn accessible_repositories<'a>(
+    user_id: Uuid,
+    role: TeamRole,
+) -> repository::BoxedQuery<'a, Pg> {
+    let team_ids = team_member::table
+        .filter(team_member::user_id.eq(user_id))
+        .filter(team_member::role.eq_any(role.at_least()))
+        .select(team_member::team_id.nullable());
+
+    repository::table
+        .filter(
+            repository::user_id
+                .eq(user_id)
+                .or(repository::team_id.eq_any(team_ids)),
+        )
+        .into_boxed()
+}
+
+pub fn team_role(
+    connection: &mut PgConnection,
+    team_id: Uuid,
+    user_id: Uuid,
+) -> QueryResult<Option<TeamRole>> {
+    let role = team_member::table
+        .filter(team_member::team_id.eq(team_id))
+        .filter(team_member::user_id.eq(user_id))
+        .select(team_member::role)
+        .first::<String>(connection)
+        .optional()?;
+
+    Ok(role.as_deref().and_then(TeamRole::parse))
+}
+
+/// Personal owners act as maintainers of their own repositories.
+pub fn repository_role(
+    connection: &mut PgConnection,
+    repo: &RepositoryModel,
+    user_id: Uuid,
+) -> QueryResult<Option<TeamRole>> {
+    if repo.user_id == Some(user_id) {
+        return Ok(Some(TeamRole::Maintainer));
+    }
+
+    match repo.team_id {
+        Some(team_id) => team_role(connection, team_id, user_id),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,166 @@
+use diesel::prelude::*;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::middlewares::client::ClientInfo;
+use crate::models::{InsertableAuditEventModel, UserModel};
+use crate::schema::audit_event;
+
+pub const AUDIT_SETUP_COMPLETE: &str = "setup.complete";
+pub const AUDIT_USER_CREATE: &str = "user.create";
+pub const AUDIT_USER_LOGIN: &str = "user.login";
+pub const AUDIT_USER_PASSWORD_RESET: &str = "user.password_reset";
+pub const AUDIT_USER_DISABLE: &str = "user.disable";
+pub const AUDIT_USER_ENABLE: &str = "user.enable";
+pub const AUDIT_USER_PASSWORD_CHANGE: &str = "user.password_change";
+pub const AUDIT_USER_SESSION_REVOKE: &str = "user.session_revoke";
+pub const AUDIT_USER_TOTP_ENABLE: &str = "user.totp_enable";
+pub const AUDIT_USER_TOTP_DISABLE: &str = "user.totp_disable";
+pub const AUDIT_USER_RECOVERY_CODES_REGENERATE: &str = "user.recovery_codes_regenerate";
+pub const AUDIT_API_TOKEN_CREATE: &str = "api_token.create";
+pub const AUDIT_API_TOKEN_REVOKE: &str = "api_token.revoke";
+pub const AUDIT_REPOSITORY_CREATE: &str = "repository.create";
+pub const AUDIT_REPOSITORY_DELETE: &str = "repository.delete";
+pub const AUDIT_REPOSITORY_UPDATE: &str = "repository.update";
+pub const AUDIT_REPOSITORY_EXPORT: &str = "repository.export";
+pub const AUDIT_REPOSITORY_IMPORT: &str = "repository.import";
+pub const AUDIT_REPOSITORY_SYNC: &str = "repository.sync";
+pub const AUDIT_REPOSITORY_ENABLE: &str = "repository.enable";
+pub const AUDIT_REPOSITORY_DISABLE: &str = "repository.disable";
+pub const AUDIT_REPOSITORY_RETENTION_UPDATE: &str = "repository.retention_update";
+pub const AUDIT_REPOSITORY_WEBHOOK_ROTATE: &str = "repository.webhook_rotate";
+pub const AUDIT_REPOSITORY_WEBHOOK_DELETE: &str = "repository.webhook_delete";
+pub const AUDIT_REPOSITORY_TRANSFER_REQUEST: &str = "repository.transfer_request";
+pub const AUDIT_REPOSITORY_TRANSFER_ACCEPT: &str = "repository.transfer_accept";
+pub const AUDIT_REPOSITORY_TRANSFER_DECLINE: &str = "repository.transfer_decline";
+pub const AUDIT_REPOSITORY_TRANSFER_CANCEL: &str = "repository.transfer_cancel";
+pub const AUDIT_NOTIFICATION_CHANNEL_CREATE: &str = "notification_channel.create";
+pub const AUDIT_NOTIFICATION_CHANNEL_DELETE: &str = "notification_channel.delete";
+pub const AUDIT_SETTINGS_UPDATE: &str = "settings.update";
+pub const AUDIT_TEAM_CREATE: &str = "team.create";
+pub const AUDIT_TEAM_DELETE: &str = "team.delete";
+pub const AUDIT_TEAM_MEMBER_SET: &str = "team.member_set";
+pub const AUDIT_TEAM_MEMBER_REMOVE: &str = "team.member_remove";
+
+pub const AUDIT_TARGET_USER: &str = "user";
+pub const AUDIT_TARGET_SESSION: &str = "session";
+pub const AUDIT_TARGET_API_TOKEN: &str = "api_token";
+pub const AUDIT_TARGET_REPOSITORY: &str = "repository";
+pub const AUDIT_TARGET_SETTINGS: &str = "settings";
+pub const AUDIT_TARGET_TEAM: &str = "team";
+pub const AUDIT_TARGET_NOTIFICATION_CHANNEL: &str = "notification_channel";
+
+pub const MASKED_VALUE: &str = "********";
+
+/// Field names containing any of these are masked before they are stored.
+const SECRET_FIELD_MARKERS: [&str; 4] = ["secret", "password", "token", "key"];
+
+/// The entity an audit event is about.
+pub struct AuditTarget<'a> {
+    pub target_type: &'a str,
+    pub target_id: Option<Uuid>,
+}
+
+impl<'a> AuditTarget<'a> {
+    pub fn new(target_type: &'a str, target_id: Uuid) -> Self {
+        AuditTarget {
+            target_type,
+            target_id: Some(target_id),
+        }
+    }
+}
+
+/// Before and after state of the target, reduced to the fields that changed.
+#[derive(Default)]
+pub struct AuditChanges {
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+impl AuditChanges {
+    pub fn none() -> Self {
+        AuditChanges::default()
+    }
+
+    pub fn diff<T: Serialize>(before: Option<&T>, after: Option<&T>) -> Self {
+        let before = before.and_then(|v| serde_json::to_value(v).ok());
+        let after = after.and_then(|v| serde_json::to_value(v).ok());
+
+        let (before, after) = match (before, after) {
+            (Some(Value::Object(mut before)), Some(Value::Object(mut after))) => {
+                let unchanged: Vec<String> = before
+                    .iter()
+                    .filter(|(k, v)| after.get(k.as_str()) == Some(v))
+                    .map(|(k, _)| k.clone())
+                    .collect();
+                for key in unchanged {
+                    before.remove(&key);
+                    after.remove(&key);
+                }
+                (Some(Value::Object(before)), Some(Value::Object(after)))
+            }
+            other => other,
+        };
+
+        let non_empty = |v: &Value| v.as_object().is_none_or(|m| !m.is_empty());
+
+        AuditChanges {
+            before: before.filter(non_empty).map(mask_secrets),
+            after: after.filter(non_empty).map(mask_secrets),
+        }
+    }
+}
+
+fn is_secret_field(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    SECRET_FIELD_MARKERS.iter().any(|m| name.contains(m))
+}
+
+/// Replaces secret values with a fixed marker, keeping nulls so an unset secret stays visible.
+fn mask_secrets(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| {
+                    let v = if is_secret_field(&k) && !v.is_null() {
+                        Value::String(MASKED_VALUE.to_string())
+                    } else {
+                        mask_secrets(v)
+                    };
+                    (k, v)
+                })
+                .collect::<Map<String, Value>>(),
+        ),
+        Value::Array(values) => Value::Array(values.into_iter().map(mask_secrets).collect()),
+        other => other,
+    }
+}
+
+/// Appends an audit event. Failures are logged and never fail the audited action.
+pub fn record_audit_event(
+    connection: &mut PgConnection,
+    actor: Option<&UserModel>,
+    client: &ClientInfo,
+    action: &str,
+    target: Option<AuditTarget>,
+    changes: AuditChanges,
+) {
+    let result = diesel::insert_into(audit_event::table)
+        .values(&InsertableAuditEventModel {
+            actor_user_id: actor.map(|u| u.id),
+            actor_username: actor.map(|u| u.username.as_str()),
+            action,
+            target_type: target.as_ref().map(|t| t.target_type),
+            target_id: target.as_ref().and_then(|t| t.target_id),
+            before: changes.before,
+            after: changes.after,
+            ip_address: client.ip_address.as_deref(),
+            user_agent: client.user_agent.as_deref(),
+        })
+        .execute(connection);
+
+    if let Err(e) = result {
+        tracing::error!(action, error = %e, "failed to record audit event");
+    }
+}
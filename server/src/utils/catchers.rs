@@ -1,6 +1,7 @@
 use rocket::Request;
 use rocket::serde::json::Json;
 
+use crate::middlewares::auth::AuthErrorMessage;
 use crate::utils::response::ApiResponse;
 
 #[catch(401)]
@@ -8,11 +9,22 @@ pub fn unauthorized(_: &Request) -> Json<ApiResponse<()>> {
     Json(ApiResponse::error("Unauthorized"))
 }
 
+#[catch(403)]
+pub fn forbidden(req: &Request) -> Json<ApiResponse<()>> {
+    let message = req.local_cache(|| AuthErrorMessage("Forbidden"));
+    Json(ApiResponse::error(message.0))
+}
+
 #[catch(404)]
 pub fn not_found(_: &Request) -> Json<ApiResponse<()>> {
     Json(ApiResponse::error("Resource not found"))
 }
 
+#[catch(429)]
+pub fn too_many_requests(_: &Request) -> Json<ApiResponse<()>> {
+    Json(ApiResponse::error("Too many requests"))
+}
+
 #[catch(500)]
 pub fn internal_error(_: &Request) -> Json<ApiResponse<()>> {
     Json(ApiResponse::error("Internal server error"))
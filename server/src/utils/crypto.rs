@@ -1,7 +1,16 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
 use argon2::password_hash::{Error, PasswordHash, SaltString, rand_core::OsRng};
-use argon2::{Argon2, PasswordHasher, PasswordVerifier};
+use argon2::{Algorithm, Argon2, Params, PasswordHasher, PasswordVerifier, Version};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
 use rand::{Rng, rng};
+use sha2::{Digest, Sha256};
 use std::iter;
+use std::sync::LazyLock;
+
+static DUMMY_PASSWORD_HASH: LazyLock<String> =
+    LazyLock::new(|| hash_password("dummy-password").expect("Failed to hash dummy password"));
 
 pub fn generate_random_string(len: usize) -> String {
     const CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
@@ -31,6 +40,66 @@ pub fn verify_password(hash: &str, password: &str) -> bool {
     }
 }
 
+/// Spends the same Argon2 work as a real verification so response timing
+/// does not reveal whether an account exists.
+pub fn verify_dummy_password(password: &str) {
+    let _ = verify_password(&DUMMY_PASSWORD_HASH, password);
+}
+
+/// Hex-encoded SHA-256 digest, used to store high-entropy tokens without keeping them in plain text.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// AES-256 key derived from a user supplied passphrase with Argon2id. The same `params`
+/// have to be used to derive the key again.
+pub fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+    params: Params,
+) -> Result<[u8; 32], argon2::Error> {
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params).hash_password_into(
+        passphrase.as_bytes(),
+        salt,
+        &mut key,
+    )?;
+    Ok(key)
+}
+
+/// AES-256-GCM with a random nonce, base64 encoded as nonce followed by ciphertext.
+pub fn encrypt_secret(key: &[u8; 32], plaintext: &str) -> String {
+    let nonce: [u8; 12] = rng().random();
+    let ciphertext = Aes256Gcm::new(key.into())
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+        .expect("Failed to encrypt secret");
+
+    STANDARD.encode([nonce.as_slice(), &ciphertext].concat())
+}
+
+/// Reverses `encrypt_secret`, `None` when the key is wrong or the data was altered.
+pub fn decrypt_secret(key: &[u8; 32], encoded: &str) -> Option<String> {
+    let data = STANDARD.decode(encoded).ok()?;
+    if data.len() < 12 {
+        return None;
+    }
+
+    let (nonce, ciphertext) = data.split_at(12);
+    let plaintext = Aes256Gcm::new(key.into())
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .ok()?;
+    String::from_utf8(plaintext).ok()
+}
+
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
 pub fn sanitize_ssh_key(input: &str) -> String {
     let normalized = input.replace("\r\n", "\n").replace('\r', "\n");
 
@@ -0,0 +1,268 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::{Action, Attempt, Policy};
+use rocket::tokio;
+use url::{Host, Url};
+
+/// Same limit as reqwest's default policy.
+const MAX_REDIRECTS: usize = 10;
+
+/// An absolute `http` or `https` URL with a host, as needed for outgoing requests.
+pub fn is_http_url(value: &str) -> bool {
+    Url::parse(value)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.host().is_some())
+}
+
+/// Whether an address is reachable on the internet. Loopback, link-local (which includes
+/// cloud metadata services at 169.254.169.254), private, shared, reserved and unspecified
+/// addresses are internal.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_ipv4(mapped),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // this network, shared address space, benchmarking and reserved
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 198 && (18..20).contains(&b))
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+    // NAT64 addresses reach the embedded IPv4 address
+    if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+        let [.., a, b, c, d] = ip.octets();
+        return is_public_ipv4(Ipv4Addr::new(a, b, c, d));
+    }
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // unique local, link-local and documentation
+        || segments[0] & 0xfe00 == 0xfc00
+        || segments[0] & 0xffc0 == 0xfe80
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8))
+}
+
+/// Whether the URL's host is an internal address literal or a `localhost` name. Other names
+/// are checked when a request resolves them.
+fn has_internal_host(url: &Url) -> bool {
+    match url.host() {
+        Some(Host::Ipv4(ip)) => !is_public_ip(IpAddr::V4(ip)),
+        Some(Host::Ipv6(ip)) => !is_public_ip(IpAddr::V6(ip)),
+        Some(Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost")
+        }
+        None => true,
+    }
+}
+
+/// Resolves names like the system resolver, leaving out internal addresses. Checking the
+/// addresses that are actually connected to also covers names that change what they resolve
+/// to after being checked.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+fn refuse_internal_redirects(attempt: Attempt) -> Action {
+    if attempt.previous().len() >= MAX_REDIRECTS {
+        attempt.error("too many redirects")
+    } else if has_internal_host(attempt.url()) {
+        let error = format!("refused redirect to internal address {}", attempt.url());
+        attempt.error(error)
+    } else {
+        attempt.follow()
+    }
+}
+
+/// HTTP client for URLs that users enter, such as notification webhooks and provider APIs. Unless internal
+/// targets are allowed, it refuses internal addresses, whether given as the host, resolved
+/// from it or reached by a redirect.
+#[derive(Clone)]
+pub struct OutboundClient {
+    http: reqwest::Client,
+    allow_internal: bool,
+}
+
+impl OutboundClient {
+    pub fn new(timeout: Duration, user_agent: &str, allow_internal: bool) -> Self {
+        let mut builder = reqwest::Client::builder()
+            .timeout(timeout)
+            .user_agent(user_agent);
+        if !allow_internal {
+            builder = builder
+                .dns_resolver(Arc::new(PublicResolver))
+                .redirect(Policy::custom(refuse_internal_redirects));
+        }
+
+        OutboundClient {
+            http: builder.build().expect("Failed to build HTTP client"),
+            allow_internal,
+        }
+    }
+
+    /// Whether requests to the URL are allowed, checked when it is entered. Names resolving
+    /// to internal addresses are only refused when requested.
+    pub fn accepts_url(&self, value: &str) -> bool {
+        is_http_url(value)
+            && (self.allow_internal || Url::parse(value).is_ok_and(|url| !has_internal_host(&url)))
+    }
+
+    fn checked(&self, value: &str) -> Result<Url, String> {
+        let url = Url::parse(value).map_err(|e| format!("invalid URL {}: {}", value, e))?;
+        if !self.allow_internal && has_internal_host(&url) {
+            return Err(format!("refused request to internal address {}", value));
+        }
+        Ok(url)
+    }
+
+    pub fn get(&self, url: &str) -> Result<reqwest::RequestBuilder, String> {
+        Ok(self.http.get(self.checked(url)?))
+    }
+
+    pub fn post(&self, url: &str) -> Result<reqwest::RequestBuilder, String> {
+        Ok(self.http.post(self.checked(url)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_only_http_urls_with_a_host() {
+        assert!(is_http_url("https://hooks.example.com/services/T0/B0"));
+        assert!(is_http_url("http://localhost:8080"));
+        assert!(!is_http_url("ftp://example.com"));
+        assert!(!is_http_url("file:///etc/passwd"));
+        assert!(!is_http_url("example.com/path"));
+        assert!(!is_http_url(""));
+    }
+
+    #[test]
+    fn tells_internal_addresses_apart() {
+        for internal in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "64:ff9b::a00:1",
+        ] {
+            assert!(!is_public_ip(internal.parse().unwrap()), "{}", internal);
+        }
+        for public in [
+            "1.1.1.1",
+            "140.82.112.3",
+            "2606:4700::1111",
+            "64:ff9b::101:101",
+        ] {
+            assert!(is_public_ip(public.parse().unwrap()), "{}", public);
+        }
+    }
+
+    #[test]
+    fn refuses_internal_urls_unless_allowed() {
+        let client = OutboundClient::new(Duration::from_secs(1), "test", false);
+        assert!(client.accepts_url("https://hooks.example.com/services/T0/B0"));
+        assert!(client.accepts_url("https://140.82.112.3/hook"));
+        for internal in [
+            "http://localhost:8080",
+            "http://LOCALHOST./",
+            "http://app.localhost/",
+            "http://127.0.0.1:8080",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://[::1]/",
+            "http://[::ffff:10.0.0.1]/",
+            "http://2130706433/",
+        ] {
+            assert!(!client.accepts_url(internal), "{}", internal);
+            assert!(client.post(internal).is_err(), "{}", internal);
+        }
+        assert!(!client.accepts_url("file:///etc/passwd"));
+
+        let allowing = OutboundClient::new(Duration::from_secs(1), "test", true);
+        assert!(allowing.accepts_url("http://localhost:8080"));
+        assert!(allowing.post("http://127.0.0.1:8080").is_ok());
+        assert!(!allowing.accepts_url("file:///etc/passwd"));
+    }
+
+    #[rocket::async_test]
+    async fn leaves_out_internal_addresses_when_resolving() {
+        let error = PublicResolver
+            .resolve("localhost".parse().unwrap())
+            .await
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("no public address"));
+    }
+
+    #[rocket::async_test]
+    async fn refuses_redirects_to_internal_addresses() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).await.unwrap();
+            stream
+                .write_all(
+                    b"HTTP/1.1 302 Found\r\nLocation: http://169.254.169.254/latest/meta-data/\r\nContent-Length: 0\r\n\r\n",
+                )
+                .await
+                .unwrap();
+        });
+
+        // the redirect policy alone, since the stand-in itself listens on loopback
+        let client = reqwest::Client::builder()
+            .redirect(Policy::custom(refuse_internal_redirects))
+            .build()
+            .unwrap();
+        let error = client.get(&url).send().await.unwrap_err();
+        assert!(error.is_redirect());
+        assert!(format!("{:?}", error).contains("refused redirect to internal address"));
+    }
+}
@@ -0,0 +1,262 @@
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{Integer, Text};
+use uuid::Uuid;
+
+use crate::config::LoginConfig;
+use crate::middlewares::client::ClientInfo;
+use crate::models::InsertableLoginAttemptModel;
+use crate::schema::login_attempt;
+
+pub const FAILURE_UNKNOWN_USER: &str = "unknown_user";
+pub const FAILURE_BAD_PASSWORD: &str = "bad_password";
+pub const FAILURE_NO_PASSWORD: &str = "no_password";
+pub const FAILURE_BAD_TOTP: &str = "bad_totp";
+pub const FAILURE_LOCKED: &str = "locked";
+pub const FAILURE_LDAP_REJECTED: &str = "ldap_rejected";
+pub const FAILURE_LDAP_ERROR: &str = "ldap_error";
+pub const FAILURE_DISABLED: &str = "disabled";
+/// Recorded while the credentials are checked, counted like a failure until discarded.
+pub const FAILURE_PENDING: &str = "pending";
+
+const MAX_DELAY_MILLISECONDS: i64 = 8_000;
+/// First key of the transaction advisory locks taken per username and client IP, "logi" in
+/// ASCII, so they do not collide with other advisory locks.
+const LOGIN_LOCK_NAMESPACE: i32 = 0x6c6f_6769;
+
+pub struct LoginThrottleStatus {
+    pub locked: bool,
+    pub delay: std::time::Duration,
+    /// The pending attempt, unset when locked.
+    pub attempt_id: Option<Uuid>,
+}
+
+/// Checks the throttle and records the attempt, as locked or as pending until its outcome is
+/// recorded and [`discard_login_attempt`] is called. Both happen under advisory locks on the
+/// username and the client IP, so parallel attempts see each other and a burst of guesses
+/// cannot all pass on the same failure count.
+pub fn begin_login_attempt(
+    connection: &mut PgConnection,
+    config: &LoginConfig,
+    username: &str,
+    client: &ClientInfo,
+) -> QueryResult<LoginThrottleStatus> {
+    connection.transaction(|connection| {
+        // always the username first, so two attempts never wait on each other's lock
+        let mut keys = vec![format!("user:{}", username)];
+        keys.extend(client.ip_address.as_ref().map(|ip| format!("ip:{}", ip)));
+        for key in keys {
+            sql_query("SELECT pg_advisory_xact_lock($1, hashtext($2))")
+                .bind::<Integer, _>(LOGIN_LOCK_NAMESPACE)
+                .bind::<Text, _>(key)
+                .execute(connection)?;
+        }
+
+        let mut status = check_login_throttle(connection, config, username, client)?;
+        if status.locked {
+            record_login_attempt(connection, username, client, Some(FAILURE_LOCKED))?;
+        } else {
+            status.attempt_id = Some(
+                diesel::insert_into(login_attempt::table)
+                    .values(&InsertableLoginAttemptModel {
+                        username,
+                        ip_address: client.ip_address.as_deref(),
+                        user_agent: client.user_agent.as_deref(),
+                        succeeded: false,
+                        failure_reason: Some(FAILURE_PENDING),
+                    })
+                    .returning(login_attempt::id)
+                    .get_result(connection)?,
+            );
+        }
+        Ok(status)
+    })
+}
+
+/// Removes a pending attempt once its outcome was recorded with [`record_login_attempt`], or
+/// handed on to the second factor.
+pub fn discard_login_attempt(connection: &mut PgConnection, attempt_id: Uuid) -> QueryResult<()> {
+    diesel::delete(
+        login_attempt::table
+            .filter(login_attempt::id.eq(attempt_id))
+            .filter(login_attempt::failure_reason.eq(FAILURE_PENDING)),
+    )
+    .execute(connection)?;
+
+    Ok(())
+}
+
+/// Looks at failed attempts inside the lockout window for both the username and the client IP.
+/// Failures before the last successful login for the username are forgiven.
+fn check_login_throttle(
+    connection: &mut PgConnection,
+    config: &LoginConfig,
+    username: &str,
+    client: &ClientInfo,
+) -> QueryResult<LoginThrottleStatus> {
+    let window_start = Utc::now() - Duration::seconds(config.lockout_seconds);
+
+    let last_success = login_attempt::table
+        .filter(login_attempt::username.eq(username))
+        .filter(login_attempt::succeeded.eq(true))
+        .select(diesel::dsl::max(login_attempt::created_at))
+        .first::<Option<chrono::DateTime<Utc>>>(connection)?;
+
+    let username_since = last_success.map_or(window_start, |t| t.max(window_start));
+
+    let username_failures = login_attempt::table
+        .filter(login_attempt::username.eq(username))
+        .filter(login_attempt::succeeded.eq(false))
+        .filter(login_attempt::failure_reason.ne(FAILURE_LOCKED))
+        .filter(login_attempt::created_at.gt(username_since))
+        .count()
+        .get_result::<i64>(connection)?;
+
+    let ip_failures = match client.ip_address.as_deref() {
+        Some(ip) => login_attempt::table
+            .filter(login_attempt::ip_address.eq(ip))
+            .filter(login_attempt::succeeded.eq(false))
+            .filter(login_attempt::failure_reason.ne(FAILURE_LOCKED))
+            .filter(login_attempt::created_at.gt(window_start))
+            .count()
+            .get_result::<i64>(connection)?,
+        None => 0,
+    };
+
+    let locked =
+        username_failures >= config.max_failures || ip_failures >= config.max_failures_per_ip;
+
+    // 250ms after the first failure, doubling with every further one
+    let failures = username_failures.max(ip_failures);
+    let delay_milliseconds = if failures == 0 {
+        0
+    } else {
+        (250_i64 << (failures - 1).min(16)).min(MAX_DELAY_MILLISECONDS)
+    };
+
+    Ok(LoginThrottleStatus {
+        locked,
+        delay: std::time::Duration::from_millis(delay_milliseconds as u64),
+        attempt_id: None,
+    })
+}
+
+pub fn record_login_attempt(
+    connection: &mut PgConnection,
+    username: &str,
+    client: &ClientInfo,
+    failure_reason: Option<&str>,
+) -> QueryResult<()> {
+    diesel::insert_into(login_attempt::table)
+        .values(&InsertableLoginAttemptModel {
+            username,
+            ip_address: client.ip_address.as_deref(),
+            user_agent: client.user_agent.as_deref(),
+            succeeded: failure_reason.is_none(),
+            failure_reason,
+        })
+        .execute(connection)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Barrier;
+    use std::thread;
+
+    use super::*;
+    use crate::db::init_pool;
+    use crate::db::migrations::run_pending_migrations;
+
+    const ATTEMPTS: usize = 8;
+
+    /// Starts `ATTEMPTS` logins at once against the database in `DATABASE_URL`, one per
+    /// connection, and removes the recorded attempts afterwards.
+    fn parallel_attempts(
+        config: &LoginConfig,
+        client_for: impl Fn(usize) -> (String, ClientInfo) + Sync,
+    ) -> Vec<LoginThrottleStatus> {
+        let url = std::env::var("DATABASE_URL").expect("DATABASE_URL is not set");
+        let pool = init_pool(&url);
+        run_pending_migrations(&mut pool.get().unwrap()).unwrap();
+
+        let barrier = Barrier::new(ATTEMPTS);
+        let attempts: Vec<(String, ClientInfo)> = (0..ATTEMPTS).map(&client_for).collect();
+        let statuses = thread::scope(|scope| {
+            let handles: Vec<_> = attempts
+                .iter()
+                .map(|(username, client)| {
+                    let (pool, barrier) = (pool.clone(), &barrier);
+                    scope.spawn(move || {
+                        let connection = &mut pool.get().unwrap();
+                        barrier.wait();
+                        begin_login_attempt(connection, config, username, client).unwrap()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        let usernames: Vec<&String> = attempts.iter().map(|(username, _)| username).collect();
+        diesel::delete(login_attempt::table.filter(login_attempt::username.eq_any(usernames)))
+            .execute(&mut pool.get().unwrap())
+            .unwrap();
+        statuses
+    }
+
+    fn unique(prefix: &str) -> String {
+        format!("{}-{}", prefix, &Uuid::new_v4().simple().to_string()[..16])
+    }
+
+    #[test]
+    #[ignore = "needs a Postgres database in DATABASE_URL"]
+    fn parallel_attempts_count_each_other_per_username() {
+        let config = LoginConfig {
+            max_failures: 3,
+            ..LoginConfig::default()
+        };
+        let username = unique("throttle");
+
+        let statuses = parallel_attempts(&config, |_| {
+            let client = ClientInfo {
+                user_agent: None,
+                ip_address: None,
+            };
+            (username.clone(), client)
+        });
+
+        let mut delays: Vec<u128> = statuses
+            .iter()
+            .filter(|s| s.attempt_id.is_some())
+            .map(|s| s.delay.as_millis())
+            .collect();
+        delays.sort();
+        assert_eq!(delays, [0, 250, 500]);
+        assert_eq!(statuses.iter().filter(|s| s.locked).count(), ATTEMPTS - 3);
+    }
+
+    #[test]
+    #[ignore = "needs a Postgres database in DATABASE_URL"]
+    fn parallel_attempts_count_each_other_per_ip() {
+        let config = LoginConfig {
+            max_failures_per_ip: 2,
+            ..LoginConfig::default()
+        };
+        let ip = unique("ip");
+
+        let statuses = parallel_attempts(&config, |i| {
+            let client = ClientInfo {
+                user_agent: None,
+                ip_address: Some(ip.clone()),
+            };
+            (unique(&format!("throttle{}", i)), client)
+        });
+
+        assert_eq!(
+            statuses.iter().filter(|s| s.attempt_id.is_some()).count(),
+            2
+        );
+    }
+}
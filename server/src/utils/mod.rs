@@ -1,3 +1,13 @@
+pub mod access;
+pub mod audit;
 pub mod catchers;
 pub mod crypto;
+pub mod http;
+pub mod login_throttle;
+pub mod master_key;
+pub mod pagination;
 pub mod response;
+pub mod session;
+pub mod settings;
+pub mod totp;
+pub mod webhook;
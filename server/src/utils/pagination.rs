@@ -0,0 +1,45 @@
+use chrono::{DateTime, Utc};
+use data_encoding::BASE64URL_NOPAD;
+use uuid::Uuid;
+
+/// Page size of list endpoints when `limit` is not given, and the largest accepted one.
+pub const DEFAULT_PAGE_LIMIT: i64 = 50;
+pub const MAX_PAGE_LIMIT: i64 = 500;
+
+pub fn page_limit(limit: Option<i64>) -> i64 {
+    limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT)
+}
+
+pub fn parse_time_filter(value: Option<&str>) -> Result<Option<DateTime<Utc>>, ()> {
+    match value {
+        Some(v) => DateTime::parse_from_rfc3339(v)
+            .map(|t| Some(t.with_timezone(&Utc)))
+            .map_err(|_| ()),
+        None => Ok(None),
+    }
+}
+
+/// Position after the last row of a page ordered by `(created_at, id)` descending.
+/// Clients get it as an opaque string and pass it back unchanged.
+pub struct Cursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let raw = format!("{}.{}", self.created_at.timestamp_micros(), self.id);
+        BASE64URL_NOPAD.encode(raw.as_bytes())
+    }
+
+    pub fn decode(value: &str) -> Option<Self> {
+        let raw = BASE64URL_NOPAD.decode(value.as_bytes()).ok()?;
+        let raw = String::from_utf8(raw).ok()?;
+        let (micros, id) = raw.split_once('.')?;
+
+        Some(Cursor {
+            created_at: DateTime::from_timestamp_micros(micros.parse().ok()?)?,
+            id: Uuid::parse_str(id).ok()?,
+        })
+    }
+}
@@ -0,0 +1,55 @@
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use rocket::http::{Cookie, CookieJar, SameSite};
+use uuid::Uuid;
+
+use crate::config::SessionConfig;
+use crate::middlewares::client::ClientInfo;
+use crate::models::InsertableSessionModel;
+use crate::schema::session;
+use crate::utils::crypto::{generate_random_string, hash_token};
+
+pub const SESSION_COOKIE_NAME: &str = "gitmirrors_session_token";
+
+/// Creates a new session row and returns the raw token. Only its hash is stored.
+pub fn create_session(
+    connection: &mut PgConnection,
+    user_id: Uuid,
+    client: &ClientInfo,
+    config: &SessionConfig,
+) -> QueryResult<String> {
+    let token = generate_random_string(64);
+    let token_hash = hash_token(&token);
+
+    // opportunistically drop sessions that can no longer be used
+    diesel::delete(session::table.filter(session::expires_at.le(Utc::now())))
+        .execute(connection)?;
+
+    diesel::insert_into(session::table)
+        .values(&InsertableSessionModel {
+            user_id,
+            token_hash: &token_hash,
+            user_agent: client.user_agent.as_deref(),
+            ip_address: client.ip_address.as_deref(),
+            expires_at: Utc::now() + Duration::seconds(config.ttl_seconds),
+        })
+        .execute(connection)?;
+
+    Ok(token)
+}
+
+pub fn set_session_cookie(cookie_jar: &CookieJar<'_>, token: String, config: &SessionConfig) {
+    cookie_jar.add(
+        Cookie::build((SESSION_COOKIE_NAME, token))
+            .http_only(true)
+            .secure(config.cookie_secure)
+            .same_site(SameSite::Lax)
+            .path("/")
+            .max_age(rocket::time::Duration::seconds(config.ttl_seconds))
+            .build(),
+    );
+}
+
+pub fn remove_session_cookie(cookie_jar: &CookieJar<'_>) {
+    cookie_jar.remove(Cookie::build(SESSION_COOKIE_NAME).path("/").build());
+}
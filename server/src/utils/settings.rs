@@ -0,0 +1,61 @@
+use chrono::Utc;
+use diesel::prelude::*;
+
+use crate::schema::app_setting;
+
+/// When enabled, users without TOTP can only reach enrollment endpoints.
+pub const SETTING_REQUIRE_TOTP: &str = "require_totp";
+/// Repository logs older than this many days are pruned, 0 keeps them forever.
+pub const SETTING_LOG_RETENTION_DAYS: &str = "log_retention_days";
+/// Only the newest this many logs of each repository are kept, 0 keeps all of them.
+pub const SETTING_LOG_MAX_ROWS: &str = "log_max_rows";
+
+/// Healthy repositories this many seconds past their next due sync are reported as stale, 0 disables it.
+pub const SETTING_STALE_AFTER_SECONDS: &str = "stale_after_seconds";
+
+pub const DEFAULT_LOG_RETENTION_DAYS: i32 = 90;
+pub const DEFAULT_LOG_MAX_ROWS: i32 = 10_000;
+pub const DEFAULT_STALE_AFTER_SECONDS: i32 = 24 * 60 * 60;
+
+fn get_setting(connection: &mut PgConnection, key: &str) -> QueryResult<Option<String>> {
+    app_setting::table
+        .filter(app_setting::key.eq(key))
+        .select(app_setting::value)
+        .first::<String>(connection)
+        .optional()
+}
+
+pub fn get_bool_setting(connection: &mut PgConnection, key: &str) -> QueryResult<bool> {
+    let value = get_setting(connection, key)?;
+
+    Ok(value.is_some_and(|v| v == "true"))
+}
+
+/// Unset or unparsable values fall back to `default`.
+pub fn get_int_setting(connection: &mut PgConnection, key: &str, default: i32) -> QueryResult<i32> {
+    let value = get_setting(connection, key)?;
+
+    Ok(value.and_then(|v| v.parse().ok()).unwrap_or(default))
+}
+
+pub fn set_bool_setting(connection: &mut PgConnection, key: &str, value: bool) -> QueryResult<()> {
+    set_setting(connection, key, if value { "true" } else { "false" })
+}
+
+pub fn set_int_setting(connection: &mut PgConnection, key: &str, value: i32) -> QueryResult<()> {
+    set_setting(connection, key, &value.to_string())
+}
+
+fn set_setting(connection: &mut PgConnection, key: &str, value: &str) -> QueryResult<()> {
+    diesel::insert_into(app_setting::table)
+        .values((app_setting::key.eq(key), app_setting::value.eq(value)))
+        .on_conflict(app_setting::key)
+        .do_update()
+        .set((
+            app_setting::value.eq(value),
+            app_setting::updated_at.eq(Utc::now()),
+        ))
+        .execute(connection)?;
+
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use rocket::http::RawStr;
+use sha1::Sha1;
+
+pub const TOTP_ISSUER: &str = "GitMirrors";
+const TOTP_PERIOD_SECONDS: i64 = 30;
+const TOTP_DIGITS: u32 = 6;
+/// Accept codes from one step before and after the current one to tolerate clock drift.
+const TOTP_ALLOWED_SKEW_STEPS: i64 = 1;
+
+/// 160-bit secret, base32 encoded as expected by authenticator apps.
+pub fn generate_totp_secret() -> String {
+    let mut secret = [0u8; 20];
+    rand::rng().fill_bytes(&mut secret);
+    BASE32_NOPAD.encode(&secret)
+}
+
+pub fn totp_otpauth_uri(secret: &str, username: &str) -> String {
+    let label = format!("{}:{}", TOTP_ISSUER, username);
+
+    format!(
+        "otpauth://totp/{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        RawStr::new(&label).percent_encode(),
+        secret,
+        RawStr::new(TOTP_ISSUER).percent_encode(),
+        TOTP_DIGITS,
+        TOTP_PERIOD_SECONDS
+    )
+}
+
+fn hotp(secret: &[u8], counter: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    // dynamic truncation, RFC 4226 section 5.3
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+
+    binary % 10u32.pow(TOTP_DIGITS)
+}
+
+/// Verifies a code at `unix_time` and returns the matched time step, so callers can reject
+/// a code that was already used.
+pub fn verify_totp(secret: &str, code: &str, unix_time: i64) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != TOTP_DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let code = code.parse::<u32>().ok()?;
+    let secret = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+
+    let current_step = unix_time / TOTP_PERIOD_SECONDS;
+    (-TOTP_ALLOWED_SKEW_STEPS..=TOTP_ALLOWED_SKEW_STEPS)
+        .map(|skew| current_step + skew)
+        .find(|step| *step >= 0 && hotp(&secret, *step as u64) == code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The SHA-1 seed of RFC 6238 appendix B, `12345678901234567890`.
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn matches_rfc_6238_vectors() {
+        // the RFC lists 8-digit codes, 6-digit codes are their last six digits
+        let vectors = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ];
+
+        for (time, code) in vectors {
+            assert_eq!(
+                verify_totp(RFC_SECRET, code, time),
+                Some(time / TOTP_PERIOD_SECONDS),
+                "code at {}",
+                time
+            );
+        }
+    }
+
+    #[test]
+    fn tolerates_one_step_of_clock_drift() {
+        assert_eq!(verify_totp(RFC_SECRET, "287082", 59 + 30), Some(1));
+        assert_eq!(
+            verify_totp(RFC_SECRET, "081804", 1111111109 - 30),
+            Some(37037036)
+        );
+        assert_eq!(verify_totp(RFC_SECRET, "287082", 59 + 60), None);
+    }
+
+    #[test]
+    fn rejects_malformed_codes() {
+        assert_eq!(verify_totp(RFC_SECRET, "28708", 59), None);
+        assert_eq!(verify_totp(RFC_SECRET, "2870822", 59), None);
+        assert_eq!(verify_totp(RFC_SECRET, "28708a", 59), None);
+        assert_eq!(verify_totp(RFC_SECRET, " 287082 ", 59), Some(1));
+        assert_eq!(verify_totp("not base32!", "287082", 59), None);
+    }
+
+    #[test]
+    fn generates_secrets_authenticators_accept() {
+        let secret = generate_totp_secret();
+        assert_eq!(BASE32_NOPAD.decode(secret.as_bytes()).unwrap().len(), 20);
+
+        let uri = totp_otpauth_uri(&secret, "alice");
+        assert!(uri.starts_with(&format!(
+            "otpauth://totp/GitMirrors:alice?secret={}&",
+            secret
+        )));
+        assert!(uri.contains("&issuer=GitMirrors&"));
+        assert!(uri.contains("&digits=6&period=30"));
+    }
+}
@@ -0,0 +1,155 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::middlewares::webhook::WebhookProvider;
+use crate::utils::crypto::constant_time_eq;
+
+pub const DELIVERY_ACCEPTED: &str = "accepted";
+pub const DELIVERY_IGNORED: &str = "ignored";
+pub const DELIVERY_REJECTED: &str = "rejected";
+
+/// Checks the delivery against the repository's webhook secret. GitHub and Gitea sign
+/// the body with HMAC-SHA256, GitLab sends the secret itself.
+pub fn verify_webhook_signature(
+    provider: WebhookProvider,
+    signature: &str,
+    secret: &str,
+    body: &[u8],
+) -> bool {
+    let hex_signature = match provider {
+        WebhookProvider::GitHub => match signature.strip_prefix("sha256=") {
+            Some(hex_signature) => hex_signature,
+            None => return false,
+        },
+        WebhookProvider::Gitea => signature,
+        WebhookProvider::GitLab => return constant_time_eq(signature, secret),
+    };
+
+    let Ok(expected) = hex::decode(hex_signature) else {
+        return false;
+    };
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+/// Events that change refs and so should refresh the mirror.
+pub fn is_ref_change_event(provider: WebhookProvider, event: &str) -> bool {
+    match provider {
+        WebhookProvider::GitHub | WebhookProvider::Gitea => {
+            matches!(event, "push" | "create" | "delete")
+        }
+        WebhookProvider::GitLab => matches!(event, "Push Hook" | "Tag Push Hook"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // example from GitHub's "Validating webhook deliveries" documentation
+    const GITHUB_SECRET: &str = "It's a Secret to Everybody";
+    const GITHUB_BODY: &[u8] = b"Hello, World!";
+    const GITHUB_SIGNATURE: &str =
+        "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+    // RFC 4231 test case 2
+    const GITEA_SECRET: &str = "Jefe";
+    const GITEA_BODY: &[u8] = b"what do ya want for nothing?";
+    const GITEA_SIGNATURE: &str =
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843";
+
+    #[test]
+    fn accepts_github_signatures() {
+        assert!(verify_webhook_signature(
+            WebhookProvider::GitHub,
+            GITHUB_SIGNATURE,
+            GITHUB_SECRET,
+            GITHUB_BODY
+        ));
+    }
+
+    #[test]
+    fn rejects_github_signatures_without_prefix_or_for_another_body() {
+        let unprefixed = GITHUB_SIGNATURE.trim_start_matches("sha256=");
+        assert!(!verify_webhook_signature(
+            WebhookProvider::GitHub,
+            unprefixed,
+            GITHUB_SECRET,
+            GITHUB_BODY
+        ));
+        assert!(!verify_webhook_signature(
+            WebhookProvider::GitHub,
+            GITHUB_SIGNATURE,
+            GITHUB_SECRET,
+            b"Hello, World?"
+        ));
+        assert!(!verify_webhook_signature(
+            WebhookProvider::GitHub,
+            GITHUB_SIGNATURE,
+            "another secret",
+            GITHUB_BODY
+        ));
+    }
+
+    #[test]
+    fn accepts_gitea_signatures() {
+        assert!(verify_webhook_signature(
+            WebhookProvider::Gitea,
+            GITEA_SIGNATURE,
+            GITEA_SECRET,
+            GITEA_BODY
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_gitea_signatures() {
+        assert!(!verify_webhook_signature(
+            WebhookProvider::Gitea,
+            "not hex",
+            GITEA_SECRET,
+            GITEA_BODY
+        ));
+        assert!(!verify_webhook_signature(
+            WebhookProvider::Gitea,
+            &GITEA_SIGNATURE[..62],
+            GITEA_SECRET,
+            GITEA_BODY
+        ));
+    }
+
+    #[test]
+    fn compares_gitlab_tokens_with_the_secret() {
+        assert!(verify_webhook_signature(
+            WebhookProvider::GitLab,
+            "token",
+            "token",
+            b"{}"
+        ));
+        assert!(!verify_webhook_signature(
+            WebhookProvider::GitLab,
+            "token2",
+            "token",
+            b"{}"
+        ));
+        assert!(!verify_webhook_signature(
+            WebhookProvider::GitLab,
+            "",
+            "token",
+            b"{}"
+        ));
+    }
+
+    #[test]
+    fn only_ref_changes_trigger_syncs() {
+        assert!(is_ref_change_event(WebhookProvider::GitHub, "push"));
+        assert!(is_ref_change_event(WebhookProvider::Gitea, "delete"));
+        assert!(is_ref_change_event(
+            WebhookProvider::GitLab,
+            "Tag Push Hook"
+        ));
+        assert!(!is_ref_change_event(WebhookProvider::GitHub, "issues"));
+        assert!(!is_ref_change_event(WebhookProvider::GitLab, "push"));
+    }
+}
@@ -4,6 +4,9 @@ import type { IApiResponse } from "~/types/api";
 import type { Repository } from "~/types/repository";
 import type { RepositoryLog } from "~/types/repositoryLog";
 
+// largest page the server accepts
+const REPOSITORY_PAGE_LIMIT = 500;
+
 export const repositoryApi = (config: { baseUrl: string }) => {
   const axiosBase = axios.create({
     baseURL: config.baseUrl + "/repository",
@@ -17,9 +20,22 @@ export const repositoryApi = (config: { baseUrl: string }) => {
         repositories: Repository[];
       }>
     > {
-      const { data: payload } = await axiosBase.get("/");
+      // the list is paginated, so fetch pages until every repository is loaded
+      const repositories: Repository[] = [];
+      for (;;) {
+        const { data: payload } = await axiosBase.get<
+          IApiResponse<{ repositories: Repository[]; total: number }>
+        >("/", {
+          params: { limit: REPOSITORY_PAGE_LIMIT, offset: repositories.length },
+        });
 
-      return payload;
+        const page = payload?.data?.repositories ?? [];
+        repositories.push(...page);
+
+        if (page.length === 0 || repositories.length >= payload.data.total) {
+          return { ...payload, data: { repositories } };
+        }
+      }
     },
     async getRepository(
       id: string,
@@ -35,9 +51,11 @@ export const repositoryApi = (config: { baseUrl: string }) => {
     },
     async getRepositoryLogs(
       id: string,
+      limit: number,
       nuxtServerInitOptions?: NuxtServerInitOptions,
     ): Promise<IApiResponse<{ repositoryLogs: RepositoryLog[] }>> {
       const { data: payload } = await axiosBase.get(`/${id}/logs`, {
+        params: { limit },
         headers: { Cookie: nuxtServerInitOptions?.serverSideCookiesRaw },
       });
 
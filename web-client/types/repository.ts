@@ -4,9 +4,9 @@ export type Repository = {
   url: string;
   isEnabled: boolean;
   gitSource: string;
-  gitSourceSecretKey?: string;
+  hasSourceKey: boolean;
   gitTarget: string;
-  gitTargetSecretKey: string;
+  hasTargetKey: boolean;
   gitClonePeriodSeconds: number;
   lastCloneAt: Date;
   createdAt: Date;